@@ -0,0 +1,56 @@
+//! Measures the cost of allocating a fresh captures map for every line versus reusing the storage
+//! for captures across lines.
+//!
+//! ```text
+//! cargo bench --bench captures
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use grits::Transformer;
+
+const LINES: usize = 1_000_000;
+
+/// Every line has several matches for each capture name so that each line would otherwise need to
+/// allocate and grow a vector per capture name.
+fn lines() -> Vec<String> {
+    (0..LINES)
+        .map(|i| {
+            format!(
+                "ts={i} ip=10.0.0.{} ip=127.0.0.1 ip=192.168.0.1 user=foo user=bar",
+                i % 255
+            )
+        })
+        .collect()
+}
+
+fn bench_captures(c: &mut Criterion) {
+    let lines = lines();
+    let transformer = Transformer::new(
+        &[r"ts=(?<ts>\d+)", r"ip=(?<ip>[^ ]+)", r"user=(?<user>\w+)"],
+        &["{ts} {ip[*]} {user[-1]}"],
+    )
+    .unwrap();
+
+    let mut group = c.benchmark_group("captures over 1M lines");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(LINES as u64));
+
+    group.bench_function("fresh captures map per line", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(transformer.transform_line(line));
+            }
+        })
+    });
+
+    group.bench_function("reused captures buffer", |b| {
+        b.iter(|| {
+            for out in transformer.transform_lines(&lines) {
+                black_box(out);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_captures);
+criterion_main!(benches);
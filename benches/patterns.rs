@@ -0,0 +1,57 @@
+//! Measures the cost of applying many patterns to many lines with and without the prefilter that
+//! determines which patterns match a line before extracting captures.
+//!
+//! ```text
+//! cargo bench --bench patterns
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use grits::Transformer;
+
+const PATTERNS: usize = 50;
+const LINES: usize = 1_000_000;
+
+fn patterns() -> Vec<String> {
+    (0..PATTERNS).map(|i| format!(r"key{i}=(?<key{i}>\w+)")).collect()
+}
+
+/// Each line matches at most a single pattern, as is typical when patterns target different kinds
+/// of log lines.
+fn lines() -> Vec<String> {
+    (0..LINES)
+        .map(|i| {
+            format!(
+                "ts={i} level=info key{}=value{i} msg=\"hello world\"",
+                i % (PATTERNS * 2)
+            )
+        })
+        .collect()
+}
+
+fn bench_patterns(c: &mut Criterion) {
+    let patterns = patterns();
+    let lines = lines();
+    let templates = ["{key0 || key1 || key2} {key49}"];
+
+    let mut group = c.benchmark_group("50 patterns over 1M lines");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(LINES as u64));
+
+    for prefilter in [true, false] {
+        let transformer = Transformer::new(&patterns, &templates)
+            .unwrap()
+            .with_prefilter(prefilter);
+
+        let id = if prefilter { "prefilter" } else { "no prefilter" };
+        group.bench_function(id, |b| {
+            b.iter(|| {
+                for line in &lines {
+                    black_box(transformer.transform_line(line));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_patterns);
+criterion_main!(benches);
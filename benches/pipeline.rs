@@ -0,0 +1,93 @@
+//! Measures the end-to-end cost of matching patterns against lines and rendering templates with the
+//! resulting captures, which is the per-line hot path of the application. Serves as a guardrail
+//! against regressions of the whole pipeline rather than any one stage of it.
+//!
+//! ```text
+//! cargo bench --bench pipeline
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use grits::Transformer;
+
+const LINES: usize = 1_000_000;
+
+/// An in-memory corpus of logfmt-style lines with a mix of levels, hosts, and latencies.
+fn lines() -> Vec<String> {
+    const LEVELS: [&str; 4] = ["DEBUG", "INFO", "WARN", "ERROR"];
+
+    (0..LINES)
+        .map(|i| {
+            format!(
+                "ts=2024-01-01T00:00:{:02} level={} host=web-{} ip=10.0.{}.{} method=GET path=/api/v1/items/{i} \
+                 status={} ms={}.{} user=user{} msg=\"request handled\"",
+                i % 60,
+                LEVELS[i % LEVELS.len()],
+                i % 8,
+                i % 16,
+                i % 255,
+                if i % 10 == 0 { 500 } else { 200 },
+                i % 900,
+                i % 10,
+                i % 100,
+            )
+        })
+        .collect()
+}
+
+/// A single pattern and a template with a couple of plain anchors.
+fn simple() -> Transformer {
+    Transformer::new(&[r"level=(?<lvl>\w+).*msg=(?<msg>.*)"], &["[{lvl}] {msg}"]).unwrap()
+}
+
+/// A pattern per field, some of which never match, and several templates whose anchors carry
+/// attributes, indexes, and defaults.
+fn complex() -> Transformer {
+    let patterns = [
+        r"ts=(?<ts>\S+)",
+        r"level=(?<lvl>\w+)",
+        r"host=(?<host>\S+)",
+        r"ip=(?<ip>[\d.]+)",
+        r"method=(?<method>[A-Z]+)",
+        r"path=(?<path>\S+)",
+        r"status=(?<status>\d+)",
+        r"ms=(?<ms>[\d.]+)",
+        r"user=(?<user>\w+)",
+        r#"msg="(?<msg>[^"]*)""#,
+        r"trace_id=(?<trace>[0-9a-f]+)",
+        r"span_id=(?<span>[0-9a-f]+)",
+    ];
+    let templates = [
+        "{(blue):ts}",
+        "{(red|bold|lalign(5)):lvl}",
+        "{(cyan):host}/{ip[-1]}",
+        "{(bold):method} {(underlined|hash):path}",
+        "{(yellow):status} {(ralign(7)):ms || \"-\"}ms",
+        "{(magenta):user} {trace || span || \"untraced\"}",
+        "{(reverse):msg}",
+    ];
+    Transformer::new(&patterns, &templates).unwrap().with_separator(" ")
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let lines = lines();
+
+    let mut group = c.benchmark_group("pipeline over 1M lines");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(LINES as u64));
+
+    for (id, transformer) in [
+        ("few patterns, simple template", simple()),
+        ("many patterns, complex templates", complex()),
+    ] {
+        group.bench_function(id, |b| {
+            b.iter(|| {
+                for out in transformer.transform_lines(&lines) {
+                    black_box(out);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Measures the cost of transforming lines with a template whose anchors reference a handful of
+//! captures many times over, as is the case for wide tables that repeat captures with different
+//! attributes. The pattern is kept cheap so that rendering the template dominates.
+//!
+//! ```text
+//! cargo bench --bench templates
+//! ```
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use grits::Transformer;
+
+const LINES: usize = 1_000_000;
+
+/// A template of 32 anchors that reference 4 distinct captures, some of them indexed.
+fn template() -> String {
+    let anchors = ["{ts}", "{(red):lvl}", "{ip[-1]}", "{(bold|lalign(8)):user}"];
+    (0..32)
+        .map(|i| anchors[i % anchors.len()])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bench_templates(c: &mut Criterion) {
+    let patterns = [r"^(?<ts>\d+) (?<lvl>\w+) (?<ip>\S+)", r"(?<ip>\S+) (?<user>\w+)$"];
+    let transformer = Transformer::new(&patterns, &[template()]).unwrap();
+
+    let lines = (0..LINES)
+        .map(|i| format!("{i} INFO 10.0.0.1 127.0.0.1 foo"))
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("32 anchors over 1M lines");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(LINES as u64));
+
+    group.bench_function("transform", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(transformer.transform_line(line));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_templates);
+criterion_main!(benches);
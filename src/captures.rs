@@ -0,0 +1,177 @@
+use std::{borrow::Cow, collections::HashMap, ops::Range};
+
+/// The matches of each capture name that templates are rendered with and that filters such as
+/// '--where' inspect.
+pub trait Captures {
+    /// The matches of the capture `name` in the order they were matched, or `None` if `name` isn't
+    /// a capture name at all.
+    fn get(&self, name: &str) -> Option<Matches<'_>>;
+
+    /// Every capture name along with its matches, in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, Matches<'_>)> + '_>;
+
+    /// Copies every capture name along with its matches out of the captures.
+    fn to_map(&self) -> HashMap<String, Vec<String>> {
+        self.iter()
+            .map(|(name, vals)| (name.to_string(), vals.iter().map(Cow::into_owned).collect()))
+            .collect()
+    }
+}
+
+/// Matches that are text in their own right, keyed by capture name.
+impl Captures for HashMap<&str, Vec<&str>> {
+    fn get(&self, name: &str) -> Option<Matches<'_>> {
+        HashMap::get(self, name).map(|vals| Matches::Values(vals))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, Matches<'_>)> + '_> {
+        Box::new(HashMap::iter(self).map(|(name, vals)| (*name, Matches::Values(vals))))
+    }
+}
+
+/// A single match of a capture name. Matches are recorded as offsets into the line they were found
+/// in rather than borrowing from it so that they can be stored independently of any one line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureHit {
+    /// The bytes of the line from `start` up to `end`.
+    Text { start: usize, end: usize },
+    /// A byte offset, e.g. where the line begins within its input.
+    Offset(u64),
+}
+
+impl CaptureHit {
+    /// The value of the match within `line`, the line that it was found in. Text that is exactly
+    /// equal to a key of `replacements` is replaced by the corresponding value.
+    pub fn text<'c>(self, line: &'c str, replacements: &'c HashMap<String, String>) -> Cow<'c, str> {
+        match self {
+            Self::Text { start, end } => {
+                let text = &line[start..end];
+                Cow::Borrowed(replacements.get(text).map_or(text, String::as_str))
+            }
+            Self::Offset(offset) => Cow::Owned(offset.to_string()),
+        }
+    }
+}
+
+/// The captures of a single line that were recorded as [CaptureHit]s.
+#[derive(Debug, Clone, Copy)]
+pub struct LineCaptures<'c> {
+    hits: &'c HashMap<&'c str, Vec<CaptureHit>>,
+    line: &'c str,
+    replacements: &'c HashMap<String, String>,
+}
+
+impl<'c> LineCaptures<'c> {
+    /// The `hits` of each capture name that were found in `line`, whose text is replaced according
+    /// to `replacements` when read.
+    pub fn new(
+        hits: &'c HashMap<&'c str, Vec<CaptureHit>>,
+        line: &'c str,
+        replacements: &'c HashMap<String, String>,
+    ) -> Self {
+        Self {
+            hits,
+            line,
+            replacements,
+        }
+    }
+
+    /// The matches of `hits`, which were found in the line.
+    fn matches(&self, hits: &'c [CaptureHit]) -> Matches<'c> {
+        Matches::Hits {
+            hits,
+            line: self.line,
+            replacements: self.replacements,
+        }
+    }
+}
+
+impl Captures for LineCaptures<'_> {
+    fn get(&self, name: &str) -> Option<Matches<'_>> {
+        self.hits.get(name).map(|hits| self.matches(hits))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&str, Matches<'_>)> + '_> {
+        Box::new(self.hits.iter().map(|(name, hits)| (*name, self.matches(hits))))
+    }
+}
+
+/// The matches of a single capture name in the order they were matched.
+#[derive(Debug, Clone, Copy)]
+pub enum Matches<'c> {
+    /// Matches that are text in their own right.
+    Values(&'c [&'c str]),
+    /// Matches that were found in `line`, see [CaptureHit::text].
+    Hits {
+        hits: &'c [CaptureHit],
+        line: &'c str,
+        replacements: &'c HashMap<String, String>,
+    },
+}
+
+impl<'c> Matches<'c> {
+    /// The number of matches.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Values(vals) => vals.len(),
+            Self::Hits { hits, .. } => hits.len(),
+        }
+    }
+
+    /// Whether there aren't any matches.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `i`th match if there is one.
+    pub fn get(&self, i: usize) -> Option<Cow<'c, str>> {
+        match *self {
+            Self::Values(vals) => vals.get(i).map(|val| Cow::Borrowed(*val)),
+            Self::Hits {
+                hits,
+                line,
+                replacements,
+            } => hits.get(i).map(|hit| hit.text(line, replacements)),
+        }
+    }
+
+    /// The first match if there is one.
+    pub fn first(&self) -> Option<Cow<'c, str>> {
+        self.get(0)
+    }
+
+    /// The matches within `range`. Panics if `range` is out of bounds like slicing does.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        match *self {
+            Self::Values(vals) => Self::Values(&vals[range]),
+            Self::Hits {
+                hits,
+                line,
+                replacements,
+            } => Self::Hits {
+                hits: &hits[range],
+                line,
+                replacements,
+            },
+        }
+    }
+
+    /// Every match in order.
+    pub fn iter(&self) -> impl Iterator<Item = Cow<'c, str>> {
+        let matches = *self;
+        (0..self.len()).filter_map(move |i| matches.get(i))
+    }
+
+    /// Joins every match with `separator` in between.
+    pub fn join(&self, separator: &str) -> String {
+        let mut out = String::new();
+
+        for (i, val) in self.iter().enumerate() {
+            if i > 0 {
+                out.push_str(separator);
+            }
+            out.push_str(&val);
+        }
+        out
+    }
+}
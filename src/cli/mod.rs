@@ -0,0 +1,965 @@
+use crate::{
+    config::Config,
+    scanner::InputFormat,
+    template::{
+        self,
+        parse::rules::{Rules, ANCHOR_CHARSET},
+        ANCHOR_CLOSE, ANCHOR_OPEN, ESCAPE,
+    },
+    tty::{Buffering, BLOCK_SIZE},
+};
+use anyhow::{format_err, Result};
+use clap::{crate_authors, crate_version, ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use clap_complete::Shell;
+use encoding_rs::Encoding;
+use std::{env, fmt, str::FromStr, time::Duration};
+
+#[cfg(test)]
+mod test;
+
+/// Arguments that context lines can't be combined with as they either don't process input line by
+/// line or don't preserve the order of output.
+const CONTEXT_CONFLICTS: [&str; 9] = [
+    "multiline",
+    "passthrough",
+    "jobs",
+    "unique",
+    "unique_count",
+    "sort",
+    "sort_reverse",
+    "sort_by",
+    "route",
+];
+
+#[derive(Parser, Debug)]
+#[command(
+    author = crate_authors!(),
+    version = crate_version!(),
+    about = "
+A text line processor that applies regular expressions with named captures to input lines
+and transforms them using a user-generated template. See the long help '--help' for further
+details and examples or visit the 'https://github.com/solidiquis/grits' repository.",
+    long_about = include_str!("../../docs/help.md"),
+)]
+pub struct Cli {
+    /// A regular expression with named captures. Can be specified multiple times.
+    #[arg(short, long)]
+    pub pattern: Vec<String>,
+
+    /// A regular expression like '-p, --pattern' that is given a NAME, e.g.
+    /// 'req=status=(?<code>\d+)', so that templates can refer to its captures specifically as
+    /// '{NAME:capture}'. Can be specified multiple times. See long '--help'.
+    #[arg(long, value_name = "NAME=PATTERN", value_parser = parse_named_pattern)]
+    pub named_pattern: Vec<NamedPattern>,
+
+    /// Path to a file containing regular expressions, one per line. Blank lines and lines starting
+    /// with '#' are ignored. Patterns from the file are applied after those specified with
+    /// '-p, --pattern'.
+    #[arg(long)]
+    pub pattern_file: Option<String>,
+
+    /// Match all patterns case-insensitively. Inline flags such as '(?-i)' within a pattern take
+    /// precedence.
+    #[arg(short, long)]
+    pub ignore_case: bool,
+
+    /// Ignore whitespace within all patterns and treat '#' as the start of a comment that extends
+    /// to the end of the line so that long patterns can be laid out readably. Patterns of
+    /// '--pattern-file' may then span several lines. See long '--help'.
+    #[arg(long)]
+    pub verbose_regex: bool,
+
+    /// Read each input in its entirety rather than line by line so that patterns can match across
+    /// line boundaries. Each match is transformed separately. See long '--help'.
+    #[arg(long, conflicts_with_all = ["field_separator", "columns", "jobs"])]
+    pub multiline: bool,
+
+    /// A regular expression used to split each line into fields which templates may reference
+    /// by position, e.g. '{1}' for the first field and '{0}' for the entire line. Can be combined
+    /// with patterns. See long '--help'.
+    #[arg(short = 'F', long)]
+    pub field_separator: Option<String>,
+
+    /// Comma-separated character ranges that slice each line of fixed-width input into columns
+    /// which templates may reference by position, e.g. '{0}' for the first column. Ranges are
+    /// inclusive and may be open-ended, e.g. '0-9,10-19,20-'. See long '--help'.
+    #[arg(long, value_name = "RANGES", value_delimiter = ',', value_parser = parse_column, conflicts_with = "field_separator")]
+    pub columns: Vec<Column>,
+
+    /// Replace capture values that are exactly KEY with VALUE, e.g. 'WARNING=WARN', before they're
+    /// templated. Applies to every capture ahead of the attributes of anchors. Can be specified
+    /// multiple times. See long '--help'.
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_replacement)]
+    pub replace_map: Vec<Replacement>,
+
+    /// Path to a file containing replacements as in '--replace-map', one per line. Blank lines and
+    /// lines starting with '#' are ignored. Replacements specified with '--replace-map' take
+    /// precedence over those of the file.
+    #[arg(long)]
+    pub replace_map_file: Option<String>,
+
+    /// A template string that defines how to transform a line input using
+    /// times. Can be specified multiple times. See long '--help'.
+    #[arg(short, long, group = "tmpl")]
+    pub template: Vec<String>,
+
+    /// Path to a file containing templates, one per line. Blank lines are ignored. Templates from
+    /// the file are applied after those specified with '-t, --template'.
+    #[arg(long)]
+    pub template_file: Option<String>,
+
+    /// A template to render in place of all others for lines that satisfy a condition, as in
+    /// '--where', e.g. 'lvl=ERROR:{(red):msg}'. Can be specified multiple times in which case the
+    /// first one whose condition is satisfied applies. Lines that don't satisfy any of them are
+    /// rendered with '-t, --template' as usual. See long '--help'.
+    #[arg(long, value_name = "CONDITION:TEMPLATE", value_parser = parse_template_when, conflicts_with = "only")]
+    pub template_when: Vec<TemplateWhen>,
+
+    /// Define a named list of attributes, e.g. 'error=red|bold', that templates can reference
+    /// amongst their attributes as '@error'. Can be specified multiple times and definitions may
+    /// reference one another.
+    #[arg(long, value_name = "NAME=ATTRIBUTES")]
+    pub define: Vec<String>,
+
+    /// The character that causes the character following it in a template to be treated literally.
+    /// Useful when templates contain many backslashes. May not be alphanumeric, whitespace, or a
+    /// character with special meaning within templates.
+    #[arg(long, value_name = "CHAR", default_value_t = ESCAPE, value_parser = parse_escape_char)]
+    pub escape_char: char,
+
+    /// The character that begins anchors within templates. Useful when templates contain many
+    /// braces such as JSON. May be the same as '--anchor-close'. See long '--help'.
+    #[arg(long, value_name = "CHAR", default_value_t = ANCHOR_OPEN, value_parser = parse_anchor_delimiter)]
+    pub anchor_open: char,
+
+    /// The character that ends anchors within templates. See '--anchor-open'.
+    #[arg(long, value_name = "CHAR", default_value_t = ANCHOR_CLOSE, value_parser = parse_anchor_delimiter)]
+    pub anchor_close: char,
+
+    /// The characters allowed in anchor names, written as the contents of a regular expression
+    /// bracket expression. Names can't begin with '.' or '-'. See long '--help'.
+    #[arg(long, value_name = "CHARS", default_value = ANCHOR_CHARSET, value_parser = parse_anchor_charset)]
+    pub anchor_charset: String,
+
+    /// Output only the matches of the given capture name without a template, like 'grep -o'. If the
+    /// capture matches multiple times on the same line, each match is written on its own line.
+    #[arg(long, value_name = "CAPTURE", conflicts_with_all = ["template", "template_file", "output"])]
+    pub only: Option<String>,
+
+    /// Separator used to join results of transforming each template if multiple are specified. Can
+    /// be specified multiple times in which case the Nth separator is placed between the Nth and
+    /// following template, with the last separator used for any remaining templates. The escapes
+    /// '\n', '\t', '\r', '\0', and '\\' are supported.
+    #[arg(short, long, value_parser = parse_escaped)]
+    pub separator: Vec<String>,
+
+    /// Treat the output of each template as a column and pad every column to the width of its widest
+    /// value amongst all output, like 'column -t'. Columns are joined by '-s, --separator' or by two
+    /// spaces if not provided. All output is held in memory. See long '--help'.
+    #[arg(long, conflicts_with = "only")]
+    pub tab_align: bool,
+
+    /// Input files. A file of '-' reads standard input at that position.
+    pub files: Vec<String>,
+
+    /// Write a header such as '==> app.log <==' before the output of each input file, like 'tail',
+    /// separated from the output of the preceding file by a blank line. Files without output don't
+    /// get a header. See long '--help'.
+    #[arg(long, overrides_with = "no_filename", conflicts_with_all = ["sort", "sort_reverse", "sort_by"])]
+    pub with_filename: bool,
+
+    /// Don't write a header before the output of each input file, which is the default. Overrides
+    /// '--with-filename'.
+    #[arg(long, overrides_with = "with_filename")]
+    pub no_filename: bool,
+
+    /// Path to a file listing input files, one per line, or '-' to read the list from standard
+    /// input in which case input must come from the listed files. Listed files are processed after
+    /// those provided as arguments.
+    #[arg(long, value_name = "PATH")]
+    pub files_from: Option<String>,
+
+    /// Omit the newline, or the record separator, after the final output line. Output lines are
+    /// otherwise separated as usual. See long '--help'.
+    #[arg(long)]
+    pub no_trailing_newline: bool,
+
+    /// Input records are separated by NUL bytes rather than newlines, such as the output of
+    /// 'find -print0'. Output records are separated by NUL bytes as well.
+    #[arg(short = '0', long)]
+    pub null_data: bool,
+
+    /// A single ASCII character that separates input records rather than a newline. Output records
+    /// are separated by the same character. The same escapes as '-s, --separator' are supported.
+    #[arg(long, conflicts_with = "null_data", value_parser = parse_record_separator)]
+    pub record_separator: Option<u8>,
+
+    /// Terminate output records with NUL bytes rather than newlines or the record separator
+    /// regardless of how input records are separated, e.g. for 'xargs -0'.
+    #[arg(long)]
+    pub null_output: bool,
+
+    /// Keep the carriage return at the end of CRLF-terminated input lines rather than removing it.
+    /// Has no effect on input read as a whole with '--multiline'.
+    #[arg(long)]
+    pub keep_cr: bool,
+
+    /// The encoding of the input, such as 'latin1' or 'shift_jis', whose lines are transcoded into
+    /// UTF-8. Any WHATWG encoding label of an ASCII-compatible encoding is accepted, which excludes
+    /// UTF-16. Defaults to UTF-8.
+    #[arg(long, value_name = "LABEL", value_parser = parse_encoding)]
+    pub input_encoding: Option<&'static Encoding>,
+
+    /// Number of input files to process concurrently. Output is written in the order the files are
+    /// provided.
+    #[arg(short, long, default_value_t = 1)]
+    pub jobs: usize,
+
+    /// Comma-separated capture names that must have a match for a given input line to be
+    /// processed; otherwise it is ignored.
+    #[arg(short, long, group = "req")]
+    pub require: Option<String>,
+
+    /// Modify '-r, --require' to require matching on all specified capture names or any.
+    #[arg(long, requires = "req", default_value_t = RequireMode::default())]
+    pub require_mode: RequireMode,
+
+    /// Only process lines on which the first match of a capture compares to a value as specified,
+    /// e.g. 'lvl=ERROR', 'status!=200', or 'latency>100'. Supported operators are '=', '!=', '>',
+    /// '<', '>=', and '<='. Values are compared as numbers if both sides are numbers and as strings
+    /// otherwise. A capture without a match never satisfies the condition. Can be specified
+    /// multiple times in which case all conditions must be satisfied.
+    #[arg(long = "where", value_name = "CONDITION", value_parser = parse_condition)]
+    pub conditions: Vec<Condition>,
+
+    /// Also write up to N input lines preceding each line that produces output, unmodified, like
+    /// 'grep -B'. Groups of lines that aren't contiguous are separated by '--'.
+    #[arg(short = 'B', long, value_name = "N", conflicts_with_all = CONTEXT_CONFLICTS)]
+    pub before_context: Option<usize>,
+
+    /// Also write up to N input lines following each line that produces output, unmodified, like
+    /// 'grep -A'. Groups of lines that aren't contiguous are separated by '--'.
+    #[arg(short = 'A', long, value_name = "N", conflicts_with_all = CONTEXT_CONFLICTS)]
+    pub after_context: Option<usize>,
+
+    /// Like both '-B, --before-context' and '-A, --after-context' which take precedence if
+    /// provided.
+    #[arg(short = 'C', long, value_name = "N", conflicts_with_all = CONTEXT_CONFLICTS)]
+    pub context: Option<usize>,
+
+    /// Write the output of lines that satisfy a condition, as in '--where', to the given stream
+    /// rather than stdout, e.g. 'lvl=ERROR:stderr'. Can be specified multiple times in which case
+    /// the first route whose condition is satisfied applies. Supported streams are 'stdout' and
+    /// 'stderr'.
+    #[arg(long, value_name = "CONDITION:STREAM", value_parser = parse_route)]
+    pub route: Vec<Route>,
+
+    /// Write lines that don't have any captures, that don't satisfy '-r, --require', or whose
+    /// transformation produces no output unchanged rather than omitting them.
+    #[arg(long, conflicts_with = "multiline")]
+    pub passthrough: bool,
+
+    /// Suppress output that is identical to the output immediately preceding it, like 'uniq'.
+    #[arg(long)]
+    pub unique: bool,
+
+    /// Like '--unique' but prefixes output that was repeated with the number of times it occurred
+    /// in a row, e.g. '3× '. Output is written once a different output follows it.
+    #[arg(long)]
+    pub unique_count: bool,
+
+    /// Write output in sorted order once all input has been processed rather than as it is
+    /// produced. All output is held in memory. See long '--help'.
+    #[arg(long)]
+    pub sort: bool,
+
+    /// Like '--sort' but in reverse order.
+    #[arg(long)]
+    pub sort_reverse: bool,
+
+    /// Like '--sort' but order output by the first match of the given capture name rather than by
+    /// the output itself. Output without a match for the capture is ordered first. Can be combined
+    /// with '--sort-reverse'.
+    #[arg(long, value_name = "CAPTURE")]
+    pub sort_by: Option<String>,
+
+    /// Once all input has been processed, write a summary to stderr of the number of lines read,
+    /// emitted, and filtered out by '-r, --require' or '--where', as well as the number of lines on which each
+    /// capture matched.
+    #[arg(long)]
+    pub stats: bool,
+
+    /// Format of the output. 'template' renders the provided templates whereas the other formats
+    /// serialize the captures of each processed line. See long '--help'.
+    #[arg(long, default_value_t = OutputFormat::default())]
+    pub output: OutputFormat,
+
+    /// Treat templates that reference captures that don't appear in any pattern as an error rather
+    /// than a warning. Also fail if an anchor that isn't required has neither a match nor a default
+    /// value rather than interpolating an empty string.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Exit successfully without output rather than failing if none of the patterns contain named
+    /// capture groups, and don't write warnings to stderr.
+    #[arg(short, long)]
+    pub quiet: bool,
+
+    /// Rather than processing any input, print how each template and pattern was parsed and exit.
+    /// This includes the name, index, defaults, and attributes of every anchor and the capture
+    /// groups of every pattern.
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Rather than processing any input, print the name of every named capture group amongst the
+    /// patterns in sorted order along with the patterns it appears in, then exit.
+    #[arg(long)]
+    pub list_captures: bool,
+
+    /// Rather than producing output, read the first N lines of input, 5 by default, and print each
+    /// along with the values of every capture and the output rendered from them, then exit.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "5", conflicts_with = "multiline")]
+    pub template_test: Option<usize>,
+
+    /// Write output to the file at PATH rather than stdout, truncating it unless '--append' is
+    /// provided. A PATH of '-' stands for stdout. Output written to a file is only colorized if
+    /// '--color always' is provided.
+    #[arg(long, value_name = "PATH")]
+    pub output_file: Option<String>,
+
+    /// Append output to the end of the file provided to '--output-file' rather than truncating it.
+    #[arg(long, requires = "output_file")]
+    pub append: bool,
+
+    /// Force output to be line-buffered. By default, output is line buffered when stdout is a
+    /// terminal and block-buffered otherwise.
+    #[arg(long)]
+    pub line_buffered: bool,
+
+    /// Flush output after every line rather than leaving it to stdout. This guarantees that each
+    /// line is written as soon as it is produced at the cost of a write per line. See long '--help'.
+    #[arg(long, conflicts_with = "buffer_size")]
+    pub unbuffered: bool,
+
+    /// When output is block-buffered, flush output once it has been buffered for the given number of
+    /// milliseconds, including while waiting for input, rather than whenever standard input is
+    /// idle. See long '--help'.
+    #[arg(long, value_name = "MS", value_parser = parse_flush_interval, conflicts_with_all = ["line_buffered", "unbuffered"])]
+    pub flush_interval: Option<Duration>,
+
+    /// Skip input lines longer than the given number of bytes before matching any patterns against
+    /// them, guarding against pathologically long lines. See '--truncate-long'.
+    #[arg(long, value_name = "BYTES", value_parser = parse_max_line_length, conflicts_with = "multiline")]
+    pub max_line_length: Option<usize>,
+
+    /// Truncate input lines longer than '--max-line-length' to that length rather than skipping
+    /// them.
+    #[arg(long, requires = "max_line_length")]
+    pub truncate_long: bool,
+
+    /// Skip input lines whose patterns take longer than the given number of milliseconds to match,
+    /// guarding against pathological input. Skipped lines are counted by '--stats'. See long
+    /// '--help'.
+    #[arg(long, value_name = "MS", value_parser = parse_pattern_timeout, conflicts_with = "multiline")]
+    pub pattern_timeout: Option<Duration>,
+
+    /// Number of bytes of output to buffer before writing when output is block-buffered. Larger
+    /// buffers mean fewer writes when piping large amounts of output.
+    #[arg(long, value_name = "BYTES", default_value_t = BLOCK_SIZE, value_parser = parse_buffer_size)]
+    pub buffer_size: usize,
+
+    /// When to colorize output and errors. 'auto' colorizes them if they are written to a terminal
+    /// and defers to the 'NO_COLOR', 'CLICOLOR_FORCE', and 'CLICOLOR' environment variables. See
+    /// long '--help'.
+    #[arg(long, value_name = "WHEN", default_value_t = ColorChoice::default())]
+    pub color: ColorChoice,
+
+    /// The background of the terminal that grits' own errors and warnings are styled for so that
+    /// they remain legible. Styles applied by templates are unaffected.
+    #[arg(long, default_value_t = Theme::default())]
+    pub theme: Theme,
+
+    /// Path to a TOML config file that sets defaults for patterns, templates, separators, required
+    /// captures, and colorization. Arguments on the command-line take precedence. By default,
+    /// 'grits.toml' is read from the current directory if it exists. See long '--help'.
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Don't read 'grits.toml' from the current directory.
+    #[arg(long, conflicts_with = "config")]
+    pub no_config: bool,
+
+    /// Produce completions for shell and exit.
+    #[arg(short, long)]
+    pub completions: Option<clap_complete::Shell>,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum RequireMode {
+    /// Require all capture names specified to be matched for input line to be processed.
+    #[default]
+    All,
+    /// Require at least one capture name among those specified to be matched for input line to be
+    /// processed.
+    Any,
+}
+
+impl fmt::Display for RequireMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum ColorChoice {
+    /// Colorize if writing to a terminal unless environment variables say otherwise.
+    #[default]
+    Auto,
+    /// Always colorize regardless of the destination or environment variables.
+    Always,
+    /// Never colorize.
+    Never,
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Always => write!(f, "always"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Theme {
+    /// Styles for a terminal with a dark background.
+    #[default]
+    Dark,
+    /// Styles for a terminal with a light background.
+    Light,
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dark => write!(f, "dark"),
+            Self::Light => write!(f, "light"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutputFormat {
+    /// Transform input lines using the provided templates.
+    #[default]
+    Template,
+    /// Emit a compact JSON object of captures for each processed line, one per output line.
+    #[value(alias = "json-lines")]
+    Json,
+    /// Emit a header row of capture names followed by a row of comma-separated values for each
+    /// processed line.
+    Csv,
+    /// Emit a header row of capture names followed by a row of tab-separated values for each
+    /// processed line.
+    Tsv,
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Template => write!(f, "template"),
+            Self::Json => write!(f, "json"),
+            Self::Csv => write!(f, "csv"),
+            Self::Tsv => write!(f, "tsv"),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// The field delimiter if the format produces delimiter-separated values.
+    pub fn delimiter(&self) -> Option<u8> {
+        match self {
+            Self::Csv => Some(b','),
+            Self::Tsv => Some(b'\t'),
+            _ => None,
+        }
+    }
+}
+
+/// A range of characters, by index, that makes up a column of fixed-width input. The range is
+/// inclusive of `end` and extends to the end of the line if there is no `end`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+/// A comparison between the value of a capture and a value as specified by '--where'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub capture: String,
+    pub op: Comparison,
+    pub value: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+}
+
+impl Condition {
+    /// Whether `val`, the value of the capture, satisfies the condition. Both sides are compared as
+    /// numbers if they both parse as such and as strings otherwise.
+    pub fn is_satisfied_by(&self, val: &str) -> bool {
+        let ordering = match (val.trim().parse::<f64>(), self.value.trim().parse::<f64>()) {
+            (Ok(lhs), Ok(rhs)) => match lhs.partial_cmp(&rhs) {
+                Some(ordering) => ordering,
+                None => return false,
+            },
+            _ => val.cmp(&self.value),
+        };
+
+        match self.op {
+            Comparison::Eq => ordering.is_eq(),
+            Comparison::Ne => ordering.is_ne(),
+            Comparison::Gt => ordering.is_gt(),
+            Comparison::Lt => ordering.is_lt(),
+            Comparison::Ge => ordering.is_ge(),
+            Comparison::Le => ordering.is_le(),
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eq => write!(f, "="),
+            Self::Ne => write!(f, "!="),
+            Self::Gt => write!(f, ">"),
+            Self::Lt => write!(f, "<"),
+            Self::Ge => write!(f, ">="),
+            Self::Le => write!(f, "<="),
+        }
+    }
+}
+
+/// Directs the output of lines that satisfy `condition` to `stream` as specified by '--route'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub condition: Condition,
+    pub stream: Stream,
+}
+
+/// A pattern as specified by '--named-pattern' whose captures templates can refer to specifically
+/// as `{name:capture}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedPattern {
+    pub name: String,
+    pub pattern: String,
+}
+
+/// A capture value as specified by '--replace-map' that is replaced in its entirety by another.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+/// Renders `template` in place of all other templates for lines that satisfy `condition` as
+/// specified by '--template-when'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateWhen {
+    pub condition: Condition,
+    pub template: String,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum Stream {
+    /// Standard output, or the file provided to '--output-file'.
+    #[default]
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+impl Cli {
+    /// The byte that separates input records and, unless '--null-output' is provided, output records.
+    pub fn record_delimiter(&self) -> u8 {
+        if self.null_data {
+            return b'\0';
+        }
+        self.record_separator.unwrap_or(b'\n')
+    }
+
+    /// The byte that terminates output records.
+    pub fn output_delimiter(&self) -> u8 {
+        if self.null_output {
+            return b'\0';
+        }
+        self.record_delimiter()
+    }
+
+    /// How input is split into lines and decoded.
+    pub fn input_format(&self) -> InputFormat {
+        let format = InputFormat::new(self.record_delimiter()).keep_cr(self.keep_cr);
+
+        match self.input_encoding {
+            Some(encoding) => format.encoding(encoding),
+            None => format,
+        }
+    }
+
+    /// How output is buffered.
+    pub fn buffering(&self) -> Buffering {
+        Buffering {
+            line_buffered: self.line_buffered,
+            unbuffered: self.unbuffered,
+            buffer_size: self.buffer_size,
+            flush_interval: self.flush_interval,
+        }
+    }
+
+    /// The number of lines of context to write before and after each line that produces output.
+    pub fn context_lines(&self) -> (usize, usize) {
+        let context = self.context.unwrap_or_default();
+        (
+            self.before_context.unwrap_or(context),
+            self.after_context.unwrap_or(context),
+        )
+    }
+
+    /// Parses arguments from the command-line, exiting on failure like [Parser::parse], then
+    /// merges in the config file if there is one.
+    pub fn parse_with_config() -> Result<Self> {
+        Self::from_matches_with_config(&Self::command().get_matches())
+    }
+
+    /// Like [Cli::parse_with_config] but from arguments that have already been matched. The
+    /// config file is either the one provided by '--config' or
+    /// [CONFIG_FILE](crate::config::CONFIG_FILE) in the current directory unless '--no-config' is
+    /// provided.
+    pub fn from_matches_with_config(matches: &ArgMatches) -> Result<Self> {
+        let mut cli = Self::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+
+        let config = match &cli.config {
+            Some(path) => Some(Config::from_path(path)?),
+            None if cli.no_config => None,
+            None => Config::discover()?,
+        };
+        if let Some(config) = config {
+            config.merge_into(&mut cli, matches);
+        }
+        Ok(cli)
+    }
+
+    pub fn compute_shell_used_for_completions() -> Result<Option<Shell>> {
+        let mut raw_args = env::args_os();
+
+        if raw_args.any(|a| {
+            a.as_os_str()
+                .to_str()
+                .is_some_and(|s| s == "--completions" || s == "-c")
+        }) {
+            if let Some(raw_shell) = raw_args.next().and_then(|a| a.as_os_str().to_str().map(String::from)) {
+                let shell = <Shell as FromStr>::from_str(&raw_shell.to_lowercase())
+                    .map_err(|e| format_err!("failed to determine which Shell to generate autocomplete due to {e}"))?;
+                return Ok(Some(shell));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Parses the argument of '--record-separator' which must be a single ASCII character after
+/// escapes are interpreted.
+fn parse_record_separator(raw: &str) -> Result<u8> {
+    let unescaped = unescape(raw);
+    let mut chars = unescaped.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(c as u8),
+        _ => Err(format_err!("record separator must be a single ASCII character")),
+    }
+}
+
+/// Parses a single character range of '--columns' which is either a lone index, e.g. '5', a range
+/// such as '0-9', or an open-ended range such as '20-'.
+fn parse_column(raw: &str) -> Result<Column> {
+    let parse_index = |index: &str| {
+        index
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format_err!("expected a column such as '0-9' or '20-' but found '{raw}'"))
+    };
+    let column = match raw.split_once('-') {
+        None => {
+            let index = parse_index(raw)?;
+            Column {
+                start: index,
+                end: Some(index),
+            }
+        }
+        Some((start, "")) => Column {
+            start: parse_index(start)?,
+            end: None,
+        },
+        Some((start, end)) => Column {
+            start: parse_index(start)?,
+            end: Some(parse_index(end)?),
+        },
+    };
+    if column.end.is_some_and(|end| end < column.start) {
+        return Err(format_err!("column '{raw}' ends before it begins"));
+    }
+    Ok(column)
+}
+
+/// Parses the argument of '--input-encoding' which is an encoding label as defined by the WHATWG
+/// Encoding Standard, e.g. 'latin1'. Input is split into lines before it's decoded, so the encoding
+/// must be ASCII-compatible which rules out UTF-16.
+fn parse_encoding(raw: &str) -> Result<&'static Encoding> {
+    let encoding =
+        Encoding::for_label(raw.trim().as_bytes()).ok_or_else(|| format_err!("unrecognized encoding '{raw}'"))?;
+
+    if !encoding.is_ascii_compatible() {
+        return Err(format_err!(
+            "encoding '{raw}' isn't ASCII-compatible and can't be split into lines before it's decoded"
+        ));
+    }
+    Ok(encoding)
+}
+
+/// Parses the argument of '--escape-char' which must be a single character that is a valid escape.
+fn parse_escape_char(raw: &str) -> Result<char> {
+    let mut chars = raw.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if template::is_valid_escape(c) => Ok(c),
+        (Some(c), None) => Err(format_err!(
+            "'{c}' has special meaning within templates and can't be the escape"
+        )),
+        _ => Err(format_err!("escape must be a single character")),
+    }
+}
+
+/// Parses the argument of '--anchor-open' or '--anchor-close' which must be a single character that
+/// is a valid anchor delimiter.
+fn parse_anchor_delimiter(raw: &str) -> Result<char> {
+    let mut chars = raw.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if template::is_valid_anchor_delimiter(c) => Ok(c),
+        (Some(c), None) => Err(format_err!(
+            "'{c}' has special meaning within templates and can't delimit anchors"
+        )),
+        _ => Err(format_err!("anchor delimiter must be a single character")),
+    }
+}
+
+/// Parses the argument of '--anchor-charset' which must be the valid contents of a bracket expression.
+/// Brackets must be escaped so that the charset can't end the bracket expression it's placed in.
+fn parse_anchor_charset(raw: &str) -> Result<String> {
+    let mut escaped = false;
+    for c in raw.chars() {
+        if !escaped && (c == '[' || c == ']') {
+            return Err(format_err!("'{c}' must be escaped within the anchor charset"));
+        }
+        escaped = !escaped && c == '\\';
+    }
+    match Rules::new(raw) {
+        Ok(_) => Ok(raw.to_string()),
+        Err(e) => Err(format_err!("anchor charset isn't a valid bracket expression: {e}")),
+    }
+}
+
+/// Parses the size of the output buffer which must be positive.
+fn parse_buffer_size(raw: &str) -> Result<usize> {
+    match raw.parse::<usize>() {
+        Ok(size) if size > 0 => Ok(size),
+        _ => Err(format_err!("buffer size must be a positive number of bytes")),
+    }
+}
+
+/// Parses the interval at which block-buffered output is flushed which must be a positive number of
+/// milliseconds.
+fn parse_flush_interval(raw: &str) -> Result<Duration> {
+    match raw.parse::<u64>() {
+        Ok(millis) if millis > 0 => Ok(Duration::from_millis(millis)),
+        _ => Err(format_err!("flush interval must be a positive number of milliseconds")),
+    }
+}
+
+/// Parses the maximum length of input lines which must be positive.
+fn parse_max_line_length(raw: &str) -> Result<usize> {
+    match raw.parse::<usize>() {
+        Ok(length) if length > 0 => Ok(length),
+        _ => Err(format_err!("maximum line length must be a positive number of bytes")),
+    }
+}
+
+/// Parses the time that patterns are given to match each line which must be a positive number of
+/// milliseconds.
+fn parse_pattern_timeout(raw: &str) -> Result<Duration> {
+    match raw.parse::<u64>() {
+        Ok(millis) if millis > 0 => Ok(Duration::from_millis(millis)),
+        _ => Err(format_err!("pattern timeout must be a positive number of milliseconds")),
+    }
+}
+
+/// Describes the syntax of a condition for errors raised by [`parse_condition`].
+const CONDITION_SYNTAX: &str = "a condition such as 'name=value' with one of the operators =, !=, >, <, >=, <=";
+
+/// Parses the argument of '--where' which is a capture name followed by a comparison operator and
+/// the value to compare against, e.g. 'status!=200'. The operator is the first one that appears.
+fn parse_condition(raw: &str) -> Result<Condition> {
+    let Some(start) = raw.find(['=', '!', '<', '>']) else {
+        return Err(format_err!("expected {CONDITION_SYNTAX}"));
+    };
+    let (capture, rest) = raw.split_at(start);
+
+    let (op, value) = if let Some(value) = rest.strip_prefix("!=") {
+        (Comparison::Ne, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Comparison::Ge, value)
+    } else if let Some(value) = rest.strip_prefix("<=") {
+        (Comparison::Le, value)
+    } else if let Some(value) = rest.strip_prefix('=') {
+        (Comparison::Eq, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Comparison::Gt, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Comparison::Lt, value)
+    } else {
+        return Err(format_err!(
+            "expected {CONDITION_SYNTAX} but found '{rest}' in condition: {raw}"
+        ));
+    };
+
+    let capture = capture.trim();
+    if capture.is_empty() {
+        return Err(format_err!("expected a capture name before '{op}' in condition: {raw}"));
+    }
+    Ok(Condition {
+        capture: capture.to_string(),
+        op,
+        value: value.to_string(),
+    })
+}
+
+/// Parses the argument of '--named-pattern' which is a name followed by a '=' and a pattern, e.g.
+/// 'req=status=(?<code>\d+)'. The pattern follows the first '=' so that it may itself contain one.
+fn parse_named_pattern(raw: &str) -> Result<NamedPattern> {
+    let Some((name, pattern)) = raw.split_once('=') else {
+        return Err(format_err!(
+            "expected a named pattern such as 'name=(?<capture>.*)' with a name followed by a pattern"
+        ));
+    };
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format_err!(
+            "expected pattern name to consist of one or more ASCII letters, digits, or underscores: {name}"
+        ));
+    }
+    Ok(NamedPattern {
+        name: name.to_string(),
+        pattern: pattern.to_string(),
+    })
+}
+
+/// Parses the argument of '--replace-map', or a line of '--replace-map-file', which is the value to
+/// replace followed by a '=' and its replacement, e.g. 'WARNING=WARN'. The replacement follows the
+/// first '=' so that it may itself contain one.
+pub(crate) fn parse_replacement(raw: &str) -> Result<Replacement> {
+    let Some((from, to)) = raw.split_once('=') else {
+        return Err(format_err!(
+            "expected a replacement such as 'WARNING=WARN' with a value followed by a '=' and its replacement"
+        ));
+    };
+    Ok(Replacement {
+        from: from.to_string(),
+        to: to.to_string(),
+    })
+}
+
+/// Parses the argument of '--route' which is a condition as in '--where' followed by a ':' and the
+/// name of a stream, e.g. 'lvl=ERROR:stderr'. The stream follows the last ':' so that the value
+/// of the condition may itself contain one.
+fn parse_route(raw: &str) -> Result<Route> {
+    let Some((condition, stream)) = raw.rsplit_once(':') else {
+        return Err(format_err!(
+            "expected a route such as 'name=value:stderr' with a condition followed by a stream"
+        ));
+    };
+    let stream = <Stream as ValueEnum>::from_str(stream.trim(), true)
+        .map_err(|_| format_err!("expected a stream of either 'stdout' or 'stderr' in route: {raw}"))?;
+
+    Ok(Route {
+        condition: parse_condition(condition)?,
+        stream,
+    })
+}
+
+/// Parses the argument of '--template-when' which is a condition as in '--where' followed by a ':'
+/// and a template, e.g. 'lvl=ERROR:{(red):msg}'. The template follows the first ':' so that it may
+/// itself contain one, which means that the value of the condition can't.
+fn parse_template_when(raw: &str) -> Result<TemplateWhen> {
+    let Some((condition, template)) = raw.split_once(':') else {
+        return Err(format_err!(
+            "expected a conditional template such as 'name=value:{{name}}' with a condition followed by a template"
+        ));
+    };
+    Ok(TemplateWhen {
+        condition: parse_condition(condition)?,
+        template: template.to_string(),
+    })
+}
+
+/// Parses an argument that may contain backslash escapes. See [unescape].
+fn parse_escaped(raw: &str) -> Result<String> {
+    Ok(unescape(raw))
+}
+
+/// Interprets the backslash escapes '\n', '\t', '\r', '\0', and '\\' in `raw`. Any other backslash
+/// sequence, including a trailing backslash, is left as is.
+pub fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let unescaped = match chars.peek() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            _ => {
+                out.push(c);
+                continue;
+            }
+        };
+        chars.next();
+        out.push(unescaped);
+    }
+    out
+}
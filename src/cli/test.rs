@@ -0,0 +1,518 @@
+use super::{unescape, Cli, ColorChoice, Column, Comparison, NamedPattern, Replacement, Stream, Theme};
+use crate::config::{Config, Values};
+use clap::{CommandFactory, Parser};
+use std::time::Duration;
+
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape(r"a\tb\nc\rd\0e\\f"), "a\tb\nc\rd\0e\\f");
+    assert_eq!(unescape(r"\x \q \"), r"\x \q \");
+    assert_eq!(unescape(r"\\t"), r"\t");
+    assert_eq!(unescape("plain"), "plain");
+}
+
+#[test]
+fn test_separator_escapes() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "-t", "{a}", "-s", r"\t", "-s", r"\z"]).unwrap();
+    assert_eq!(cli.separator, vec![String::from("\t"), String::from(r"\z")]);
+}
+
+#[test]
+fn test_record_separator_escapes() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--record-separator", r"\0"]).unwrap();
+    assert_eq!(cli.record_delimiter(), b'\0');
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--record-separator", ";"]).unwrap();
+    assert_eq!(cli.record_delimiter(), b';');
+
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--record-separator", r"\t\t"]).is_err());
+}
+
+#[test]
+fn test_null_output() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--record-separator", ";"]).unwrap();
+    assert_eq!(cli.output_delimiter(), b';');
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--record-separator", ";", "--null-output"]).unwrap();
+    assert_eq!(cli.record_delimiter(), b';');
+    assert_eq!(cli.output_delimiter(), b'\0');
+}
+
+#[test]
+fn test_named_pattern() {
+    let cli = Cli::try_parse_from([
+        "grits",
+        "--named-pattern",
+        r"req=status=(?<code>\d+)",
+        "-t",
+        "{req:code}",
+    ])
+    .unwrap();
+    assert_eq!(
+        cli.named_pattern,
+        vec![NamedPattern {
+            name: String::from("req"),
+            pattern: String::from(r"status=(?<code>\d+)"),
+        }]
+    );
+
+    for invalid in ["req", "=status", "re-q=status", "re q=status"] {
+        assert!(
+            Cli::try_parse_from(["grits", "--named-pattern", invalid, "-t", "{a}"]).is_err(),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn test_replace_map() {
+    let cli = Cli::try_parse_from([
+        "grits",
+        "-p",
+        r"level=(?<lvl>\w+)",
+        "--replace-map",
+        "WARNING=WARN",
+        "--replace-map",
+        "=-",
+        "--replace-map",
+        "a==b",
+        "-t",
+        "{lvl}",
+    ])
+    .unwrap();
+    assert_eq!(
+        cli.replace_map,
+        vec![
+            Replacement {
+                from: String::from("WARNING"),
+                to: String::from("WARN"),
+            },
+            Replacement {
+                from: String::new(),
+                to: String::from("-"),
+            },
+            Replacement {
+                from: String::from("a"),
+                to: String::from("=b"),
+            },
+        ]
+    );
+
+    assert!(Cli::try_parse_from(["grits", "--replace-map", "WARNING", "-t", "{a}"]).is_err());
+}
+
+#[test]
+fn test_only_conflicts() {
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--only", "a"]).is_ok());
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--only", "a", "-t", "{a}"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--only", "a", "--output", "json"]).is_err());
+}
+
+#[test]
+fn test_buffer_size() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!(cli.buffer_size, 512);
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--buffer-size", "65536"]).unwrap();
+    assert_eq!(cli.buffer_size, 65536);
+
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--buffer-size", "0"]).is_err());
+}
+
+#[test]
+fn test_flush_interval() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!(cli.buffering().flush_interval, None);
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--flush-interval", "250"]).unwrap();
+    assert_eq!(cli.buffering().flush_interval, Some(Duration::from_millis(250)));
+
+    for invalid in [
+        &["--flush-interval", "0"][..],
+        &["--flush-interval", "1.5"],
+        &["--flush-interval", "100", "--unbuffered"],
+    ] {
+        let args = ["grits", "-p", "(?<a>a)"].iter().chain(invalid);
+        assert!(Cli::try_parse_from(args).is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_pattern_timeout() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--pattern-timeout", "50"]).unwrap();
+    assert_eq!(cli.pattern_timeout, Some(Duration::from_millis(50)));
+
+    for invalid in [
+        &["--pattern-timeout", "0"][..],
+        &["--pattern-timeout", "-1"],
+        &["--pattern-timeout", "50", "--multiline"],
+    ] {
+        let args = ["grits", "-p", "(?<a>a)"].iter().chain(invalid);
+        assert!(Cli::try_parse_from(args).is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_unbuffered_conflicts() {
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--unbuffered"]).is_ok());
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--unbuffered", "--line-buffered"]).is_ok());
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--unbuffered", "--buffer-size", "1024"]).is_err());
+}
+
+#[test]
+fn test_append_requires_output_file() {
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--append"]).is_err());
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--output-file", "out.log", "--append"]).unwrap();
+    assert_eq!(cli.output_file.as_deref(), Some("out.log"));
+    assert!(cli.append);
+}
+
+#[test]
+fn test_escape_char() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!(cli.escape_char, '\\');
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--escape-char", "~"]).unwrap();
+    assert_eq!(cli.escape_char, '~');
+
+    for invalid in ["", "~~", "{", "|", "\"", "@", "$", "a", " "] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--escape-char", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn test_anchor_delimiters() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!((cli.anchor_open, cli.anchor_close), ('{', '}'));
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--anchor-open", "%", "--anchor-close", "%"]).unwrap();
+    assert_eq!((cli.anchor_open, cli.anchor_close), ('%', '%'));
+
+    for invalid in ["", "<<", "(", "|", "[", "'", "@", "$", "a", " "] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--anchor-open", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn test_anchor_charset() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!(cli.anchor_charset, r"a-zA-Z0-9_.\-");
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--anchor-charset", r"\p{L}_\]"]).unwrap();
+    assert_eq!(cli.anchor_charset, r"\p{L}_\]");
+
+    for invalid in ["a-z]", "[a-z", r"a-z\", r"\p{Nope}"] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--anchor-charset", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+}
+
+#[test]
+fn test_quiet() {
+    assert!(!Cli::try_parse_from(["grits", "-p", "a"]).unwrap().quiet);
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "-q"]).unwrap().quiet);
+    assert!(
+        Cli::try_parse_from(["grits", "-p", "a", "--quiet", "--strict"])
+            .unwrap()
+            .quiet
+    );
+}
+
+#[test]
+fn test_max_line_length() {
+    let cli = Cli::try_parse_from(["grits", "-p", "a", "--max-line-length", "4096", "--truncate-long"]).unwrap();
+    assert_eq!(cli.max_line_length, Some(4096));
+    assert!(cli.truncate_long);
+
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--max-line-length", "0"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--truncate-long"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--max-line-length", "10", "--multiline"]).is_err());
+}
+
+#[test]
+fn test_columns() {
+    let cli = Cli::try_parse_from(["grits", "-t", "{0}", "--columns", "0-9,10, 11-"]).unwrap();
+    assert_eq!(
+        cli.columns,
+        vec![
+            Column { start: 0, end: Some(9) },
+            Column {
+                start: 10,
+                end: Some(10)
+            },
+            Column { start: 11, end: None },
+        ]
+    );
+
+    for invalid in ["9-0", "a-b", "-5", ""] {
+        assert!(
+            Cli::try_parse_from(["grits", "-t", "{0}", "--columns", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+    assert!(Cli::try_parse_from(["grits", "-t", "{0}", "--columns", "0-", "-F", ","]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-t", "{0}", "--columns", "0-", "--multiline"]).is_err());
+}
+
+#[test]
+fn test_input_encoding() {
+    let cli = Cli::try_parse_from(["grits", "-p", "a"]).unwrap();
+    assert_eq!(cli.input_format().encoding, encoding_rs::UTF_8);
+
+    let cli = Cli::try_parse_from(["grits", "-p", "a", "--input-encoding", "Latin1", "--keep-cr"]).unwrap();
+    let format = cli.input_format();
+    assert_eq!(format.encoding, encoding_rs::WINDOWS_1252);
+    assert!(format.keep_cr);
+
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--input-encoding", "klingon"]).is_err());
+
+    for encoding in ["utf-16", "UTF-16BE", "iso-2022-jp"] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "a", "--input-encoding", encoding]).is_err(),
+            "{encoding}"
+        );
+    }
+}
+
+#[test]
+fn test_context_lines() {
+    let cli = Cli::try_parse_from(["grits", "-p", "a"]).unwrap();
+    assert_eq!(cli.context_lines(), (0, 0));
+
+    let cli = Cli::try_parse_from(["grits", "-p", "a", "-C", "2"]).unwrap();
+    assert_eq!(cli.context_lines(), (2, 2));
+
+    let cli = Cli::try_parse_from(["grits", "-p", "a", "-C", "2", "-A", "0"]).unwrap();
+    assert_eq!(cli.context_lines(), (2, 0));
+
+    let cli = Cli::try_parse_from(["grits", "-p", "a", "--before-context", "3"]).unwrap();
+    assert_eq!(cli.context_lines(), (3, 0));
+
+    for conflict in ["--multiline", "--passthrough", "--unique", "--sort"] {
+        assert!(Cli::try_parse_from(["grits", "-p", "a", "-B", "1", conflict]).is_err());
+    }
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "-A", "1", "-j", "2"]).is_err());
+}
+
+#[test]
+fn test_route() {
+    let cli = Cli::try_parse_from([
+        "grits",
+        "-p",
+        "a",
+        "--route",
+        "lvl=ERROR:stderr",
+        "--route",
+        "url=http://a:STDOUT",
+    ])
+    .unwrap();
+    let routes = cli
+        .route
+        .iter()
+        .map(|route| {
+            (
+                route.condition.capture.as_str(),
+                route.condition.value.as_str(),
+                route.stream,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        routes,
+        [("lvl", "ERROR", Stream::Stderr), ("url", "http://a", Stream::Stdout)]
+    );
+
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--route", "lvl=ERROR"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--route", "lvl=ERROR:stdin"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--route", "ERROR:stderr"]).is_err());
+    assert!(Cli::try_parse_from(["grits", "-p", "a", "--route", "lvl=ERROR:stderr", "-C", "1"]).is_err());
+}
+
+#[test]
+fn test_template_test() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--template-test"]).unwrap();
+    assert_eq!(cli.template_test, Some(5));
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--template-test", "20"]).unwrap();
+    assert_eq!(cli.template_test, Some(20));
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)"]).unwrap();
+    assert_eq!(cli.template_test, None);
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--template-test", "--multiline"]).is_err());
+}
+
+#[test]
+fn test_where_conditions() {
+    let cli = Cli::try_parse_from([
+        "grits",
+        "-p",
+        "(?<a>a)",
+        "--where",
+        "lvl=ERROR",
+        "--where",
+        "status!=200",
+        "--where",
+        "ms>=1.5",
+    ])
+    .unwrap();
+    let conditions = cli
+        .conditions
+        .iter()
+        .map(|c| (c.capture.as_str(), c.op, c.value.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        conditions,
+        [
+            ("lvl", Comparison::Eq, "ERROR"),
+            ("status", Comparison::Ne, "200"),
+            ("ms", Comparison::Ge, "1.5"),
+        ]
+    );
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--where", "url=/a?b=c"]).unwrap();
+    assert_eq!(cli.conditions[0].value, "/a?b=c");
+
+    for invalid in ["lvl", "=ERROR", "lvl!ERROR"] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--where", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+
+    let err = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--where", "lvl!ERROR"])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains(
+            "expected a condition such as 'name=value' with one of the operators =, !=, >, <, >=, <= \
+             but found '!ERROR' in condition: lvl!ERROR"
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_template_when() {
+    let cli = Cli::try_parse_from([
+        "grits",
+        "-p",
+        "(?<a>a)",
+        "--template-when",
+        "lvl=ERROR:{(red):msg}",
+        "--template-when",
+        "ms>=1.5:slow: {msg}",
+    ])
+    .unwrap();
+    let templates = cli
+        .template_when
+        .iter()
+        .map(|t| {
+            (
+                t.condition.capture.as_str(),
+                t.condition.op,
+                t.condition.value.as_str(),
+                t.template.as_str(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        templates,
+        [
+            ("lvl", Comparison::Eq, "ERROR", "{(red):msg}"),
+            ("ms", Comparison::Ge, "1.5", "slow: {msg}"),
+        ]
+    );
+
+    for invalid in ["lvl=ERROR", "lvl:{msg}", "=ERROR:{msg}"] {
+        assert!(
+            Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--template-when", invalid]).is_err(),
+            "{invalid}"
+        );
+    }
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--template-when", "a=a:{a}", "--only", "a"]).is_err());
+}
+
+/// Parses `args` along with a config file of the given `contents`.
+fn parse_with_config(contents: &str, args: &[&str]) -> Cli {
+    let path = std::env::temp_dir().join(format!("grits-config-{}.toml", std::process::id()));
+    std::fs::write(&path, contents).unwrap();
+
+    let mut argv = vec!["grits", "--config", path.to_str().unwrap()];
+    argv.extend_from_slice(args);
+    let matches = Cli::command().try_get_matches_from(argv).unwrap();
+    let cli = Cli::from_matches_with_config(&matches).unwrap();
+
+    std::fs::remove_file(path).unwrap();
+    cli
+}
+
+#[test]
+fn test_config() {
+    let config = Config::parse("pattern = ['a', 'b']\ntemplate = 't'").unwrap();
+    assert_eq!(
+        config.pattern,
+        Some(Values::Many(vec![String::from("a"), String::from("b")]))
+    );
+    assert_eq!(config.template, Some(Values::One(String::from("t"))));
+
+    assert!(Config::parse("patern = 'a'").is_err());
+    assert!(Config::parse("color = 'sometimes'").is_err());
+    assert!(Config::parse("theme = 'solarized'").is_err());
+    assert!(Config::parse("pattern = 1").is_err());
+}
+
+#[test]
+fn test_config_merge() {
+    let contents = indoc::indoc! {r#"
+        pattern = '(?<a>a)'
+        template = ['{a}', '{a}']
+        separator = '\t'
+        require = ['a', 'b']
+        color = 'never'
+        theme = 'light'
+    "#};
+
+    let cli = parse_with_config(contents, &[]);
+    assert_eq!(cli.pattern, ["(?<a>a)"]);
+    assert_eq!(cli.template, ["{a}", "{a}"]);
+    assert_eq!(cli.separator, ["\t"]);
+    assert_eq!(cli.require.as_deref(), Some("a,b"));
+    assert_eq!(cli.color, ColorChoice::Never);
+    assert_eq!(cli.theme, Theme::Light);
+
+    let cli = parse_with_config(
+        contents,
+        &[
+            "-p", "(?<b>b)", "-t", "{b}", "-s", ",", "-r", "b", "--color", "auto", "--theme", "dark",
+        ],
+    );
+    assert_eq!(cli.pattern, ["(?<b>b)"]);
+    assert_eq!(cli.template, ["{b}"]);
+    assert_eq!(cli.separator, [","]);
+    assert_eq!(cli.require.as_deref(), Some("b"));
+    assert_eq!(cli.color, ColorChoice::Auto);
+    assert_eq!(cli.theme, Theme::Dark);
+
+    let cli = parse_with_config(contents, &["--pattern-file", "patterns.txt", "--only", "a"]);
+    assert!(cli.pattern.is_empty());
+    assert!(cli.template.is_empty());
+
+    assert!(Cli::try_parse_from(["grits", "--config", "grits.toml", "--no-config"]).is_err());
+}
+
+#[test]
+fn test_with_filename() {
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--with-filename", "a.log"]).unwrap();
+    assert!(cli.with_filename);
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--with-filename", "--no-filename"]).unwrap();
+    assert!(!cli.with_filename);
+
+    let cli = Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--no-filename", "--with-filename"]).unwrap();
+    assert!(cli.with_filename);
+
+    assert!(Cli::try_parse_from(["grits", "-p", "(?<a>a)", "--with-filename", "--sort"]).is_err());
+}
@@ -0,0 +1,111 @@
+use crate::cli::{unescape, Cli, ColorChoice, Theme};
+use anyhow::{format_err, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Name of the config file that is read from the current working directory if '--config' isn't
+/// provided.
+pub const CONFIG_FILE: &str = "grits.toml";
+
+/// Defaults read from a TOML config file. Each setting applies only if the corresponding
+/// argument isn't provided on the command-line.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub pattern: Option<Values>,
+    pub template: Option<Values>,
+    pub separator: Option<Values>,
+    pub require: Option<Values>,
+    pub color: Option<String>,
+    pub theme: Option<String>,
+}
+
+/// A setting that may be either a single string or an array of them.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Values {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Values {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::One(value) => vec![value],
+            Self::Many(values) => values,
+        }
+    }
+}
+
+impl Config {
+    /// Reads and parses the config file at `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).with_context(|| format!("failed to read config file '{}'", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("invalid config file '{}'", path.display()))
+    }
+
+    /// Reads [CONFIG_FILE] from the current working directory if there is one.
+    pub fn discover() -> Result<Option<Self>> {
+        let path = Path::new(CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Self::from_path(path).map(Some)
+    }
+
+    /// Parses the contents of a config file.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+
+        if let Some(color) = &config.color {
+            <ColorChoice as ValueEnum>::from_str(color, true)
+                .map_err(|_| format_err!("expected a color of 'auto', 'always', or 'never' but found '{color}'"))?;
+        }
+        if let Some(theme) = &config.theme {
+            <Theme as ValueEnum>::from_str(theme, true)
+                .map_err(|_| format_err!("expected a theme of 'dark' or 'light' but found '{theme}'"))?;
+        }
+        Ok(config)
+    }
+
+    /// Fills in the arguments of `cli` that weren't provided on the command-line, as reported by
+    /// `matches`, with the settings of the config file. Patterns and templates from the file are
+    /// ignored if any are provided on the command-line whether directly or from a file.
+    pub fn merge_into(self, cli: &mut Cli, matches: &ArgMatches) {
+        if let Some(pattern) = self.pattern {
+            if cli.pattern.is_empty() && cli.named_pattern.is_empty() && cli.pattern_file.is_none() {
+                cli.pattern = pattern.into_vec();
+            }
+        }
+        if let Some(template) = self.template {
+            if cli.template.is_empty() && cli.template_file.is_none() && cli.only.is_none() {
+                cli.template = template.into_vec();
+            }
+        }
+        if let Some(separator) = self.separator {
+            if cli.separator.is_empty() {
+                cli.separator = separator.into_vec().iter().map(|s| unescape(s)).collect();
+            }
+        }
+        if let Some(require) = self.require {
+            if cli.require.is_none() {
+                cli.require = Some(require.into_vec().join(","));
+            }
+        }
+        if let Some(color) = self.color {
+            if matches.value_source("color") != Some(ValueSource::CommandLine) {
+                // Validated when parsed.
+                cli.color = <ColorChoice as ValueEnum>::from_str(&color, true).unwrap_or_default();
+            }
+        }
+        if let Some(theme) = self.theme {
+            if matches.value_source("theme") != Some(ValueSource::CommandLine) {
+                // Validated when parsed.
+                cli.theme = <Theme as ValueEnum>::from_str(&theme, true).unwrap_or_default();
+            }
+        }
+    }
+}
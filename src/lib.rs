@@ -0,0 +1,91 @@
+//! `grits` applies regular expressions with named captures to input lines and transforms them
+//! using template strings. Aside from the command-line application, the templating engine is
+//! available for embedding via [Transformer]:
+//!
+//! ```
+//! use grits::Transformer;
+//!
+//! let transformer = Transformer::new(&["level=(?<lvl>\\w+)"], &["[{lvl}]"]).unwrap();
+//! assert_eq!(transformer.transform_line("level=info msg=foo"), Some(String::from("[info]")));
+//! assert_eq!(transformer.transform_line("msg=foo"), Some(String::from("[]")));
+//! ```
+//!
+//! Templates with invalid syntax produce a [ParseError] which, aside from its display, exposes
+//! the [kind](ParseError::kind) of error and the [position](ParseError::char_index) at which it
+//! occurred, e.g. so that an editor can highlight it.
+//!
+//! Everything else is an implementation detail of the command-line application.
+//!
+//! See the [documentation](https://github.com/solidiquis/grits/blob/main/docs/help.md) for the
+//! templating language.
+
+use anyhow::Result;
+use clap::{crate_name, CommandFactory};
+use cli::Cli;
+use std::{env, process::ExitCode};
+use tty::{is_broken_pipe, TtyContext};
+
+/// The matches of each capture name that patterns produce and templates are rendered with.
+mod captures;
+
+/// Defines the commandline-interface and the context of the application.
+mod cli;
+pub use cli::RequireMode;
+
+/// Reads defaults for the commandline-interface from a config file.
+mod config;
+
+/// Concerned with the actual line-processing.
+mod line;
+
+/// Concerned with compiling the user-provided patterns and matching them against lines.
+mod pattern;
+
+/// Contains iterator types that read in lines from various sources.
+mod scanner;
+
+/// Defines the user-sourced template strings that define how to transform input lines and generate
+/// an output.
+mod template;
+pub use template::error::{ParseError, ParseErrorKind};
+
+/// Contains the terminal context. The rest of the application accesses handlers to standard output
+/// and standard error via the [TtyContext]. Also concerned with output colorization
+/// and writing to standard output.
+mod tty;
+
+/// Concerned with applying patterns and templates to individual lines independent of any IO.
+mod transformer;
+pub use transformer::Transformer;
+
+/// The entry point of the command-line application which isn't part of the library's API.
+#[doc(hidden)]
+pub fn main() -> ExitCode {
+    if env::var("RUST_LOG").is_ok() {
+        env_logger::init();
+    }
+    let mut tty = TtyContext::new();
+
+    if let Err(e) = run(&mut tty) {
+        // The consumer of the output stopped reading, e.g. 'head', so there's nothing left to do.
+        if is_broken_pipe(&e) {
+            log::debug!("{e:?}");
+            return ExitCode::SUCCESS;
+        }
+        log::error!("{e:?}");
+        let _ = tty.write_err(&e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn run(tty: &mut TtyContext) -> Result<()> {
+    if let Some(shell) = Cli::compute_shell_used_for_completions()? {
+        clap_complete::generate(shell, &mut Cli::command(), crate_name!(), &mut tty.stdout);
+        return Ok(());
+    }
+    let ctx = Cli::parse_with_config()?;
+    tty.set_color_choice(ctx.color);
+    tty.set_theme(ctx.theme);
+    line::process_lines(tty, &ctx)
+}
@@ -0,0 +1,98 @@
+use super::Emitted;
+use crate::tty::OutputWriter;
+use anyhow::Result;
+use std::collections::VecDeque;
+
+/// Written between groups of output and context lines that aren't contiguous, like 'grep'.
+pub const GROUP_SEPARATOR: &str = "--";
+
+/// Tracks the lines surrounding those that produce output so that they can be written unmodified as
+/// context, like 'grep -B' and 'grep -A'. Every input line must be passed to [ContextWindow::write]
+/// in order so that line numbers, and thus contiguity, are accurate.
+#[derive(Debug)]
+pub struct ContextWindow {
+    before: usize,
+    after: usize,
+    /// The most recent lines that were neither output nor written as context, up to `before` of
+    /// them, along with their line numbers.
+    recent: VecDeque<(usize, String)>,
+    /// Number of lines that are still to be written as context following the last line that
+    /// produced output.
+    remaining_after: usize,
+    line_number: usize,
+    /// Line number of the last line that was written whether as output or as context.
+    last_written: Option<usize>,
+}
+
+impl ContextWindow {
+    /// Writes up to `before` lines preceding and `after` lines following each line that produces
+    /// output.
+    pub fn new(before: usize, after: usize) -> Self {
+        Self {
+            before,
+            after,
+            recent: VecDeque::with_capacity(before),
+            remaining_after: 0,
+            line_number: 0,
+            last_written: None,
+        }
+    }
+
+    /// Writes the output of the next input `line` if there is any, preceded by the lines before it
+    /// that haven't yet been written. If there is no output then `line` is either written as
+    /// context following the last output or held back in case output soon follows. A line is
+    /// never written more than once when windows overlap.
+    pub fn write(&mut self, line: &str, emitted: Option<Emitted>, writer: &mut dyn OutputWriter) -> Result<()> {
+        self.line_number += 1;
+
+        let Some(emitted) = emitted else {
+            if self.remaining_after > 0 {
+                self.remaining_after -= 1;
+                self.separate(self.line_number, writer)?;
+                writer.writeln(line)?;
+                self.last_written = Some(self.line_number);
+            } else if self.before > 0 {
+                if self.recent.len() == self.before {
+                    self.recent.pop_front();
+                }
+                self.recent.push_back((self.line_number, line.to_string()));
+            }
+            return Ok(());
+        };
+
+        while let Some((line_number, line)) = self.recent.pop_front() {
+            self.separate(line_number, writer)?;
+            writer.writeln(&line)?;
+            self.last_written = Some(line_number);
+        }
+        self.separate(self.line_number, writer)?;
+        emitted.write(writer)?;
+        self.last_written = Some(self.line_number);
+        self.remaining_after = self.after;
+        Ok(())
+    }
+
+    /// Forgets the lines of the previous input file so that they aren't written as context for the
+    /// lines of the next one. If `separate` then output of the next file is separated from that of
+    /// the previous file by the [GROUP_SEPARATOR] like any other group of lines.
+    pub fn reset(&mut self, separate: bool) {
+        self.recent.clear();
+        self.remaining_after = 0;
+
+        if separate {
+            // Pretend that a line was skipped so that the next line isn't contiguous.
+            self.line_number += 1;
+        } else {
+            self.last_written = None;
+        }
+    }
+
+    /// Writes the [GROUP_SEPARATOR] if the line about to be written at `line_number` doesn't
+    /// immediately follow the last line that was written.
+    fn separate(&self, line_number: usize, writer: &mut dyn OutputWriter) -> Result<()> {
+        if self.last_written.is_some_and(|last| last + 1 < line_number) {
+            writer.writeln(GROUP_SEPARATOR)?;
+        }
+        Ok(())
+    }
+}
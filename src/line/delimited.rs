@@ -0,0 +1,25 @@
+use anyhow::{format_err, Result};
+use csv::{Terminator, WriterBuilder};
+
+/// Serializes a single record of delimiter-separated values, quoting and escaping fields as
+/// necessary. The returned record does not contain a trailing line terminator.
+pub fn to_record<I, T>(fields: I, delimiter: u8) -> Result<String>
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = WriterBuilder::new()
+        .delimiter(delimiter)
+        .terminator(Terminator::Any(b'\n'))
+        .from_writer(Vec::new());
+
+    writer.write_record(fields)?;
+
+    let buffer = writer
+        .into_inner()
+        .map_err(|e| format_err!("failed to serialize delimited record: {}", e.error()))?;
+
+    let mut record = String::from_utf8(buffer)?;
+    record.pop();
+    Ok(record)
+}
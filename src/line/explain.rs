@@ -0,0 +1,172 @@
+use crate::template::{parse::AttributeKind, Anchor, Attribute, DefaultValue, Index, OutputTemplate};
+use crossterm::style::Color;
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write},
+};
+
+/// Describes how each of the `raw_templates` and `regexes` were parsed: the anchors of every
+/// template along with their indices, defaults, and attributes, followed by the capture groups of
+/// every pattern. `templates` are the parsed `raw_templates`.
+pub fn explain<S: AsRef<str>>(
+    raw_templates: &[S],
+    templates: &[OutputTemplate],
+    regexes: &[Regex],
+) -> Result<String, fmt::Error> {
+    let mut out = String::new();
+
+    for (i, (raw, template)) in raw_templates.iter().zip(templates).enumerate() {
+        let raw = raw.as_ref();
+        writeln!(out, "template {}: {raw}", i + 1)?;
+
+        for anchor in template.anchors() {
+            let text = raw
+                .chars()
+                .skip(anchor.start)
+                .take(anchor.end - anchor.start)
+                .collect::<String>();
+            writeln!(out, "  anchor {text}")?;
+            explain_anchor(&mut out, anchor)?;
+        }
+    }
+
+    for (i, regex) in regexes.iter().enumerate() {
+        writeln!(out, "pattern {}: {}", i + 1, regex.as_str())?;
+
+        for (position, name) in regex.capture_names().enumerate().skip(1) {
+            match name {
+                Some(name) => writeln!(out, "  ${position}: {name}")?,
+                None => writeln!(out, "  ${position}: (unnamed)")?,
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Lists the named capture groups amongst all `regexes` in sorted order, one per line, each along
+/// with the patterns that it appears in numbered from 1.
+pub fn list_captures(regexes: &[Regex]) -> Result<String, fmt::Error> {
+    let mut captures: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+
+    for (i, regex) in regexes.iter().enumerate() {
+        for name in regex.capture_names().flatten() {
+            captures.entry(name).or_default().push(i + 1);
+        }
+    }
+    let width = captures
+        .keys()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    let mut out = String::new();
+    for (name, patterns) in captures {
+        let label = if patterns.len() == 1 { "pattern" } else { "patterns" };
+        let patterns = patterns.iter().map(usize::to_string).collect::<Vec<_>>();
+        writeln!(out, "{name:<width$}  {label} {}", patterns.join(", "))?;
+    }
+    Ok(out)
+}
+
+/// Describes a single `anchor`, one property per line. Properties that aren't set are omitted.
+fn explain_anchor(out: &mut String, anchor: &Anchor) -> fmt::Result {
+    if !anchor.name.is_empty() {
+        writeln!(out, "    name: {}", anchor.name)?;
+    }
+    if let Some(index) = &anchor.index {
+        writeln!(out, "    index: {}", describe_index(index))?;
+    }
+    if anchor.required {
+        writeln!(out, "    required: true")?;
+    }
+    if anchor.count_of {
+        writeln!(out, "    count: true")?;
+    }
+    if anchor.default_on_blank {
+        writeln!(out, "    default on blank: true")?;
+    }
+    for default_val in &anchor.defaults {
+        writeln!(out, "    default: {}", describe_default(default_val))?;
+    }
+    for attribute in &anchor.attributes {
+        writeln!(out, "    attribute: {}", describe_attribute(attribute))?;
+    }
+    Ok(())
+}
+
+/// Describes a default value as it would be written in a template.
+fn describe_default(default_val: &DefaultValue) -> String {
+    match default_val {
+        DefaultValue::Literal(val) => format!("{val:?}"),
+        DefaultValue::Interpolated { literal, .. } => format!("{literal:?} (interpolated)"),
+        DefaultValue::Anchor { name, index: None } => name.to_string(),
+        DefaultValue::Anchor {
+            name,
+            index: Some(index),
+        } => format!("{name}{}", describe_index(index)),
+        DefaultValue::Env(var) => format!("${var}"),
+        DefaultValue::Attributed { value, attributes } => {
+            let attributes = attributes.iter().map(describe_attribute).collect::<Vec<_>>();
+            format!("{} with attributes {}", describe_default(value), attributes.join(", "))
+        }
+    }
+}
+
+/// Describes an `attribute` along with the pattern that the value must match for it to apply.
+fn describe_attribute(attribute: &Attribute) -> String {
+    let described = match &attribute.kind {
+        AttributeKind::Align { direction, width } => {
+            format!("align {} to width {width}", format!("{direction:?}").to_lowercase())
+        }
+        AttributeKind::Color { color, background } => {
+            let ground = if *background { "background" } else { "foreground" };
+            match color {
+                Color::Rgb { r, g, b } => format!("{ground} rgb({r}, {g}, {b})"),
+                Color::AnsiValue(index) => format!("{ground} color256({index})"),
+                color => format!("{ground} {color:?}"),
+            }
+        }
+        AttributeKind::Gradient { start, end } => format!(
+            "foreground gradient from rgb({}, {}, {}) to rgb({}, {}, {})",
+            start.0, start.1, start.2, end.0, end.1, end.2
+        ),
+        AttributeKind::Fit { width, align, ellipsis } => {
+            let align = format!("{align:?}").to_lowercase();
+            let ellipsis = if *ellipsis { " with ellipsis" } else { "" };
+            format!("fit {align} to width {width}{ellipsis}")
+        }
+        AttributeKind::Repeat { count } => format!("repeat {count} times"),
+        AttributeKind::Wrap { width } => format!("wrap at width {width}"),
+        AttributeKind::Indent { spaces } => format!("indent each line by {spaces} spaces"),
+        AttributeKind::Bar { width, max } => format!("bar of width {width} with a maximum of {max}"),
+        AttributeKind::Hash { len } => format!("hash to {len} hex characters"),
+        AttributeKind::Mask {
+            keep_start,
+            keep_end,
+            ch,
+        } => format!("mask with '{ch}' keeping the first {keep_start} and last {keep_end} characters"),
+        kind => format!("{kind:?}"),
+    };
+    let described = match (&attribute.must_match, &attribute.must_match_env) {
+        (Some(re), _) => format!("{described} if matching {}", re.as_str()),
+        (None, Some(pattern)) => format!("{described} if matching ${}", pattern.var),
+        (None, None) => described,
+    };
+    if attribute.chained {
+        format!("otherwise {described}")
+    } else {
+        described
+    }
+}
+
+/// Describes an `index` as it would be written in a template.
+fn describe_index(index: &Index) -> String {
+    let bound = |bound: &Option<isize>| bound.map(|b| b.to_string()).unwrap_or_default();
+
+    match index {
+        Index::One(i) => format!("[{i}]"),
+        Index::All(separator) => format!("[*:{separator:?}]"),
+        Index::Range { start, end, separator } => format!("[{}..{}:{separator:?}]", bound(start), bound(end)),
+    }
+}
@@ -0,0 +1,74 @@
+use super::Writers;
+use crate::scanner::ScanEvent;
+use anyhow::Result;
+use std::{
+    sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError},
+    time::Instant,
+    vec,
+};
+
+/// The events of the input. Output that has been buffered for '--flush-interval' is flushed before
+/// the next event is read, and when following standard input it is also flushed while waiting for
+/// the next line rather than being held back until that line arrives.
+pub(crate) enum Input {
+    /// Events that are read as they're needed.
+    Read(Box<dyn Iterator<Item = ScanEvent>>),
+    /// Events of standard input that is read on a separate thread and received in batches, the
+    /// remainder of the latest of which is `batch`. If `flush_when_idle` then buffered output is
+    /// flushed as soon as the next line hasn't been read yet, otherwise once it is due.
+    Followed {
+        events: Receiver<Vec<ScanEvent>>,
+        batch: vec::IntoIter<ScanEvent>,
+        flush_when_idle: bool,
+    },
+}
+
+impl Input {
+    /// The next event, if any, flushing the buffered output of `writers` whenever it is due.
+    pub(crate) fn next_event(&mut self, writers: &mut Writers) -> Result<Option<ScanEvent>> {
+        if writers
+            .flush_deadline()
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            writers.flush_buffered()?;
+        }
+        let (events, batch, flush_when_idle) = match self {
+            Self::Read(events) => return Ok(events.next()),
+            Self::Followed {
+                events,
+                batch,
+                flush_when_idle,
+            } => (events, batch, *flush_when_idle),
+        };
+        if let Some(event) = batch.next() {
+            return Ok(Some(event));
+        }
+
+        let received = match events.try_recv() {
+            Ok(received) => Some(received),
+            Err(TryRecvError::Disconnected) => None,
+            Err(TryRecvError::Empty) => Self::wait(events, writers, flush_when_idle)?,
+        };
+        *batch = received.unwrap_or_default().into_iter();
+        Ok(batch.next())
+    }
+
+    /// Waits for the next batch of `events` while the input is idle, flushing `writers` right away
+    /// if `flush_when_idle` and otherwise once their buffered output is due, if ever.
+    fn wait(
+        events: &Receiver<Vec<ScanEvent>>,
+        writers: &mut Writers,
+        flush_when_idle: bool,
+    ) -> Result<Option<Vec<ScanEvent>>> {
+        if flush_when_idle {
+            writers.flush_buffered()?;
+        } else if let Some(deadline) = writers.flush_deadline() {
+            match events.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(received) => return Ok(Some(received)),
+                Err(RecvTimeoutError::Disconnected) => return Ok(None),
+                Err(RecvTimeoutError::Timeout) => writers.flush_buffered()?,
+            }
+        }
+        Ok(events.recv().ok())
+    }
+}
@@ -0,0 +1,21 @@
+use crate::captures::Captures;
+use serde_json::{Map, Value};
+
+/// Converts the captures of a single input line into a JSON object. Captures with exactly one
+/// match are serialized as a string, captures with multiple matches as an array of strings in the
+/// order they were matched, and captures without a match as `null`. Keys are sorted by capture
+/// name.
+pub fn captures_to_json(captures_map: &dyn Captures) -> Value {
+    let mut object = Map::new();
+
+    for (name, vals) in captures_map.iter() {
+        let mut vals = vals.iter().map(|v| Value::String(v.into_owned())).collect::<Vec<_>>();
+        let val = match vals.len() {
+            0 => Value::Null,
+            1 => vals.remove(0),
+            _ => Value::Array(vals),
+        };
+        object.insert(name.to_string(), val);
+    }
+    Value::Object(object)
+}
@@ -0,0 +1,1080 @@
+use crate::{
+    captures::{Captures, LineCaptures},
+    cli::{
+        self, Cli, ColorChoice, Condition, NamedPattern, OutputFormat, Replacement, RequireMode, Route, Stream,
+        TemplateWhen,
+    },
+    pattern::{self, CapturesBuffer, Flags, Patterns},
+    scanner::{DecodingReader, MultiFileScanner, ScanEvent, StdinScanner, STDIN_PATH},
+    template::{Aliases, OutputTemplate, ParseOptions},
+    transformer::Transformer,
+    tty::{
+        init_output_writer, open_output_file, AlignedOutputWriter, OutputWriter, SortedOutputWriter, TtyContext,
+        UniqueOutputWriter, COLUMN_DELIMITER, STDOUT_PATH,
+    },
+};
+use anyhow::{format_err, Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    path::Path,
+    slice,
+    time::Instant,
+};
+
+/// Concerned with writing the lines surrounding output for '--before-context' and '--after-context'.
+mod context;
+use context::ContextWindow;
+
+/// Concerned with serializing captures into delimiter-separated values such as CSV and TSV.
+mod delimited;
+
+/// Concerned with describing how templates and patterns were parsed for '--explain' and
+/// '--list-captures'.
+mod explain;
+
+/// Concerned with reading input while flushing buffered output for '--flush-interval' and when
+/// following standard input.
+mod follow;
+use follow::Input;
+
+/// Concerned with serializing captures into JSON.
+mod json;
+
+/// Concerned with summarizing processing for '--stats'.
+mod stats;
+use stats::Stats;
+
+/// Concerned with processing multiple input files concurrently.
+mod parallel;
+
+/// Concerned with rendering sample lines for human inspection for '--template-test'.
+mod preview;
+
+#[cfg(test)]
+mod test;
+
+/// Joins aligned columns when '--tab-align' is provided without '-s, --separator', like 'column -t'.
+const ALIGNED_SEPARATOR: &str = "  ";
+
+/// Process input lines from files or standard input.
+pub fn process_lines(tty: &mut TtyContext, args: &Cli) -> Result<()> {
+    let Cli {
+        pattern,
+        named_pattern,
+        pattern_file,
+        ignore_case,
+        verbose_regex,
+        field_separator,
+        columns,
+        replace_map,
+        replace_map_file,
+        template,
+        template_file,
+        template_when,
+        define,
+        escape_char,
+        anchor_open,
+        anchor_close,
+        anchor_charset,
+        only,
+        files,
+        files_from,
+        with_filename,
+        output_file,
+        append,
+        color,
+        max_line_length,
+        truncate_long,
+        pattern_timeout,
+        no_trailing_newline,
+        require,
+        require_mode,
+        conditions,
+        route,
+        separator,
+        tab_align,
+        output,
+        jobs,
+        multiline,
+        passthrough,
+        unique,
+        unique_count,
+        sort,
+        sort_reverse,
+        sort_by,
+        stats,
+        explain,
+        list_captures,
+        template_test,
+        strict,
+        quiet,
+        ..
+    } = args;
+
+    if *output != OutputFormat::Template
+        && (!template.is_empty() || template_file.is_some() || !template_when.is_empty())
+    {
+        return Err(format_err!(
+            "templates are not used when '--output' is '{output}' and should be omitted"
+        ));
+    }
+
+    let filters = require
+        .as_ref()
+        .map_or_else(Vec::new, |r| r.split(",").map(str::trim).collect::<Vec<_>>());
+
+    let raw_templates = read_templates(template, template_file.as_deref())?;
+
+    if *escape_char == *anchor_open || *escape_char == *anchor_close {
+        return Err(format_err!(
+            "'{escape_char}' can't be both the escape and an anchor delimiter"
+        ));
+    }
+
+    let aliases = Aliases::parse_with_escape(define, *escape_char)?;
+
+    // Output written to a file is only colorized if explicitly asked for.
+    let to_stdout = output_file.as_deref().is_none_or(|path| path == STDOUT_PATH);
+    let color = if to_stdout {
+        tty.color_enabled()
+    } else {
+        *color == ColorChoice::Always
+    };
+
+    let parse_template = |templ: &str| -> Result<OutputTemplate> {
+        let options = ParseOptions::new(&aliases)
+            .with_escape(*escape_char)
+            .with_anchor_delimiters(*anchor_open, *anchor_close)
+            .with_anchor_charset(anchor_charset);
+        Ok(OutputTemplate::parse_with_options(templ, options)?.with_color(color))
+    };
+
+    let mut templates = Vec::with_capacity(raw_templates.len());
+    for templ in &raw_templates {
+        templates.push(parse_template(templ)?);
+    }
+
+    // Rendered in place of `templates` for lines that satisfy their condition.
+    let mut conditional_templates = Vec::with_capacity(template_when.len());
+    for TemplateWhen { condition, template } in template_when {
+        conditional_templates.push((condition, parse_template(template)?));
+    }
+
+    let flags = Flags {
+        ignore_case: *ignore_case,
+        multiline: *multiline,
+        verbose: *verbose_regex,
+    };
+
+    let mut regexes = Vec::new();
+    for pat in pattern {
+        let re =
+            pattern::compile(pat, flags).with_context(|| format!("encountered invalid regular expression: {pat}"))?;
+        regexes.push(re);
+    }
+
+    // The index of each named pattern amongst all patterns along with its name.
+    let mut namespaces = Vec::with_capacity(named_pattern.len());
+    for NamedPattern { name, pattern: pat } in named_pattern {
+        let re = pattern::compile(pat, flags)
+            .with_context(|| format!("encountered invalid regular expression for pattern '{name}': {pat}"))?;
+        namespaces.push((regexes.len(), name.as_str()));
+        regexes.push(re);
+    }
+
+    if let Some(path) = pattern_file {
+        let contents = fs::read_to_string(path).with_context(|| format!("failed to read pattern file: {path}"))?;
+        regexes.extend(compile_pattern_file(&contents, path, flags)?);
+    }
+
+    let group_names = regexes
+        .iter()
+        .flat_map(|re| re.capture_names().flatten())
+        .collect::<Vec<_>>();
+    for templ in templates
+        .iter_mut()
+        .chain(conditional_templates.iter_mut().map(|(_, templ)| templ))
+    {
+        templ.resolve_capture_names(&group_names);
+    }
+
+    if *explain {
+        let raw_templates = raw_templates
+            .iter()
+            .map(String::as_str)
+            .chain(template_when.iter().map(|when| when.template.as_str()))
+            .collect::<Vec<_>>();
+        let templates = templates
+            .iter()
+            .chain(conditional_templates.iter().map(|(_, templ)| templ))
+            .cloned()
+            .collect::<Vec<_>>();
+        let explanation = explain::explain(&raw_templates, &templates, &regexes)?;
+        write!(tty.stdout, "{explanation}")?;
+        return Ok(());
+    }
+
+    if *list_captures {
+        let listed = explain::list_captures(&regexes)?;
+        write!(tty.stdout, "{listed}")?;
+        return Ok(());
+    }
+
+    let mut files = files.clone();
+
+    if let Some(path) = files_from {
+        files.extend(read_files_from(path)?);
+    }
+
+    let stdin_count = files.iter().filter(|path| *path == STDIN_PATH).count();
+    if stdin_count > 1 || (stdin_count == 1 && files_from.as_deref() == Some(STDIN_PATH)) {
+        return Err(format_err!(
+            "standard input '{STDIN_PATH}' can only be used once amongst input files and '--files-from'"
+        ));
+    }
+
+    let anchor_names = templates
+        .iter()
+        .chain(conditional_templates.iter().map(|(_, templ)| templ))
+        .flat_map(OutputTemplate::anchor_names)
+        .chain(only.as_deref())
+        .collect::<Vec<_>>();
+    let mut patterns = Patterns::new(regexes, flags)
+        .with_namespaces(&namespaces, anchor_names.iter().copied())
+        .with_positional_groups(anchor_names.iter().copied())
+        .with_line_capture(anchor_names.iter().copied())
+        .with_offset_capture(anchor_names.iter().copied())
+        .with_span_captures(anchor_names.iter().copied())
+        .with_replacements(read_replacements(replace_map, replace_map_file.as_deref())?);
+
+    if let Some(sep) = field_separator {
+        if *output != OutputFormat::Template {
+            return Err(format_err!(
+                "'--field-separator' requires templates and can't be used when '--output' is '{output}'"
+            ));
+        }
+        let re = Regex::new(sep).with_context(|| format!("encountered invalid field separator: {sep}"))?;
+        patterns = patterns.with_field_separator(re, anchor_names.iter().copied());
+    }
+    if !columns.is_empty() {
+        if *output != OutputFormat::Template {
+            return Err(format_err!(
+                "'--columns' requires templates and can't be used when '--output' is '{output}'"
+            ));
+        }
+        patterns = patterns.with_columns(columns.clone(), anchor_names.iter().copied());
+    }
+    // Columns are delimited so that they can be aligned once all output has been produced, unless
+    // only previewing output.
+    let align = *tab_align && template_test.is_none();
+
+    if align && *output != OutputFormat::Template {
+        return Err(format_err!(
+            "'--tab-align' requires templates and can't be used when '--output' is '{output}'"
+        ));
+    }
+    let column_delimiter = [COLUMN_DELIMITER.to_string()];
+    let separators = if align { &column_delimiter[..] } else { &separator[..] };
+
+    // Patterns and templates are compiled once up front and shared by every line of every file.
+    let transformer = Transformer::from_parts(patterns, templates)
+        .with_separators(separators)
+        .with_require(&filters, *require_mode);
+    let patterns = transformer.patterns();
+
+    let captures_map = patterns.captures_map();
+
+    if let Some(name) = only.as_deref().filter(|name| !captures_map.contains_key(name)) {
+        return Err(format_err!(
+            "'--only' capture '{name}' doesn't appear in any of the provided patterns"
+        ));
+    }
+
+    if let Some(condition) = conditions
+        .iter()
+        .find(|condition| !captures_map.contains_key(condition.capture.as_str()))
+    {
+        return Err(format_err!(
+            "'--where' capture '{}' doesn't appear in any of the provided patterns",
+            condition.capture
+        ));
+    }
+
+    if let Some((condition, _)) = conditional_templates
+        .iter()
+        .find(|(condition, _)| !captures_map.contains_key(condition.capture.as_str()))
+    {
+        return Err(format_err!(
+            "'--template-when' capture '{}' doesn't appear in any of the provided patterns",
+            condition.capture
+        ));
+    }
+
+    if let Some(route) = route
+        .iter()
+        .find(|route| !captures_map.contains_key(route.condition.capture.as_str()))
+    {
+        return Err(format_err!(
+            "'--route' capture '{}' doesn't appear in any of the provided patterns",
+            route.condition.capture
+        ));
+    }
+
+    if let Some(name) = sort_by.as_deref().filter(|name| !captures_map.contains_key(name)) {
+        return Err(format_err!(
+            "'--sort-by' capture '{name}' doesn't appear in any of the provided patterns"
+        ));
+    }
+
+    // There's nothing to output without captures, which is only an error if it was unexpected.
+    if captures_map.is_empty() && *quiet {
+        log::debug!("none of the provided patterns contained named capture groups");
+        return Ok(());
+    } else if captures_map.is_empty() && field_separator.is_some() {
+        return Err(format_err!(
+            "templates don't reference any fields and none of the provided patterns contained named capture groups"
+        ));
+    } else if captures_map.is_empty() && !columns.is_empty() {
+        return Err(format_err!(
+            "templates don't reference any columns and none of the provided patterns contained named capture groups"
+        ));
+    } else if captures_map.is_empty() {
+        return Err(format_err!(
+            "none of the provided patterns contained named capture groups"
+        ));
+    }
+
+    // A required anchor without a capture would silently suppress every line.
+    let all_templates = transformer
+        .templates()
+        .iter()
+        .chain(conditional_templates.iter().map(|(_, templ)| templ));
+
+    let unknown_required = unknown_required_anchor_names(all_templates.clone(), &captures_map);
+
+    if !unknown_required.is_empty() {
+        return Err(format_err!(
+            "required anchors reference captures that don't appear in any of the provided patterns so no line would ever produce output: {}",
+            unknown_required.join(", ")
+        ));
+    }
+
+    let unknown_anchors = unknown_anchor_names(all_templates, &captures_map);
+
+    if !unknown_anchors.is_empty() {
+        let unknown_anchors = unknown_anchors.join(", ");
+
+        if *strict {
+            return Err(format_err!(
+                "templates reference captures that don't appear in any of the provided patterns: {unknown_anchors}"
+            ));
+        }
+        if !*quiet {
+            tty.write_warning(&format!(
+                "templates reference captures that don't appear in any of the provided patterns and will never match: {unknown_anchors}"
+            ))?;
+        }
+    }
+
+    let output_delimiter = args.output_delimiter();
+    let input_format = args.input_format();
+
+    // Records are split before they're decoded, and the characters of multi-byte encodings such as
+    // Shift_JIS or GB18030 may contain ASCII digits, letters, and some punctuation.
+    if !*multiline && !input_format.encoding.is_single_byte() && input_format.delimiter >= b'0' {
+        return Err(format_err!(
+            "record separator '{}' may occur within the characters of {} and can't be used with it",
+            input_format.delimiter.escape_ascii(),
+            input_format.encoding.name()
+        ));
+    }
+
+    // Multiple matches of '--only' are written as separate records.
+    let only_separator = char::from(output_delimiter).to_string();
+
+    // Columns of delimiter-separated output are the capture names in sorted order.
+    let mut columns = captures_map.keys().copied().collect::<Vec<_>>();
+    columns.sort();
+
+    let stats = stats.then(|| {
+        let mut stats = Stats::new(captures_map.keys().copied());
+        if max_line_length.is_some() {
+            stats = stats.with_max_line_length(*truncate_long);
+        }
+        if pattern_timeout.is_some() {
+            stats = stats.with_pattern_timeout();
+        }
+        stats
+    });
+
+    // Produces the output for the captures of a single line if there is any.
+    let render = |captures_map: &dyn Captures| -> Result<Option<String>> {
+        if !transformer.passes_require(captures_map) || !passes_where(captures_map, conditions) {
+            if let Some(stats) = &stats {
+                stats.line_filtered();
+            }
+            return Ok(None);
+        }
+        let out = match output {
+            OutputFormat::Template => match only {
+                Some(name) => captures_map
+                    .get(name.as_str())
+                    .map(|vals| vals.join(&only_separator))
+                    .unwrap_or_default(),
+                None => {
+                    let conditional = conditional_templates
+                        .iter()
+                        .find(|(condition, _)| passes_where(captures_map, slice::from_ref(condition)));
+
+                    match conditional {
+                        Some((_, templ)) if *strict => templ.transform_strict(captures_map)?,
+                        Some((_, templ)) => templ.transform(captures_map),
+                        None => transformer.render_templates(captures_map, *strict)?,
+                    }
+                }
+            },
+            OutputFormat::Json => {
+                if all_empty(captures_map) {
+                    return Ok(None);
+                }
+                serde_json::to_string(&json::captures_to_json(captures_map))?
+            }
+            OutputFormat::Csv | OutputFormat::Tsv => {
+                if all_empty(captures_map) {
+                    return Ok(None);
+                }
+                let fields = columns.iter().map(|capname| {
+                    captures_map
+                        .get(capname)
+                        .and_then(|c| c.first())
+                        .unwrap_or_default()
+                        .into_owned()
+                });
+
+                let Some(delimiter) = output.delimiter() else {
+                    unreachable!("csv and tsv output formats always have a delimiter")
+                };
+                delimited::to_record(fields, delimiter)?
+            }
+        };
+
+        if out.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(out))
+    };
+
+    if let Some(limit) = template_test {
+        let events = if files.is_empty() {
+            StdinScanner::init_events(input_format)
+        } else {
+            MultiFileScanner::init_events(&files, input_format)?
+        };
+        let previewed = preview::preview(events, *limit, patterns, render)?;
+        write!(tty.stdout, "{previewed}")?;
+        return Ok(());
+    }
+
+    let output_file = match output_file {
+        Some(path) => open_output_file(path, *append)?,
+        None => None,
+    };
+    let buffering = args.buffering();
+    let block_buffered = buffering.is_block_buffered(tty, output_file.is_some());
+    let writer = init_output_writer(tty, output_file, buffering, output_delimiter, !*no_trailing_newline);
+
+    let aligned_separators = match separator.is_empty() {
+        true => vec![ALIGNED_SEPARATOR.to_string()],
+        false => separator.clone(),
+    };
+
+    // Output routed to stderr is deduplicated and sorted separately from the rest of the output as
+    // each stream is consumed on its own.
+    let wrap_writer = |mut writer: Box<dyn OutputWriter>| -> Result<Box<dyn OutputWriter>> {
+        // Alignment comes last so that it sees the output that is actually written.
+        if align {
+            writer = Box::new(AlignedOutputWriter::new(writer, &aligned_separators));
+        }
+
+        if *unique || *unique_count {
+            writer = Box::new(UniqueOutputWriter::new(writer, *unique_count));
+        }
+
+        if let Some(delimiter) = output.delimiter() {
+            writer.writeln(&delimited::to_record(&columns, delimiter)?)?;
+        }
+
+        // Sorting happens before deduplication so that '--unique' behaves like 'sort | uniq'. The
+        // header of delimiter-separated output has already been written so it isn't sorted along
+        // with the rows.
+        if *sort || *sort_reverse || sort_by.is_some() {
+            writer = Box::new(SortedOutputWriter::new(writer, *sort_reverse));
+        }
+        Ok(writer)
+    };
+
+    let stderr_writer = match route.iter().any(|route| route.stream == Stream::Stderr) {
+        true => Some(wrap_writer(tty.stderr_writer(output_delimiter, !*no_trailing_newline))?),
+        false => None,
+    };
+    let mut writers = Writers {
+        stdout: wrap_writer(writer)?,
+        stderr: stderr_writer,
+        with_filename: *with_filename,
+        header: None,
+        headed: Vec::new(),
+        separated: Vec::new(),
+    };
+
+    // The value that output is ordered by when sorting by a capture.
+    let sort_key = |captures_map: &dyn Captures| -> Option<String> {
+        let name = sort_by.as_deref()?;
+        let val = captures_map.get(name).and_then(|vals| vals.first());
+        Some(val.unwrap_or_default().into_owned())
+    };
+
+    if *multiline {
+        // Produces the output for a single match if there is any.
+        let process_match = |captures_map: &dyn Captures| -> Result<Option<Emitted>> {
+            if let Some(stats) = &stats {
+                stats.line_read(captures_map);
+            }
+            let Some(out) = render(captures_map)? else {
+                return Ok(None);
+            };
+            if let Some(stats) = &stats {
+                stats.line_emitted();
+            }
+            let sort_key = sort_key(captures_map);
+            let stream = route_stream(captures_map, route);
+            Ok(Some(Emitted { out, sort_key, stream }))
+        };
+        process_multiline(&files, input_format.encoding, patterns, &process_match, &mut writers)?;
+        drop(writers);
+        return write_stats(tty, stats.as_ref());
+    }
+
+    // Produces the output for a single line if there is any. This only borrows state that is
+    // shareable across threads so that files may be processed concurrently. `offset` is the byte
+    // offset at which the line begins within its input.
+    let process_line = |line: &str, offset: u64, buffer: &mut CapturesBuffer| -> Result<Option<Emitted>> {
+        let line = match *max_line_length {
+            Some(max) if line.len() > max => {
+                if let Some(stats) = &stats {
+                    stats.line_too_long();
+                }
+                if !*truncate_long {
+                    return Ok(None);
+                }
+                truncate(line, max)
+            }
+            _ => line,
+        };
+
+        // Matches are recorded as offsets into `line` rather than copied out of it. There's no
+        // telling how many matches there could possibly be per line so we're optimizing for minimal
+        // string allocations, which is also why the buffer retains the capacity of each vector of
+        // matches across lines.
+        let completed = match *pattern_timeout {
+            Some(timeout) => patterns.populate_within(line, buffer, timeout),
+            None => {
+                patterns.populate(line, buffer);
+                true
+            }
+        };
+        if !completed {
+            if let Some(stats) = &stats {
+                stats.line_timed_out();
+            }
+            return Ok(None);
+        }
+
+        patterns.populate_offset(offset, buffer);
+        let captures_map = &buffer.captures(line);
+
+        let sort_key = sort_key(captures_map);
+        let stream = route_stream(captures_map, route);
+
+        if let Some(stats) = &stats {
+            stats.line_read(captures_map);
+        }
+
+        let out = if *passthrough && all_empty(captures_map) {
+            None
+        } else {
+            render(captures_map).with_context(|| format!("failed to transform line: {line}"))?
+        };
+
+        let out = match out {
+            Some(out) => out,
+            None if *passthrough => line.to_string(),
+            None => return Ok(None),
+        };
+        if let Some(stats) = &stats {
+            stats.line_emitted();
+        }
+        Ok(Some(Emitted { out, sort_key, stream }))
+    };
+
+    if *jobs > 1 && files.len() > 1 {
+        parallel::process_files(&files, input_format, *jobs, patterns, &process_line, &mut writers)?;
+        drop(writers);
+        return write_stats(tty, stats.as_ref());
+    }
+
+    // Standard input may be a log that is being followed in which case output is flushed whenever
+    // grits is waiting for its next line.
+    let mut input = if !files.is_empty() {
+        Input::Read(MultiFileScanner::init_events(&files, input_format)?)
+    } else if block_buffered {
+        Input::Followed {
+            events: StdinScanner::spawn_events(input_format),
+            batch: Vec::new().into_iter(),
+            flush_when_idle: buffering.flush_interval.is_none(),
+        }
+    } else {
+        Input::Read(StdinScanner::init_events(input_format))
+    };
+
+    let mut buffer = CapturesBuffer::new(patterns);
+
+    let mut context = match args.context_lines() {
+        (0, 0) => None,
+        (before, after) => Some(ContextWindow::new(before, after)),
+    };
+
+    // The input file being read, which standard input isn't announced as unless it's amongst files.
+    let mut reading = None;
+
+    while let Some(event) = input.next_event(&mut writers)? {
+        let (line, offset) = match event {
+            ScanEvent::NewFile(path) => {
+                writers.start_file(&path);
+                if let Some(context) = &mut context {
+                    context.reset(!*with_filename);
+                }
+                reading = Some(path);
+                continue;
+            }
+            ScanEvent::Line { line, offset } => (line, offset),
+            ScanEvent::ReadError(e) => {
+                return Err(match reading {
+                    Some(path) => format_err!("failed to read {}: {e}", path.display()),
+                    None => format_err!("failed to read input: {e}"),
+                })
+            }
+        };
+        let emitted = process_line(&line, offset, &mut buffer)?;
+
+        match (&mut context, emitted) {
+            (Some(context), emitted) => {
+                if emitted.is_some() {
+                    writers.write_header(Stream::Stdout)?;
+                }
+                context.write(&line, emitted, writers.stdout.as_mut())?
+            }
+            (None, Some(emitted)) => emitted.write_routed(&mut writers)?,
+            (None, None) => (),
+        }
+    }
+    // Output held back by the writers is written when they're dropped which must come before the
+    // stats.
+    drop(writers);
+    write_stats(tty, stats.as_ref())
+}
+
+/// Writes the summary of processing to stderr if '--stats' was provided.
+fn write_stats(tty: &mut TtyContext, stats: Option<&Stats>) -> Result<()> {
+    if let Some(stats) = stats {
+        write!(tty.stderr, "{stats}")?;
+    }
+    Ok(())
+}
+
+/// Output produced for a single line or match along with the value it is ordered by if sorting
+/// by a capture and the stream it is routed to.
+pub(crate) struct Emitted {
+    out: String,
+    sort_key: Option<String>,
+    stream: Stream,
+}
+
+impl Emitted {
+    /// Writes the output to `writer`, keyed by the sort key if there is one.
+    fn write(&self, writer: &mut dyn OutputWriter) -> Result<()> {
+        match &self.sort_key {
+            Some(key) => writer.writeln_keyed(key, &self.out),
+            None => writer.writeln(&self.out),
+        }
+    }
+
+    /// Writes the output to whichever of `writers` it is routed to, preceded by the header of the
+    /// current input file if it hasn't been written to that stream yet.
+    fn write_routed(&self, writers: &mut Writers) -> Result<()> {
+        writers.write_header(self.stream)?;
+        self.write(writers.get(self.stream))
+    }
+}
+
+/// The writers of each stream that output may be routed to by '--route'. There is only a writer
+/// for stderr if any output is routed there. If `with_filename` then the first output of each input
+/// file that is written to a stream is preceded by a header.
+pub(crate) struct Writers {
+    stdout: Box<dyn OutputWriter>,
+    stderr: Option<Box<dyn OutputWriter>>,
+    with_filename: bool,
+    /// The header of the current input file.
+    header: Option<String>,
+    /// Streams that the header of the current input file has been written to.
+    headed: Vec<Stream>,
+    /// Streams that any header has been written to so that subsequent headers are separated from
+    /// the output preceding them.
+    separated: Vec<Stream>,
+}
+
+impl Writers {
+    /// The writer of `stream`, falling back to stdout if there is no writer for stderr.
+    fn get(&mut self, stream: Stream) -> &mut dyn OutputWriter {
+        match (stream, &mut self.stderr) {
+            (Stream::Stderr, Some(stderr)) => stderr.as_mut(),
+            _ => self.stdout.as_mut(),
+        }
+    }
+
+    /// Signals that subsequent output is produced from the input file at `path`.
+    fn start_file(&mut self, path: &Path) {
+        if !self.with_filename {
+            return;
+        }
+        let name = match path == Path::new(STDIN_PATH) {
+            true => String::from("standard input"),
+            false => path.display().to_string(),
+        };
+        self.header = Some(format!("==> {name} <=="));
+        self.headed.clear();
+    }
+
+    /// Writes the output of every stream that is merely buffered, see
+    /// [OutputWriter::flush_buffered].
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.stdout.flush_buffered()?;
+
+        match &mut self.stderr {
+            Some(stderr) => stderr.flush_buffered(),
+            None => Ok(()),
+        }
+    }
+
+    /// The earliest time at which the buffered output of any stream is due to be flushed, see
+    /// [OutputWriter::flush_deadline].
+    fn flush_deadline(&self) -> Option<Instant> {
+        let stderr = self.stderr.as_ref().and_then(|stderr| stderr.flush_deadline());
+        self.stdout.flush_deadline().into_iter().chain(stderr).min()
+    }
+
+    /// Writes the header of the current input file to `stream` unless it already has been.
+    fn write_header(&mut self, stream: Stream) -> Result<()> {
+        let Some(header) = self.header.take() else {
+            return Ok(());
+        };
+        if !self.headed.contains(&stream) {
+            if self.separated.contains(&stream) {
+                self.get(stream).writeln("")?;
+            } else {
+                self.separated.push(stream);
+            }
+            self.get(stream).writeln(&header)?;
+            self.headed.push(stream);
+        }
+        self.header = Some(header);
+        Ok(())
+    }
+}
+
+/// Reads each of the `files`, or standard input if there are none, in its entirety, decoding it
+/// from `encoding`, and applies the patterns across line boundaries. `process_match` produces the
+/// output of each individual match, in the order in which the matches occur.
+fn process_multiline<F>(
+    files: &[String],
+    encoding: &'static Encoding,
+    patterns: &Patterns,
+    process_match: &F,
+    writers: &mut Writers,
+) -> Result<()>
+where
+    F: Fn(&dyn Captures) -> Result<Option<Emitted>>,
+{
+    let process_text = |text: &str, writers: &mut Writers| -> Result<()> {
+        for hits in patterns.match_captures(text) {
+            let captures_map = LineCaptures::new(&hits, text, patterns.replacements());
+            if let Some(emitted) = process_match(&captures_map)? {
+                emitted.write_routed(writers)?;
+            }
+        }
+        Ok(())
+    };
+
+    if files.is_empty() {
+        let text = read_text(STDIN_PATH, encoding)?;
+        return process_text(&text, writers);
+    }
+
+    for path in files {
+        let text = read_text(path, encoding)?;
+        writers.start_file(Path::new(path));
+        process_text(&text, writers)?;
+    }
+    Ok(())
+}
+
+/// Reads the entirety of the file at `path`, or standard input if `path` is [STDIN_PATH], as
+/// UTF-8 text decoded from `encoding`.
+fn read_text(path: &str, encoding: &'static Encoding) -> Result<String> {
+    let (reader, source): (Box<dyn Read>, _) = if path == STDIN_PATH {
+        (Box::new(io::stdin().lock()), String::from("standard input"))
+    } else {
+        let file = File::open(path).with_context(|| format!("failed to read input file: {path}"))?;
+        (Box::new(file), format!("input file: {path}"))
+    };
+    let mut text = String::new();
+
+    let result = if encoding == UTF_8 {
+        BufReader::new(reader).read_to_string(&mut text)
+    } else {
+        DecodingReader::new(reader, encoding).read_to_string(&mut text)
+    };
+    result.with_context(|| format!("failed to read {source}"))?;
+    Ok(text)
+}
+
+/// The templates of '-t, --template' followed by those of the file at `template_file`, if any, one
+/// per line. Blank lines of the file are ignored.
+pub(crate) fn read_templates(template: &[String], template_file: Option<&str>) -> Result<Vec<String>> {
+    let mut templates = template.to_vec();
+
+    if let Some(path) = template_file {
+        let contents = fs::read_to_string(path).with_context(|| format!("failed to read template file: {path}"))?;
+        templates.extend(contents.lines().filter(|l| !l.is_empty()).map(String::from));
+    }
+    Ok(templates)
+}
+
+/// Compiles the patterns of '--pattern-file' whose `contents` were read from `path`. An invalid
+/// pattern is reported along with the line of the file on which it begins.
+pub(crate) fn compile_pattern_file(contents: &str, path: &str, flags: Flags) -> Result<Vec<Regex>> {
+    let mut regexes = Vec::new();
+
+    for (line_number, pat) in parse_pattern_file(contents, flags.verbose) {
+        let re = pattern::compile(&pat, flags).with_context(|| {
+            format!("encountered invalid regular expression on line {line_number} of {path}: {pat}")
+        })?;
+        regexes.push(re);
+    }
+    Ok(regexes)
+}
+
+/// Parses the patterns of '--pattern-file' along with the line number on which each of them begins.
+/// Blank lines and lines whose first non-whitespace character is `#` are ignored. Each remaining
+/// line is a pattern unless `verbose` in which case consecutive lines form a single pattern that
+/// ends at a blank line.
+pub(crate) fn parse_pattern_file(contents: &str, verbose: bool) -> Vec<(usize, String)> {
+    let mut patterns: Vec<(usize, String)> = Vec::new();
+    let mut in_pattern = false;
+
+    for (i, line) in contents.lines().enumerate() {
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() {
+            in_pattern = false;
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            continue;
+        }
+        match patterns.last_mut() {
+            Some((_, pat)) if verbose && in_pattern => {
+                pat.push('\n');
+                pat.push_str(line);
+            }
+            _ => patterns.push((i + 1, line.to_string())),
+        }
+        in_pattern = true;
+    }
+    patterns
+}
+
+/// Collects the replacements of '--replace-map-file', if any, followed by those of '--replace-map'
+/// so that the latter take precedence when they replace the same value.
+fn read_replacements(replace_map: &[Replacement], path: Option<&str>) -> Result<HashMap<String, String>> {
+    let mut replacements = HashMap::new();
+
+    if let Some(path) = path {
+        let contents = fs::read_to_string(path).with_context(|| format!("failed to read replace map file: {path}"))?;
+
+        for (i, entry) in contents.lines().enumerate() {
+            let trimmed = entry.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let Replacement { from, to } = cli::parse_replacement(entry)
+                .with_context(|| format!("encountered invalid replacement on line {} of {path}", i + 1))?;
+            replacements.insert(from, to);
+        }
+    }
+    for Replacement { from, to } in replace_map {
+        replacements.insert(from.clone(), to.clone());
+    }
+    Ok(replacements)
+}
+
+/// Reads the newline-separated input file paths listed in the file at `path`, or standard input if
+/// `path` is `-`.
+fn read_files_from(path: &str) -> Result<Vec<String>> {
+    let contents = if path == STDIN_PATH {
+        let mut contents = String::new();
+        io::stdin()
+            .read_to_string(&mut contents)
+            .context("failed to read list of input files from standard input")?;
+        contents
+    } else {
+        fs::read_to_string(path).with_context(|| format!("failed to read list of input files: {path}"))?
+    };
+    parse_files_from(&contents, path)
+}
+
+/// Parses the newline-separated input file paths in `contents` which were read from `source`.
+/// Every entry must be an existing file.
+pub(crate) fn parse_files_from(contents: &str, source: &str) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+
+    for (i, entry) in contents.lines().enumerate() {
+        let line_number = i + 1;
+
+        if entry.trim().is_empty() {
+            return Err(format_err!("blank entry on line {line_number} of {source}"));
+        }
+        if !Path::new(entry).is_file() {
+            return Err(format_err!(
+                "no such input file on line {line_number} of {source}: {entry}"
+            ));
+        }
+        files.push(entry.to_string());
+    }
+    Ok(files)
+}
+
+/// Transforms `captures_map` using each of the `templates` and joins the results. The Nth separator
+/// is placed between the results of the Nth and following template. If there are fewer separators
+/// than needed the last separator is used for the remaining joins, and if there are none the
+/// results are simply concatenated. If `strict` then anchors without a match are an error, see
+/// [OutputTemplate::transform_strict].
+pub(crate) fn render_templates<S: AsRef<str>>(
+    templates: &[OutputTemplate],
+    separators: &[S],
+    captures_map: &dyn Captures,
+    strict: bool,
+) -> Result<String> {
+    let mut out = String::new();
+
+    for (i, template) in templates.iter().enumerate() {
+        if i > 0 {
+            let separator = separators.get(i - 1).or(separators.last());
+            out.push_str(separator.map_or("", AsRef::as_ref));
+        }
+        if strict {
+            out.push_str(&template.transform_strict(captures_map)?);
+        } else {
+            out.push_str(&template.transform(captures_map));
+        }
+    }
+    Ok(out)
+}
+
+/// Truncates `line` to at most `max` bytes without splitting a character.
+fn truncate(line: &str, max: usize) -> &str {
+    let mut end = max.min(line.len());
+
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    &line[..end]
+}
+
+/// The distinct names of anchors, including those used as defaults, across all `templates` that
+/// aren't amongst the keys of `captures_map` in the order they first appear.
+fn unknown_anchor_names<'t, I, V>(templates: I, captures_map: &HashMap<&str, V>) -> Vec<&'t str>
+where
+    I: IntoIterator<Item = &'t OutputTemplate>,
+{
+    let mut unknown = Vec::new();
+
+    for name in templates.into_iter().flat_map(OutputTemplate::anchor_names) {
+        if !captures_map.contains_key(name) && !unknown.contains(&name) {
+            unknown.push(name);
+        }
+    }
+    unknown
+}
+
+/// The distinct names of required anchors across all `templates` that aren't amongst the keys of
+/// `captures_map` in the order they first appear. Anchors nested within default values only affect
+/// the default and so aren't considered.
+fn unknown_required_anchor_names<'t, I, V>(templates: I, captures_map: &HashMap<&str, V>) -> Vec<&'t str>
+where
+    I: IntoIterator<Item = &'t OutputTemplate>,
+{
+    let mut unknown = Vec::new();
+
+    for anchor in templates.into_iter().flat_map(OutputTemplate::anchors) {
+        let name = anchor.name.as_str();
+
+        if anchor.required && !captures_map.contains_key(name) && !unknown.contains(&name) {
+            unknown.push(name);
+        }
+    }
+    unknown
+}
+
+/// Whether the first match of the capture of every one of the `conditions` satisfies it. A capture
+/// without a match doesn't satisfy any condition.
+fn passes_where(captures_map: &dyn Captures, conditions: &[Condition]) -> bool {
+    conditions.iter().all(|condition| {
+        captures_map
+            .get(condition.capture.as_str())
+            .and_then(|vals| vals.first())
+            .is_some_and(|val| condition.is_satisfied_by(&val))
+    })
+}
+
+/// The stream of the first of `routes` whose condition the first match of its capture satisfies,
+/// or stdout if there is none.
+fn route_stream(captures_map: &dyn Captures, routes: &[Route]) -> Stream {
+    routes
+        .iter()
+        .find(|route| passes_where(captures_map, slice::from_ref(&route.condition)))
+        .map_or(Stream::Stdout, |route| route.stream)
+}
+
+/// Whether or not the captures of a line satisfy the capture names that are required to have a
+/// match according to `require_mode`.
+pub(crate) fn passes_require<S: AsRef<str>>(
+    captures_map: &dyn Captures,
+    required: &[S],
+    require_mode: RequireMode,
+) -> bool {
+    let has_match = |capname: &S| captures_map.get(capname.as_ref()).is_some_and(|c| !c.is_empty());
+
+    match require_mode {
+        RequireMode::All => required.iter().all(has_match),
+        RequireMode::Any => required.iter().any(has_match),
+    }
+}
+
+/// Whether none of the capture names of `captures_map` have a match.
+fn all_empty(captures_map: &dyn Captures) -> bool {
+    captures_map.iter().all(|(_, vals)| vals.is_empty())
+}
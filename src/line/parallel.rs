@@ -0,0 +1,92 @@
+use super::{Emitted, Writers};
+use crate::{
+    pattern::{CapturesBuffer, Patterns},
+    scanner::{InputFormat, MultiFileScanner, ScanEvent},
+};
+use anyhow::{format_err, Result};
+use std::{
+    path::Path,
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+/// Processes `files` concurrently using up to `jobs` worker threads, each of which applies
+/// `process_line` to every line, as read according to `format`, of the file it is working on. Output
+/// is funneled to `writers` on the current thread in file order so that the output is identical to
+/// processing the files serially; output of files that finish early is held in memory until all
+/// preceding files have been written.
+pub fn process_files<F>(
+    files: &[String],
+    format: InputFormat,
+    jobs: usize,
+    patterns: &Patterns,
+    process_line: &F,
+    writers: &mut Writers,
+) -> Result<()>
+where
+    F: Fn(&str, u64, &mut CapturesBuffer) -> Result<Option<Emitted>> + Sync,
+{
+    let (senders, receivers): (Vec<_>, Vec<_>) = files.iter().map(|_| mpsc::channel::<Result<Emitted>>()).unzip();
+
+    // Each worker takes ownership of the sender of the file that it processes so that the
+    // corresponding receiver disconnects once the file is done.
+    let work = Mutex::new(files.iter().zip(senders));
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(files.len()) {
+            scope.spawn(|| {
+                let mut buffer = CapturesBuffer::new(patterns);
+
+                loop {
+                    let Some((file, sender)) = work.lock().ok().and_then(|mut w| w.next()) else {
+                        break;
+                    };
+                    if process_file(file, format, process_line, &mut buffer, &sender).is_err() {
+                        // The receiving end hung up due to an error so there's no need to continue.
+                        break;
+                    }
+                }
+            });
+        }
+
+        for (file, receiver) in files.iter().zip(receivers) {
+            writers.start_file(Path::new(file));
+
+            for emitted in receiver {
+                emitted?.write_routed(writers)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Processes a single file, sending each output line through `sender`. Returns an error only if
+/// the receiving end has hung up.
+fn process_file<F>(
+    file: &str,
+    format: InputFormat,
+    process_line: &F,
+    buffer: &mut CapturesBuffer,
+    sender: &mpsc::Sender<Result<Emitted>>,
+) -> Result<()>
+where
+    F: Fn(&str, u64, &mut CapturesBuffer) -> Result<Option<Emitted>>,
+{
+    let events = match MultiFileScanner::init_events(&[file], format) {
+        Ok(events) => events,
+        Err(e) => return Ok(sender.send(Err(e))?),
+    };
+    for event in events {
+        let (line, offset) = match event {
+            ScanEvent::NewFile(_) => continue,
+            ScanEvent::Line { line, offset } => (line, offset),
+            ScanEvent::ReadError(e) => return Ok(sender.send(Err(format_err!("failed to read {file}: {e}")))?),
+        };
+        match process_line(&line, offset, buffer) {
+            Ok(Some(out)) => sender.send(Ok(out))?,
+            Ok(None) => continue,
+            Err(e) => return Ok(sender.send(Err(e))?),
+        }
+    }
+    Ok(())
+}
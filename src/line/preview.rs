@@ -0,0 +1,57 @@
+use crate::{
+    captures::Captures,
+    pattern::{CapturesBuffer, Patterns},
+    scanner::ScanEvent,
+};
+use anyhow::{format_err, Result};
+use std::fmt::Write;
+
+/// Renders up to `limit` lines of `events` for human inspection: each line is followed by the values of
+/// every capture, in name order, and the output that `render` produces for those captures.
+/// Errors produced by `render` are shown in place of the output rather than being returned.
+pub fn preview<I, F>(events: I, limit: usize, patterns: &Patterns, render: F) -> Result<String>
+where
+    I: IntoIterator<Item = ScanEvent>,
+    F: Fn(&dyn Captures) -> Result<Option<String>>,
+{
+    let mut out = String::new();
+    let mut buffer = CapturesBuffer::new(patterns);
+
+    let lines = events.into_iter().filter_map(|event| match event {
+        ScanEvent::Line { line, offset } => Some(Ok((line, offset))),
+        ScanEvent::NewFile(_) => None,
+        ScanEvent::ReadError(e) => Some(Err(format_err!("failed to read input: {e}"))),
+    });
+
+    for (i, line) in lines.take(limit).enumerate() {
+        let (line, offset) = line?;
+
+        if i > 0 {
+            writeln!(out)?;
+        }
+        writeln!(out, "line {}: {line}", i + 1)?;
+
+        patterns.populate(&line, &mut buffer);
+        patterns.populate_offset(offset, &mut buffer);
+        let captures_map = &buffer.captures(&line);
+
+        let mut captures = captures_map.iter().collect::<Vec<_>>();
+        captures.sort_by_key(|(name, _)| *name);
+
+        writeln!(out, "  captures:")?;
+        for (name, vals) in captures {
+            if vals.is_empty() {
+                writeln!(out, "    {name}: (no match)")?;
+            } else {
+                writeln!(out, "    {name}: {}", vals.join(", "))?;
+            }
+        }
+
+        match render(captures_map) {
+            Ok(Some(rendered)) => writeln!(out, "  output: {rendered}")?,
+            Ok(None) => writeln!(out, "  output: (none)")?,
+            Err(e) => writeln!(out, "  error: {e:#}")?,
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,126 @@
+use crate::captures::Captures;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Counters that summarize processing for '--stats'. Counters are atomic so that they may be
+/// shared by the threads that process files concurrently.
+#[derive(Debug, Default)]
+pub struct Stats {
+    lines_read: AtomicUsize,
+    lines_emitted: AtomicUsize,
+    lines_filtered: AtomicUsize,
+    lines_too_long: AtomicUsize,
+    lines_timed_out: AtomicUsize,
+    /// Whether lines exceeding '--max-line-length' are truncated rather than skipped, if it was
+    /// provided at all.
+    truncate_long: Option<bool>,
+    /// Whether '--pattern-timeout' was provided.
+    pattern_timeout: bool,
+    /// The number of lines on which each capture had at least one match, keyed by capture name.
+    captures: BTreeMap<String, AtomicUsize>,
+}
+
+impl Stats {
+    /// Initializes the counters with a per-capture counter for each of the `capture_names`.
+    pub fn new<'a, I: IntoIterator<Item = &'a str>>(capture_names: I) -> Self {
+        let captures = capture_names
+            .into_iter()
+            .map(|name| (name.to_string(), AtomicUsize::new(0)))
+            .collect();
+
+        Self {
+            captures,
+            ..Default::default()
+        }
+    }
+
+    /// Also report the number of lines that exceeded '--max-line-length', which were truncated if
+    /// `truncate` and skipped otherwise.
+    pub fn with_max_line_length(mut self, truncate: bool) -> Self {
+        self.truncate_long = Some(truncate);
+        self
+    }
+
+    /// Also report the number of lines that were skipped by '--pattern-timeout'.
+    pub fn with_pattern_timeout(mut self) -> Self {
+        self.pattern_timeout = true;
+        self
+    }
+
+    /// Records that a line was read along with which captures matched on it.
+    pub fn line_read(&self, captures_map: &dyn Captures) {
+        self.lines_read.fetch_add(1, Ordering::Relaxed);
+
+        for (name, vals) in captures_map.iter() {
+            if vals.is_empty() {
+                continue;
+            }
+            if let Some(count) = self.captures.get(name) {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Records that a line produced output.
+    pub fn line_emitted(&self) {
+        self.lines_emitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a line exceeded '--max-line-length'. A line that was skipped is also recorded as
+    /// read as it otherwise wouldn't be.
+    pub fn line_too_long(&self) {
+        self.lines_too_long.fetch_add(1, Ordering::Relaxed);
+
+        if self.truncate_long == Some(false) {
+            self.lines_read.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records that a line was skipped because its patterns exceeded '--pattern-timeout'. The line
+    /// is also recorded as read as it otherwise wouldn't be.
+    pub fn line_timed_out(&self) {
+        self.lines_timed_out.fetch_add(1, Ordering::Relaxed);
+        self.lines_read.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a line was omitted because it didn't satisfy '--require' or '--where'.
+    pub fn line_filtered(&self) {
+        self.lines_filtered.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lines read: {}", self.lines_read.load(Ordering::Relaxed))?;
+        writeln!(f, "lines emitted: {}", self.lines_emitted.load(Ordering::Relaxed))?;
+        writeln!(
+            f,
+            "lines filtered by --require or --where: {}",
+            self.lines_filtered.load(Ordering::Relaxed)
+        )?;
+        if let Some(truncate) = self.truncate_long {
+            let action = if truncate { "truncated" } else { "skipped" };
+            writeln!(
+                f,
+                "lines {action} by --max-line-length: {}",
+                self.lines_too_long.load(Ordering::Relaxed)
+            )?;
+        }
+        if self.pattern_timeout {
+            writeln!(
+                f,
+                "lines skipped by --pattern-timeout: {}",
+                self.lines_timed_out.load(Ordering::Relaxed)
+            )?;
+        }
+        writeln!(f, "lines matched per capture:")?;
+
+        for (name, count) in &self.captures {
+            writeln!(f, "  {name}: {}", count.load(Ordering::Relaxed))?;
+        }
+        Ok(())
+    }
+}
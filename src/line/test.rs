@@ -0,0 +1,683 @@
+use super::context::ContextWindow;
+use super::delimited::to_record;
+use super::explain::{explain, list_captures};
+use super::follow::Input;
+use super::json::captures_to_json;
+use super::preview::preview;
+use super::stats::Stats;
+use super::{
+    compile_pattern_file, parse_files_from, parse_pattern_file, passes_where, process_lines, read_templates,
+    render_templates, route_stream, truncate, unknown_anchor_names, unknown_required_anchor_names, Emitted, Writers,
+};
+use crate::captures::Captures;
+use crate::cli::{Cli, Comparison, Condition, Route, Stream};
+use crate::pattern::{Flags, Patterns};
+use crate::scanner::ScanEvent;
+use crate::template::OutputTemplate;
+use crate::tty::{BlockBufferedOutputWriter, OutputWriter, TtyContext};
+use clap::Parser;
+use indoc::indoc;
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Collects output in memory that can be inspected from another thread.
+#[derive(Default, Clone)]
+struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_captures_to_json() {
+    let mut captures_map = HashMap::new();
+    captures_map.insert("lvl", vec!["INFO"]);
+    captures_map.insert("ip", vec!["127.0.0.1", "10.0.0.1"]);
+    captures_map.insert("msg", vec![]);
+
+    let Value::Object(object) = captures_to_json(&captures_map) else {
+        panic!("expected captures to serialize into a JSON object");
+    };
+    assert_eq!(object.len(), 3);
+    assert_eq!(object["lvl"], Value::String("INFO".to_string()));
+    assert_eq!(
+        object["ip"],
+        Value::Array(vec![
+            Value::String("127.0.0.1".to_string()),
+            Value::String("10.0.0.1".to_string())
+        ])
+    );
+    assert_eq!(object["msg"], Value::Null);
+}
+
+#[test]
+fn test_delimited_record() {
+    let record = to_record(["lvl", "msg"], b',').unwrap();
+    assert_eq!(record, "lvl,msg");
+
+    let record = to_record(["INFO", "hello, \"world\""], b',').unwrap();
+    assert_eq!(record, r#"INFO,"hello, ""world""""#);
+
+    let record = to_record(["INFO", "", "a,b"], b'\t').unwrap();
+    assert_eq!(record, "INFO\t\ta,b");
+}
+
+#[test]
+fn test_render_templates() {
+    let templates = ["{a}", "{b}", "{c}", "{d}"]
+        .iter()
+        .map(|t| OutputTemplate::parse(t).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut captures_map = HashMap::new();
+    captures_map.insert("a", vec!["1"]);
+    captures_map.insert("b", vec!["2"]);
+    captures_map.insert("c", vec!["3"]);
+    captures_map.insert("d", vec!["4"]);
+
+    assert_eq!(
+        render_templates::<&str>(&templates, &[], &captures_map, false).unwrap(),
+        "1234"
+    );
+    assert_eq!(
+        render_templates(&templates, &[","], &captures_map, false).unwrap(),
+        "1,2,3,4"
+    );
+    assert_eq!(
+        render_templates(&templates, &[" ", "\t"], &captures_map, false).unwrap(),
+        "1 2\t3\t4"
+    );
+    assert_eq!(
+        render_templates(&templates, &["a", "b", "c", "d"], &captures_map, false).unwrap(),
+        "1a2b3c4"
+    );
+    assert_eq!(
+        render_templates(&templates[..1], &[","], &captures_map, false).unwrap(),
+        "1"
+    );
+}
+
+#[test]
+fn test_explain() {
+    let raw_templates = [r#"[{(red|lalign(5)):lvl}] {ip[-1] || $HOST || "none"} {!$2}"#];
+    let templates = raw_templates
+        .iter()
+        .map(|t| OutputTemplate::parse(t).unwrap())
+        .collect::<Vec<_>>();
+    let regexes = [Regex::new(r"level=(?<lvl>\w+) (\d+)").unwrap()];
+
+    assert_eq!(
+        explain(&raw_templates, &templates, &regexes).unwrap(),
+        indoc! {r#"
+            template 1: [{(red|lalign(5)):lvl}] {ip[-1] || $HOST || "none"} {!$2}
+              anchor {(red|lalign(5)):lvl}
+                name: lvl
+                attribute: Red
+                attribute: align left to width 5
+              anchor {ip[-1] || $HOST || "none"}
+                name: ip
+                index: [-1]
+                default: $HOST
+                default: "none"
+              anchor {!$2}
+                name: $2
+                required: true
+            pattern 1: level=(?<lvl>\w+) (\d+)
+              $1: lvl
+              $2: (unnamed)
+        "#}
+    );
+}
+
+#[test]
+fn test_list_captures() {
+    let regexes = [
+        Regex::new(r"level=(?<lvl>\w+) (?<msg>.*)").unwrap(),
+        Regex::new(r"(\d+) (?<status>\d+) (?<lvl>[A-Z]+)").unwrap(),
+        Regex::new(r"(?<lvl>\w+):(?<lvl2>\w+)").unwrap(),
+    ];
+
+    assert_eq!(
+        list_captures(&regexes).unwrap(),
+        indoc! {"
+            lvl     patterns 1, 2, 3
+            lvl2    pattern 3
+            msg     pattern 1
+            status  pattern 2
+        "}
+    );
+    assert_eq!(list_captures(&[Regex::new(r"(\d+)").unwrap()]).unwrap(), "");
+}
+
+#[test]
+fn test_unknown_anchor_names() {
+    let templates = ["{lvl} {mesage}", "{msg || mesage || ip[0]} {src}"]
+        .iter()
+        .map(|t| OutputTemplate::parse(t).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut captures_map: HashMap<&str, Vec<&str>> = HashMap::new();
+    captures_map.insert("lvl", vec![]);
+    captures_map.insert("msg", vec![]);
+
+    assert_eq!(
+        unknown_anchor_names(&templates, &captures_map),
+        vec!["mesage", "ip", "src"]
+    );
+
+    captures_map.insert("mesage", vec![]);
+    captures_map.insert("ip", vec![]);
+    captures_map.insert("src", vec![]);
+    assert!(unknown_anchor_names(&templates, &captures_map).is_empty());
+}
+
+#[test]
+fn test_unknown_required_anchor_names() {
+    let templates = ["{!lvl} {!mesage} {src}", "{!mesage} {msg || \"{!ip}\"}"]
+        .iter()
+        .map(|t| OutputTemplate::parse(t).unwrap())
+        .collect::<Vec<_>>();
+
+    let mut captures_map: HashMap<&str, Vec<&str>> = HashMap::new();
+    captures_map.insert("lvl", vec![]);
+    captures_map.insert("msg", vec![]);
+
+    assert_eq!(unknown_required_anchor_names(&templates, &captures_map), vec!["mesage"]);
+
+    captures_map.insert("mesage", vec![]);
+    assert!(unknown_required_anchor_names(&templates, &captures_map).is_empty());
+}
+
+#[test]
+fn test_read_templates() {
+    let path = std::env::temp_dir().join(format!("grits-templates-{}", std::process::id()));
+    std::fs::write(&path, "{lvl}\n\n{(red):msg}\n").unwrap();
+    let template_file = path.to_str();
+
+    assert_eq!(read_templates(&[], template_file).unwrap(), ["{lvl}", "{(red):msg}"]);
+
+    // Templates of the file follow those provided inline.
+    let inline = [String::from("{ts}")];
+    assert_eq!(
+        read_templates(&inline, template_file).unwrap(),
+        ["{ts}", "{lvl}", "{(red):msg}"]
+    );
+    assert_eq!(read_templates(&inline, None).unwrap(), ["{ts}"]);
+
+    std::fs::remove_file(&path).unwrap();
+    let err = read_templates(&inline, template_file).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("failed to read template file: {}", path.display())
+    );
+}
+
+#[test]
+fn test_passthrough() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("grits-passthrough-input-{}", std::process::id()));
+    let output = dir.join(format!("grits-passthrough-output-{}", std::process::id()));
+    std::fs::write(
+        &input,
+        "level=INFO msg=started\nnothing to see\nmsg=orphaned\nlevel=DEBUG msg=\nlevel=WARN msg=disk\n",
+    )
+    .unwrap();
+
+    let args = [
+        "grits",
+        "-p",
+        r"level=(?<lvl>\w+)",
+        "-p",
+        r"msg=(?<msg>\S*)",
+        "-t",
+        "{msg}",
+        "-r",
+        "lvl",
+        "--passthrough",
+        "--output-file",
+        output.to_str().unwrap(),
+        input.to_str().unwrap(),
+    ];
+    process_lines(&mut TtyContext::new(), &Cli::try_parse_from(args).unwrap()).unwrap();
+
+    // Lines without any match, lines that fail '--require', and lines whose template renders
+    // empty are written unchanged whereas matched lines are still transformed.
+    assert_eq!(
+        std::fs::read_to_string(&output).unwrap(),
+        "started\nnothing to see\nmsg=orphaned\nlevel=DEBUG msg=\ndisk\n"
+    );
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn test_json_output() {
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("grits-json-input-{}", std::process::id()));
+    let output = dir.join(format!("grits-json-output-{}", std::process::id()));
+    std::fs::write(
+        &input,
+        "level=info ip=127.0.0.1 ip=10.0.0.1
+nothing
+level=warn
+",
+    )
+    .unwrap();
+
+    for format in ["json", "json-lines"] {
+        let args = [
+            "grits",
+            "-p",
+            r"level=(?<lvl>\w+)",
+            "-p",
+            r"ip=(?<ip>[^ ]+)",
+            "--output",
+            format,
+            "--output-file",
+            output.to_str().unwrap(),
+            input.to_str().unwrap(),
+        ];
+        process_lines(&mut TtyContext::new(), &Cli::try_parse_from(args).unwrap()).unwrap();
+
+        // Each processed line is a single compact object on its own line.
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "{\"ip\":[\"127.0.0.1\",\"10.0.0.1\"],\"lvl\":\"info\"}\n{\"ip\":null,\"lvl\":\"warn\"}\n",
+            "{format}"
+        );
+    }
+
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&output).unwrap();
+}
+
+#[test]
+fn test_compile_pattern_file() {
+    let contents = "# severity\n^level=(?<lvl>\\w+)\n\n   \n  # source\nsrc=(?<src>\\S+)\n";
+    let regexes = compile_pattern_file(contents, "patterns.txt", Flags::default()).unwrap();
+    let regexes = regexes.iter().map(Regex::as_str).collect::<Vec<_>>();
+    assert_eq!(regexes, [r"^level=(?<lvl>\w+)", r"src=(?<src>\S+)"]);
+
+    let contents = "# severity\n^level=(?<lvl>\\w+)\n\nsrc=(?<src>\\S+\n";
+    let err = compile_pattern_file(contents, "patterns.txt", Flags::default()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        r"encountered invalid regular expression on line 4 of patterns.txt: src=(?<src>\S+"
+    );
+}
+
+#[test]
+fn test_parse_pattern_file() {
+    let contents = "# severity\n^level=(?<lvl>\\w+)\n\n  # addresses\nsrc=(?<src>\\S+)  # source\ndst=(?<dst>\\S+)\n";
+
+    assert_eq!(
+        parse_pattern_file(contents, false),
+        vec![
+            (2, String::from(r"^level=(?<lvl>\w+)")),
+            (5, String::from(r"src=(?<src>\S+)  # source")),
+            (6, String::from(r"dst=(?<dst>\S+)")),
+        ]
+    );
+
+    // Comment lines don't end a verbose pattern but blank lines do.
+    assert_eq!(
+        parse_pattern_file(contents, true),
+        vec![
+            (2, String::from(r"^level=(?<lvl>\w+)")),
+            (5, String::from("src=(?<src>\\S+)  # source\ndst=(?<dst>\\S+)")),
+        ]
+    );
+    assert!(parse_pattern_file("\n  \n# nothing\n", true).is_empty());
+}
+
+#[test]
+fn test_parse_files_from() {
+    let files = parse_files_from("Cargo.toml\nsrc/lib.rs\n", "list.txt").unwrap();
+    assert_eq!(files, vec!["Cargo.toml", "src/lib.rs"]);
+    assert!(parse_files_from("", "list.txt").unwrap().is_empty());
+
+    let err = parse_files_from("Cargo.toml\n\nsrc/lib.rs", "list.txt").unwrap_err();
+    assert_eq!(err.to_string(), "blank entry on line 2 of list.txt");
+
+    let err = parse_files_from("Cargo.toml\nsrc/lib.rs\nnope.txt", "-").unwrap_err();
+    assert_eq!(err.to_string(), "no such input file on line 3 of -: nope.txt");
+}
+
+#[test]
+fn test_stats() {
+    let stats = Stats::new(["lvl", "ip"]);
+
+    let mut captures_map = HashMap::new();
+    captures_map.insert("lvl", vec!["INFO"]);
+    captures_map.insert("ip", vec![]);
+    stats.line_read(&captures_map);
+    stats.line_emitted();
+
+    captures_map.insert("ip", vec!["127.0.0.1", "10.0.0.1"]);
+    stats.line_read(&captures_map);
+    stats.line_filtered();
+
+    assert_eq!(
+        stats.to_string(),
+        indoc! {"
+            lines read: 2
+            lines emitted: 1
+            lines filtered by --require or --where: 1
+            lines matched per capture:
+              ip: 1
+              lvl: 2
+        "}
+    );
+}
+
+#[test]
+fn test_stats_max_line_length() {
+    let captures_map = HashMap::from([("lvl", vec!["INFO"])]);
+
+    let stats = Stats::new(["lvl"]).with_max_line_length(false);
+    stats.line_too_long();
+    stats.line_read(&captures_map);
+    assert!(stats.to_string().contains("lines read: 2\n"));
+    assert!(stats.to_string().contains("lines skipped by --max-line-length: 1\n"));
+
+    // Truncated lines are processed and thus recorded as read like any other line.
+    let stats = Stats::new(["lvl"]).with_max_line_length(true);
+    stats.line_too_long();
+    stats.line_read(&captures_map);
+    assert!(stats.to_string().contains("lines read: 1\n"));
+    assert!(stats.to_string().contains("lines truncated by --max-line-length: 1\n"));
+}
+
+#[test]
+fn test_stats_pattern_timeout() {
+    let captures_map = HashMap::from([("lvl", vec!["INFO"])]);
+
+    let stats = Stats::new(["lvl"]);
+    assert!(!stats.to_string().contains("--pattern-timeout"));
+
+    let stats = Stats::new(["lvl"]).with_pattern_timeout();
+    stats.line_timed_out();
+    stats.line_read(&captures_map);
+    assert!(stats.to_string().contains("lines read: 2\n"));
+    assert!(stats.to_string().contains("lines skipped by --pattern-timeout: 1\n"));
+    assert!(stats.to_string().contains("  lvl: 1\n"));
+}
+
+impl OutputWriter for Vec<String> {
+    fn writeln(&mut self, txt: &str) -> anyhow::Result<()> {
+        self.push(txt.to_string());
+        Ok(())
+    }
+}
+
+/// Passes each of `lines` through a [ContextWindow], treating those that contain "match" as having
+/// produced output in which case the output is the line in upper case.
+fn write_with_context(lines: &[&str], before: usize, after: usize) -> Vec<String> {
+    let mut context = ContextWindow::new(before, after);
+    let mut written = Vec::new();
+
+    for line in lines {
+        let emitted = line.contains("match").then(|| Emitted {
+            out: line.to_uppercase(),
+            sort_key: None,
+            stream: Stream::Stdout,
+        });
+        context.write(line, emitted, &mut written).unwrap();
+    }
+    written
+}
+
+#[test]
+fn test_context_window() {
+    let lines = ["a", "b", "match c", "d", "e", "f", "g", "match h", "i"];
+
+    assert_eq!(
+        write_with_context(&lines, 1, 1),
+        vec!["b", "MATCH C", "d", "--", "g", "MATCH H", "i"]
+    );
+    assert_eq!(
+        write_with_context(&lines, 0, 2),
+        vec!["MATCH C", "d", "e", "--", "MATCH H", "i"]
+    );
+    assert_eq!(
+        write_with_context(&lines, 2, 0),
+        vec!["a", "b", "MATCH C", "--", "f", "g", "MATCH H"]
+    );
+
+    // Overlapping windows are merged rather than writing lines twice.
+    assert_eq!(
+        write_with_context(&lines, 3, 3),
+        vec!["a", "b", "MATCH C", "d", "e", "f", "g", "MATCH H", "i"]
+    );
+
+    // Adjacent output isn't separated.
+    let lines = ["match a", "match b", "c", "d", "match e"];
+    assert_eq!(
+        write_with_context(&lines, 0, 1),
+        vec!["MATCH A", "MATCH B", "c", "--", "MATCH E"]
+    );
+    assert!(write_with_context(&["a", "b"], 1, 1).is_empty());
+}
+
+#[test]
+fn test_context_window_reset() {
+    let write = |separate: bool| {
+        let mut context = ContextWindow::new(1, 1);
+        let mut written = Vec::new();
+
+        for file in [["a", "match b", "c"], ["d", "match e", "f"]] {
+            context.reset(separate);
+            for line in file {
+                let emitted = line.contains("match").then(|| Emitted {
+                    out: line.to_uppercase(),
+                    sort_key: None,
+                    stream: Stream::Stdout,
+                });
+                context.write(line, emitted, &mut written).unwrap();
+            }
+        }
+        written
+    };
+
+    // Lines of the previous file aren't written as context even though they'd be contiguous.
+    assert_eq!(write(true), vec!["a", "MATCH B", "c", "--", "d", "MATCH E", "f"]);
+    assert_eq!(write(false), vec!["a", "MATCH B", "c", "d", "MATCH E", "f"]);
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("level=info", 5), "level");
+    assert_eq!(truncate("level", 10), "level");
+    // '→' is three bytes so it is dropped entirely rather than split.
+    assert_eq!(truncate("a→b", 2), "a");
+    assert_eq!(truncate("a→b", 4), "a→");
+}
+
+#[test]
+fn test_preview() {
+    let regexes = vec![
+        Regex::new(r"level=(?<lvl>\w+)").unwrap(),
+        Regex::new(r"msg=(?<msg>\w+)").unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, Flags::default());
+    let lines = ["level=INFO msg=hi", "level=WARN", "nothing", "level=ERROR"]
+        .into_iter()
+        .map(|line| ScanEvent::Line {
+            line: String::from(line),
+            offset: 0,
+        });
+
+    let render = |captures_map: &dyn Captures| match captures_map.get("lvl").and_then(|v| v.first()) {
+        Some(lvl) if lvl == "WARN" => Err(anyhow::format_err!("no warnings")),
+        Some(lvl) => Ok(Some(lvl.to_string())),
+        None => Ok(None),
+    };
+
+    assert_eq!(
+        preview(lines, 3, &patterns, render).unwrap(),
+        indoc! {"
+            line 1: level=INFO msg=hi
+              captures:
+                lvl: INFO
+                msg: hi
+              output: INFO
+
+            line 2: level=WARN
+              captures:
+                lvl: WARN
+                msg: (no match)
+              error: no warnings
+
+            line 3: nothing
+              captures:
+                lvl: (no match)
+                msg: (no match)
+              output: (none)
+        "}
+    );
+}
+
+#[test]
+fn test_passes_where() {
+    let condition = |capture: &str, op, value: &str| Condition {
+        capture: capture.to_string(),
+        op,
+        value: value.to_string(),
+    };
+    let mut captures_map = HashMap::new();
+    captures_map.insert("lvl", vec!["ERROR"]);
+    captures_map.insert("status", vec!["503"]);
+    captures_map.insert("latency", vec!["95.5", "150"]);
+    captures_map.insert("host", vec![]);
+
+    assert!(passes_where(&captures_map, &[]));
+    assert!(passes_where(
+        &captures_map,
+        &[condition("lvl", Comparison::Eq, "ERROR")]
+    ));
+    assert!(!passes_where(
+        &captures_map,
+        &[condition("lvl", Comparison::Eq, "error")]
+    ));
+    assert!(passes_where(
+        &captures_map,
+        &[
+            condition("lvl", Comparison::Ne, "INFO"),
+            condition("status", Comparison::Ge, "500"),
+            condition("latency", Comparison::Lt, "100"),
+        ]
+    ));
+    assert!(!passes_where(
+        &captures_map,
+        &[
+            condition("lvl", Comparison::Eq, "ERROR"),
+            condition("latency", Comparison::Gt, "100"),
+        ]
+    ));
+
+    // Numbers compare numerically whereas anything else compares lexicographically.
+    assert!(passes_where(
+        &captures_map,
+        &[condition("status", Comparison::Gt, "60")]
+    ));
+    assert!(passes_where(
+        &captures_map,
+        &[condition("status", Comparison::Eq, "503.0")]
+    ));
+    assert!(passes_where(&captures_map, &[condition("lvl", Comparison::Lt, "INFO")]));
+
+    // Captures without a match never satisfy a condition.
+    assert!(!passes_where(&captures_map, &[condition("host", Comparison::Ne, "a")]));
+    assert!(!passes_where(
+        &captures_map,
+        &[condition("missing", Comparison::Ne, "a")]
+    ));
+}
+
+#[test]
+fn test_route_stream() {
+    let route = |capture: &str, op, value: &str, stream| Route {
+        condition: Condition {
+            capture: capture.to_string(),
+            op,
+            value: value.to_string(),
+        },
+        stream,
+    };
+    let mut captures_map = HashMap::new();
+    captures_map.insert("lvl", vec!["ERROR"]);
+    captures_map.insert("status", vec!["503"]);
+
+    assert_eq!(route_stream(&captures_map, &[]), Stream::Stdout);
+
+    let routes = [route("lvl", Comparison::Eq, "ERROR", Stream::Stderr)];
+    assert_eq!(route_stream(&captures_map, &routes), Stream::Stderr);
+
+    let routes = [route("lvl", Comparison::Eq, "WARN", Stream::Stderr)];
+    assert_eq!(route_stream(&captures_map, &routes), Stream::Stdout);
+
+    // The first route whose condition is satisfied applies.
+    let routes = [
+        route("status", Comparison::Ge, "500", Stream::Stdout),
+        route("lvl", Comparison::Eq, "ERROR", Stream::Stderr),
+    ];
+    assert_eq!(route_stream(&captures_map, &routes), Stream::Stdout);
+}
+
+#[test]
+fn test_input_flushes_while_waiting() {
+    for flush_interval in [None, Some(Duration::from_millis(20))] {
+        let output = SharedWriter::default();
+        let writer = BlockBufferedOutputWriter::new(output.clone()).with_flush_interval(flush_interval);
+        let mut writers = Writers {
+            stdout: Box::new(writer),
+            stderr: None,
+            with_filename: false,
+            header: None,
+            headed: Vec::new(),
+            separated: Vec::new(),
+        };
+        writers.stdout.writeln("buffered").unwrap();
+
+        // The next line only arrives once the buffered output was written, or after giving up.
+        let (sender, events) = mpsc::sync_channel(1);
+        let written = output.clone();
+        let follower = thread::spawn(move || {
+            let given_up = Instant::now() + Duration::from_secs(5);
+            while written.0.lock().unwrap().is_empty() && Instant::now() < given_up {
+                thread::sleep(Duration::from_millis(1));
+            }
+            let line = String::from("next");
+            sender.send(vec![ScanEvent::Line { line, offset: 0 }]).unwrap();
+        });
+
+        let mut input = Input::Followed {
+            events,
+            batch: Vec::new().into_iter(),
+            flush_when_idle: flush_interval.is_none(),
+        };
+        assert!(matches!(
+            input.next_event(&mut writers).unwrap(),
+            Some(ScanEvent::Line { .. })
+        ));
+        assert_eq!(*output.0.lock().unwrap(), b"buffered\n", "{flush_interval:?}");
+
+        follower.join().unwrap();
+        assert!(input.next_event(&mut writers).unwrap().is_none());
+    }
+}
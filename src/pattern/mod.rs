@@ -0,0 +1,621 @@
+use crate::{
+    captures::{CaptureHit, LineCaptures},
+    cli::Column,
+};
+use regex::{Captures, Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::{
+    collections::HashMap,
+    iter,
+    time::{Duration, Instant},
+};
+
+#[cfg(test)]
+mod test;
+
+/// Prefix of capture names that refer to capture groups by their index rather than their name.
+const POSITIONAL_PREFIX: char = '$';
+
+/// Reserved capture name that refers to the entire line unless a pattern has a capture group of
+/// the same name.
+pub const LINE_CAPTURE: &str = "_line";
+
+/// Reserved capture name that refers to the byte offset at which the line begins within its input
+/// unless a pattern has a capture group of the same name.
+pub const OFFSET_CAPTURE: &str = "_offset";
+
+/// Separates the name of a pattern from the name of one of its capture groups in capture names
+/// that refer to the captures of that pattern alone, e.g. `req:status`.
+pub const NAMESPACE_SEPARATOR: char = ':';
+
+/// Prefix of capture names that refer to the byte offsets at which the matches of a named capture
+/// group begin within the line, e.g. `@start:ip`.
+pub const SPAN_START: &str = "@start:";
+
+/// Prefix of capture names that refer to the byte offsets at which the matches of a named capture
+/// group end within the line, e.g. `@end:ip`.
+pub const SPAN_END: &str = "@end:";
+
+/// The compiled user-provided patterns along with their named capture groups. When there is more
+/// than one pattern a [RegexSet] is used as a prefilter so that the comparatively expensive
+/// capture machinery only runs for the patterns that actually match a given line.
+#[derive(Debug)]
+pub struct Patterns {
+    regexes: Vec<(Regex, Vec<String>)>,
+    prefilter: Option<RegexSet>,
+    flags: Flags,
+    fields: Option<Fields>,
+    columns: Option<Columns>,
+    /// Capture groups referenced by their index, e.g. `$1`, along with the corresponding capture
+    /// name.
+    positions: Vec<(usize, String)>,
+    /// For each pattern, its capture groups that are referenced within its namespace, e.g.
+    /// `req:status`, by their index along with the corresponding capture name.
+    namespaced: Vec<Vec<(usize, String)>>,
+    /// Whether or not the entire line is exposed as [LINE_CAPTURE].
+    line_capture: bool,
+    /// Whether or not the byte offset of the line is exposed as [OFFSET_CAPTURE].
+    offset_capture: bool,
+    /// Capture names that refer to where the matches of a named capture group begin or end.
+    spans: Vec<Span>,
+    /// Capture values that are replaced in their entirety by another value.
+    replacements: HashMap<String, String>,
+    capture_names: Vec<String>,
+}
+
+/// A capture name such as `@start:ip` along with the named capture group whose matches it refers to
+/// and whether it's the end of those matches rather than the start.
+#[derive(Debug)]
+struct Span {
+    capture_name: String,
+    group: String,
+    end: bool,
+}
+
+/// The matches of each capture name, keyed by capture name.
+type Hits<'p> = HashMap<&'p str, Vec<CaptureHit>>;
+
+/// Flags that apply to every pattern.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Flags {
+    /// Match case-insensitively.
+    pub ignore_case: bool,
+    /// Patterns are applied to entire inputs rather than individual lines so `^` and `$` match at
+    /// line boundaries and `.` matches newlines.
+    pub multiline: bool,
+    /// Whitespace within patterns is ignored and `#` begins a comment that extends to the end of
+    /// the line, so that patterns can be spread across several commented lines.
+    pub verbose: bool,
+}
+
+/// Splits lines into fields on a separator, awk-style. Fields are exposed as numeric capture names
+/// where `0` is the entire line and `1` is the first field.
+#[derive(Debug)]
+struct Fields {
+    separator: Regex,
+    /// The field positions that are referenced along with the corresponding capture name.
+    positions: Vec<(usize, String)>,
+    /// The greatest referenced position; splitting stops once it is reached.
+    max_position: usize,
+}
+
+/// Slices lines into fixed-width columns. Columns are exposed as numeric capture names where `0`
+/// is the first column.
+#[derive(Debug)]
+struct Columns {
+    columns: Vec<Column>,
+    /// The column positions that are referenced along with the corresponding capture name.
+    positions: Vec<(usize, String)>,
+}
+
+impl Patterns {
+    /// Caches the capture names of each of the `regexes` and compiles the prefilter. `flags` must
+    /// be the same that the `regexes` were compiled with, see [compile], so that the prefilter
+    /// agrees with them.
+    pub fn new(regexes: Vec<Regex>, flags: Flags) -> Self {
+        let mut capture_names: Vec<String> = Vec::new();
+        let mut regexes_with_capture_names = Vec::with_capacity(regexes.len());
+
+        for regex in regexes {
+            let capnames = regex.capture_names().flatten().map(String::from).collect::<Vec<_>>();
+
+            for capture_name in &capnames {
+                if !capture_names.contains(capture_name) {
+                    capture_names.push(capture_name.clone());
+                }
+            }
+            regexes_with_capture_names.push((regex, capnames));
+        }
+
+        let prefilter = build_prefilter(&regexes_with_capture_names, flags);
+        let namespaced = vec![Vec::new(); regexes_with_capture_names.len()];
+
+        Self {
+            regexes: regexes_with_capture_names,
+            prefilter,
+            flags,
+            fields: None,
+            columns: None,
+            positions: Vec::new(),
+            namespaced,
+            line_capture: false,
+            offset_capture: false,
+            spans: Vec::new(),
+            replacements: HashMap::new(),
+            capture_names,
+        }
+    }
+
+    /// Splits each line into fields on `separator`. Only the fields whose positions appear amongst
+    /// `anchor_names` are extracted; names that aren't canonical non-negative integers are
+    /// ignored.
+    pub fn with_field_separator<'n>(
+        mut self,
+        separator: Regex,
+        anchor_names: impl IntoIterator<Item = &'n str>,
+    ) -> Self {
+        let mut positions: Vec<(usize, String)> = Vec::new();
+
+        for name in anchor_names {
+            let Some(position) = name.parse::<usize>().ok().filter(|p| p.to_string() == name) else {
+                continue;
+            };
+            if positions.iter().any(|(p, _)| *p == position) {
+                continue;
+            }
+            positions.push((position, name.to_string()));
+
+            if !self.capture_names.iter().any(|n| n == name) {
+                self.capture_names.push(name.to_string());
+            }
+        }
+        let max_position = positions.iter().map(|(p, _)| *p).max().unwrap_or_default();
+
+        self.fields = Some(Fields {
+            separator,
+            positions,
+            max_position,
+        });
+        self
+    }
+
+    /// Slices each line into `columns`. Only the columns whose positions appear amongst
+    /// `anchor_names` are extracted; names that aren't canonical non-negative integers or that are
+    /// beyond the last column are ignored.
+    pub fn with_columns<'n>(mut self, columns: Vec<Column>, anchor_names: impl IntoIterator<Item = &'n str>) -> Self {
+        let mut positions: Vec<(usize, String)> = Vec::new();
+
+        for name in anchor_names {
+            let Some(position) = name
+                .parse::<usize>()
+                .ok()
+                .filter(|p| p.to_string() == name && *p < columns.len())
+            else {
+                continue;
+            };
+            if positions.iter().any(|(p, _)| *p == position) {
+                continue;
+            }
+            positions.push((position, name.to_string()));
+
+            if !self.capture_names.iter().any(|n| n == name) {
+                self.capture_names.push(name.to_string());
+            }
+        }
+        self.columns = Some(Columns { columns, positions });
+        self
+    }
+
+    /// Exposes the capture groups of every pattern by their index, e.g. `$1` for the first group and
+    /// `$0` for the entire match, regardless of whether or not the groups are named. Only the
+    /// groups whose positional names appear amongst `anchor_names` are extracted.
+    pub fn with_positional_groups<'n>(mut self, anchor_names: impl IntoIterator<Item = &'n str>) -> Self {
+        for name in anchor_names {
+            let Some(position) = positional_group(name) else {
+                continue;
+            };
+            if self.positions.iter().any(|(_, n)| n == name) {
+                continue;
+            }
+            self.positions.push((position, name.to_string()));
+            self.capture_names.push(name.to_string());
+        }
+        self
+    }
+
+    /// Exposes the capture groups of individual patterns within the namespace of the pattern, e.g.
+    /// `req:status` for the `status` group of the pattern named `req`, so that they only hold the
+    /// matches of that pattern. `namespaces` pairs the index of each named pattern with its name;
+    /// several patterns may share a name. Groups may also be referenced by their index, e.g.
+    /// `req:$1`. Only the groups whose namespaced names appear amongst `anchor_names` are extracted.
+    pub fn with_namespaces<'n>(
+        mut self,
+        namespaces: &[(usize, &str)],
+        anchor_names: impl IntoIterator<Item = &'n str>,
+    ) -> Self {
+        for name in anchor_names {
+            let Some((namespace, capture_name)) = name.split_once(NAMESPACE_SEPARATOR) else {
+                continue;
+            };
+            if self.capture_names.iter().any(|n| n == name) {
+                continue;
+            }
+            let mut exposed = false;
+
+            for (i, _) in namespaces.iter().filter(|(_, ns)| *ns == namespace) {
+                let regex = &self.regexes[*i].0;
+                let group = positional_group(capture_name)
+                    .filter(|position| *position < regex.captures_len())
+                    .or_else(|| regex.capture_names().position(|n| n == Some(capture_name)));
+
+                if let Some(group) = group {
+                    self.namespaced[*i].push((group, name.to_string()));
+                    exposed = true;
+                }
+            }
+            if exposed {
+                self.capture_names.push(name.to_string());
+            }
+        }
+        self
+    }
+
+    /// Exposes the entire line as [LINE_CAPTURE] if it appears amongst `anchor_names` and none of
+    /// the patterns have a capture group of the same name, which would otherwise take precedence.
+    pub fn with_line_capture<'n>(mut self, anchor_names: impl IntoIterator<Item = &'n str>) -> Self {
+        let referenced = anchor_names.into_iter().any(|name| name == LINE_CAPTURE);
+
+        if referenced && !self.capture_names.iter().any(|name| name == LINE_CAPTURE) {
+            self.line_capture = true;
+            self.capture_names.push(LINE_CAPTURE.to_string());
+        }
+        self
+    }
+
+    /// Exposes the byte offset of each line as [OFFSET_CAPTURE] if it appears amongst
+    /// `anchor_names` and none of the patterns have a capture group of the same name. Entire
+    /// inputs that are matched with [Flags::multiline] have no lines to speak of so there is no
+    /// offset to expose.
+    pub fn with_offset_capture<'n>(mut self, anchor_names: impl IntoIterator<Item = &'n str>) -> Self {
+        let referenced = anchor_names.into_iter().any(|name| name == OFFSET_CAPTURE);
+
+        if referenced && !self.flags.multiline && !self.capture_names.iter().any(|name| name == OFFSET_CAPTURE) {
+            self.offset_capture = true;
+            self.capture_names.push(OFFSET_CAPTURE.to_string());
+        }
+        self
+    }
+
+    /// Populates [OFFSET_CAPTURE] with the byte `offset` at which the line begins if it's exposed.
+    pub fn populate_offset(&self, offset: u64, buffer: &mut CapturesBuffer) {
+        if !self.offset_capture {
+            return;
+        }
+        if let Some(hits) = buffer.hits.get_mut(OFFSET_CAPTURE) {
+            hits.push(CaptureHit::Offset(offset));
+        }
+    }
+
+    /// Exposes where the matches of each named capture group begin and end within the line as
+    /// [SPAN_START] and [SPAN_END] captures, e.g. `@start:ip`, for those that appear amongst
+    /// `anchor_names`. Like [OFFSET_CAPTURE], spans aren't exposed for inputs that are matched with
+    /// [Flags::multiline].
+    pub fn with_span_captures<'n>(mut self, anchor_names: impl IntoIterator<Item = &'n str>) -> Self {
+        if self.flags.multiline {
+            return self;
+        }
+        for name in anchor_names {
+            let (group, end) = match (name.strip_prefix(SPAN_START), name.strip_prefix(SPAN_END)) {
+                (Some(group), _) => (group, false),
+                (_, Some(group)) => (group, true),
+                _ => continue,
+            };
+            if self.capture_names.iter().any(|n| n == name)
+                || !self
+                    .regexes
+                    .iter()
+                    .any(|(_, capnames)| capnames.iter().any(|n| n == group))
+            {
+                continue;
+            }
+            self.spans.push(Span {
+                capture_name: name.to_string(),
+                group: group.to_string(),
+                end,
+            });
+            self.capture_names.push(name.to_string());
+        }
+        self
+    }
+
+    /// Replaces every capture value that is exactly equal to a key of `replacements` with the
+    /// corresponding value whenever the value is read, e.g. by templates.
+    pub fn with_replacements(mut self, replacements: HashMap<String, String>) -> Self {
+        self.replacements = replacements;
+        self
+    }
+
+    /// The values that capture values are replaced by, keyed by the capture value they replace.
+    pub fn replacements(&self) -> &HashMap<String, String> {
+        &self.replacements
+    }
+
+    /// Enables or disables the prefilter. When disabled every pattern is tried against every line.
+    pub fn with_prefilter(mut self, enabled: bool) -> Self {
+        self.prefilter = if enabled {
+            build_prefilter(&self.regexes, self.flags)
+        } else {
+            None
+        };
+        self
+    }
+
+    /// The names of every capture group across all patterns in the order they first appear.
+    pub fn capture_names(&self) -> &[String] {
+        &self.capture_names
+    }
+
+    /// A map of matches with an empty entry for each capture name.
+    pub fn captures_map(&self) -> Hits<'_> {
+        self.capture_names
+            .iter()
+            .map(|capture_name| (capture_name.as_str(), Vec::new()))
+            .collect()
+    }
+
+    /// Populates `buffer` with every match of every pattern against `line`, replacing the matches
+    /// of whichever line was populated before. When several patterns share a capture name, the
+    /// matches of earlier patterns precede those of later patterns regardless of where in the line
+    /// they occur, and the matches of a single pattern are in the order they occur. The prefilter
+    /// doesn't affect this order.
+    pub fn populate(&self, line: &str, buffer: &mut CapturesBuffer) {
+        buffer.clear();
+        self.populate_until(line, &mut buffer.hits, None);
+    }
+
+    /// Like [Patterns::populate] but gives up once `timeout` has elapsed, in which case `false` is
+    /// returned and `buffer` is only partially populated. Time is only checked in between matches,
+    /// so a single match that takes longer than `timeout` isn't interrupted.
+    pub fn populate_within(&self, line: &str, buffer: &mut CapturesBuffer, timeout: Duration) -> bool {
+        buffer.clear();
+        self.populate_until(line, &mut buffer.hits, Some(Instant::now() + timeout))
+    }
+
+    /// Populates `hits` as in [Patterns::populate] unless `deadline` passes first.
+    fn populate_until(&self, line: &str, hits: &mut Hits, deadline: Option<Instant>) -> bool {
+        if let Some(fields) = &self.fields {
+            fields.populate(line, hits);
+        }
+        if let Some(columns) = &self.columns {
+            columns.populate(line, hits);
+        }
+        if self.line_capture {
+            if let Some(hits) = hits.get_mut(LINE_CAPTURE) {
+                hits.push(CaptureHit::Text {
+                    start: 0,
+                    end: line.len(),
+                });
+            }
+        }
+
+        match &self.prefilter {
+            Some(prefilter) => prefilter
+                .matches(line)
+                .iter()
+                .all(|i| self.populate_captures(i, line, hits, deadline)),
+            None => (0..self.regexes.len()).all(|i| self.populate_captures(i, line, hits, deadline)),
+        }
+    }
+
+    /// Applies every pattern to the entirety of `text` and produces the matches of each individual
+    /// match separately, ordered by where the matches begin. Matches of different patterns that
+    /// begin at the same position are ordered by pattern. The [LINE_CAPTURE] of each match is the
+    /// entire match. Offsets are relative to `text`.
+    pub fn match_captures(&self, text: &str) -> Vec<Hits<'_>> {
+        let mut matches = Vec::new();
+
+        for (i, (regex, _)) in self.regexes.iter().enumerate() {
+            for capture_match in regex.captures_iter(text) {
+                let start = capture_match.get(0).map_or(0, |m| m.start());
+                let mut hits = self.captures_map();
+                self.push_captures(i, &capture_match, &mut hits);
+
+                if let (true, Some(whole), Some(vals)) =
+                    (self.line_capture, capture_match.get(0), hits.get_mut(LINE_CAPTURE))
+                {
+                    vals.push(CaptureHit::Text {
+                        start: whole.start(),
+                        end: whole.end(),
+                    });
+                }
+                matches.push((start, hits));
+            }
+        }
+        matches.sort_by_key(|(start, _)| *start);
+        matches.into_iter().map(|(_, hits)| hits).collect()
+    }
+
+    /// Populates the pre-seeded keys of `hits` with every match of the `i`th pattern against
+    /// `line`. Returns `false` if `deadline` passes before all of the matches are found.
+    fn populate_captures(&self, i: usize, line: &str, hits: &mut Hits, deadline: Option<Instant>) -> bool {
+        let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+        for capture_match in self.regexes[i].0.captures_iter(line) {
+            self.push_captures(i, &capture_match, hits);
+
+            if expired() {
+                return false;
+            }
+        }
+        !expired()
+    }
+
+    /// Pushes where each of the named, positional, and namespaced groups of the `i`th pattern that
+    /// participated in a single `capture_match` matched onto the pre-seeded keys of `hits`.
+    fn push_captures(&self, i: usize, capture_match: &Captures, hits: &mut Hits) {
+        let named = self.regexes[i]
+            .1
+            .iter()
+            .filter_map(|capture_name| Some((capture_name, capture_match.name(capture_name)?)));
+        let positional = self
+            .positions
+            .iter()
+            .chain(&self.namespaced[i])
+            .filter_map(|(position, capture_name)| Some((capture_name, capture_match.get(*position)?)));
+
+        for (capture_name, m) in named.chain(positional) {
+            if let Some(hits) = hits.get_mut(capture_name.as_str()) {
+                hits.push(CaptureHit::Text {
+                    start: m.start(),
+                    end: m.end(),
+                });
+            }
+        }
+
+        for span in &self.spans {
+            let Some(m) = capture_match.name(&span.group) else {
+                continue;
+            };
+            if let Some(hits) = hits.get_mut(span.capture_name.as_str()) {
+                let offset = if span.end { m.end() } else { m.start() };
+                hits.push(CaptureHit::Offset(offset as u64));
+            }
+        }
+    }
+}
+
+impl Fields {
+    /// Populates the pre-seeded keys of `hits` with the referenced fields of `line`. Lines that are
+    /// empty have no fields.
+    fn populate(&self, line: &str, hits: &mut Hits) {
+        let mut fields = Vec::new();
+
+        if !line.is_empty() {
+            let mut start = 0;
+
+            for separator in self.separator.find_iter(line) {
+                if fields.len() == self.max_position {
+                    break;
+                }
+                fields.push((start, separator.start()));
+                start = separator.end();
+            }
+            if fields.len() < self.max_position {
+                fields.push((start, line.len()));
+            }
+        }
+
+        for (position, name) in &self.positions {
+            let field = match position {
+                0 => Some((0, line.len())),
+                _ => fields.get(position - 1).copied(),
+            };
+            if let (Some((start, end)), Some(hits)) = (field, hits.get_mut(name.as_str())) {
+                hits.push(CaptureHit::Text { start, end });
+            }
+        }
+    }
+}
+
+impl Columns {
+    /// Populates the pre-seeded keys of `hits` with the referenced columns of `line`. Surrounding
+    /// whitespace is removed from each column and a column that is blank or that begins beyond the
+    /// end of the line has no match.
+    fn populate(&self, line: &str, hits: &mut Hits) {
+        // Byte offsets of each character so that columns are sliced by character rather than byte.
+        let offsets = line
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(iter::once(line.len()))
+            .collect::<Vec<_>>();
+        let char_count = offsets.len() - 1;
+
+        for (position, name) in &self.positions {
+            let Column { start, end } = self.columns[*position];
+
+            if start >= char_count {
+                continue;
+            }
+            let end = end.map_or(char_count, |end| (end + 1).min(char_count));
+            let column = &line[offsets[start]..offsets[end]];
+
+            let start = offsets[start] + (column.len() - column.trim_start().len());
+            let end = start + column.trim().len();
+
+            if let (false, Some(hits)) = (start == end, hits.get_mut(name.as_str())) {
+                hits.push(CaptureHit::Text { start, end });
+            }
+        }
+    }
+}
+
+/// The matches of each capture name as populated for a single line at a time. The buffer is reused
+/// across lines so that neither the map nor the vectors holding the matches of each capture name
+/// are reallocated for every line: keys are never removed and the vectors are emptied in place
+/// whenever a line is populated. Matches are recorded as offsets into the line rather than
+/// borrowing from it, so the buffer doesn't borrow any one line.
+#[derive(Debug)]
+pub struct CapturesBuffer<'p> {
+    hits: Hits<'p>,
+    replacements: &'p HashMap<String, String>,
+}
+
+impl<'p> CapturesBuffer<'p> {
+    /// Initializes the buffer with an empty entry for each capture name of `patterns`.
+    pub fn new(patterns: &'p Patterns) -> Self {
+        Self {
+            hits: patterns.captures_map(),
+            replacements: &patterns.replacements,
+        }
+    }
+
+    /// The captures of `line` which must be the line that was populated last.
+    pub fn captures<'c>(&'c self, line: &'c str) -> LineCaptures<'c> {
+        LineCaptures::new(&self.hits, line, self.replacements)
+    }
+
+    /// Empties the matches of every capture name while retaining their capacity.
+    fn clear(&mut self) {
+        self.hits.values_mut().for_each(Vec::clear);
+    }
+}
+
+/// Compiles `pattern` according to `flags`. Inline flags within the pattern, such as `(?-i)`, take
+/// precedence.
+pub fn compile(pattern: &str, flags: Flags) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(flags.ignore_case)
+        .multi_line(flags.multiline)
+        .dot_matches_new_line(flags.multiline)
+        .ignore_whitespace(flags.verbose)
+        .build()
+}
+
+/// A single pattern gains nothing from a prefilter as it would just be matched twice. The set can
+/// also exceed the size limit of the regex engine in which case every pattern is simply tried
+/// against every line.
+fn build_prefilter(regexes: &[(Regex, Vec<String>)], flags: Flags) -> Option<RegexSet> {
+    if regexes.len() < 2 {
+        return None;
+    }
+    let prefilter = RegexSetBuilder::new(regexes.iter().map(|(re, _)| re.as_str()))
+        .case_insensitive(flags.ignore_case)
+        .multi_line(flags.multiline)
+        .dot_matches_new_line(flags.multiline)
+        .ignore_whitespace(flags.verbose)
+        .build();
+
+    match prefilter {
+        Ok(set) => Some(set),
+        Err(e) => {
+            log::warn!("failed to compile pattern prefilter: {e}");
+            None
+        }
+    }
+}
+
+/// The index of the capture group referred to by a positional `name` such as `$1`. Returns `None`
+/// if `name` isn't a `$` followed by a canonical non-negative integer.
+fn positional_group(name: &str) -> Option<usize> {
+    let digits = name.strip_prefix(POSITIONAL_PREFIX)?;
+    digits.parse::<usize>().ok().filter(|p| p.to_string() == digits)
+}
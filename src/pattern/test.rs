@@ -0,0 +1,395 @@
+use super::{compile, CapturesBuffer, Flags, Patterns};
+use crate::{
+    captures::{Captures, LineCaptures},
+    cli::Column,
+};
+use regex::Regex;
+use std::{collections::HashMap, time::Duration};
+
+/// Populates the captures of `line` and copies them out of the buffer.
+fn populate(patterns: &Patterns, line: &str) -> HashMap<String, Vec<String>> {
+    let mut buffer = CapturesBuffer::new(patterns);
+    patterns.populate(line, &mut buffer);
+    buffer.captures(line).to_map()
+}
+
+/// The captures of each match of the patterns against `text`, copied out.
+fn match_captures(patterns: &Patterns, text: &str) -> Vec<HashMap<String, Vec<String>>> {
+    let matches = patterns.match_captures(text);
+    matches
+        .iter()
+        .map(|hits| LineCaptures::new(hits, text, patterns.replacements()).to_map())
+        .collect()
+}
+
+#[test]
+fn test_field_separator() {
+    let patterns = Patterns::new(vec![Regex::new(r"level=(?<lvl>\w+)").unwrap()], Flags::default())
+        .with_field_separator(Regex::new(r"\s+").unwrap(), ["0", "1", "3", "lvl", "01", "9"]);
+
+    assert_eq!(patterns.capture_names(), ["lvl", "0", "1", "3", "9"]);
+
+    let line = "2025-01-01 level=info  hello";
+    let captures_map = populate(&patterns, line);
+
+    assert_eq!(captures_map["0"], vec![line]);
+    assert_eq!(captures_map["1"], vec!["2025-01-01"]);
+    assert_eq!(captures_map["3"], vec!["hello"]);
+    assert_eq!(captures_map["lvl"], vec!["info"]);
+    assert!(captures_map["9"].is_empty());
+    assert!(!captures_map.contains_key("01"));
+
+    let captures_map = populate(&patterns, "");
+    assert_eq!(captures_map["0"], vec![""]);
+    assert!(captures_map["1"].is_empty());
+}
+
+#[test]
+fn test_columns() {
+    let columns = vec![
+        Column { start: 0, end: Some(4) },
+        Column { start: 5, end: Some(9) },
+        Column { start: 10, end: None },
+    ];
+    let patterns = Patterns::new(vec![Regex::new(r"(?<id>\d+)").unwrap()], Flags::default())
+        .with_columns(columns, ["0", "1", "2", "3", "id", "01"]);
+
+    assert_eq!(patterns.capture_names(), ["id", "0", "1", "2"]);
+
+    let captures_map = populate(&patterns, "café 42    zürich");
+    assert_eq!(captures_map["0"], vec!["café"]);
+    assert_eq!(captures_map["1"], vec!["42"]);
+    assert_eq!(captures_map["2"], vec!["zürich"]);
+    assert_eq!(captures_map["id"], vec!["42"]);
+
+    // Columns that are blank or that begin beyond the end of the line have no match.
+    let captures_map = populate(&patterns, "ab        ");
+    assert_eq!(captures_map["0"], vec!["ab"]);
+    assert!(captures_map["1"].is_empty());
+    assert!(captures_map["2"].is_empty());
+}
+
+#[test]
+fn test_offset_capture() {
+    let patterns = Patterns::new(vec![Regex::new(r"level=(?<lvl>\w+)").unwrap()], Flags::default())
+        .with_offset_capture(["lvl", "_offset"]);
+    assert_eq!(patterns.capture_names(), ["lvl", "_offset"]);
+
+    let line = "level=info";
+    let mut buffer = CapturesBuffer::new(&patterns);
+    patterns.populate(line, &mut buffer);
+    patterns.populate_offset(42, &mut buffer);
+    assert_eq!(buffer.captures(line).to_map()["_offset"], vec!["42"]);
+
+    // A capture group of the same name takes precedence.
+    let patterns = Patterns::new(vec![Regex::new(r"at=(?<_offset>\d+)").unwrap()], Flags::default())
+        .with_offset_capture(["_offset"]);
+    let line = "at=7";
+    let mut buffer = CapturesBuffer::new(&patterns);
+    patterns.populate(line, &mut buffer);
+    patterns.populate_offset(42, &mut buffer);
+    assert_eq!(buffer.captures(line).to_map()["_offset"], vec!["7"]);
+
+    let flags = Flags {
+        multiline: true,
+        ..Flags::default()
+    };
+    let patterns =
+        Patterns::new(vec![Regex::new(r"level=(?<lvl>\w+)").unwrap()], flags).with_offset_capture(["_offset"]);
+    assert_eq!(patterns.capture_names(), ["lvl"]);
+}
+
+#[test]
+fn test_namespaces() {
+    let regexes = vec![
+        Regex::new(r"upstream status=(?<status>\d+)").unwrap(),
+        Regex::new(r"^\w+ status=(?<status>\d+)").unwrap(),
+        Regex::new(r"upstream (status)=(?<status>\d+)").unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, Flags::default()).with_namespaces(
+        &[(1, "req"), (2, "up")],
+        [
+            "status",
+            "req:status",
+            "up:status",
+            "up:$1",
+            "req:$1",
+            "req:$2",
+            "req:code",
+            "nope:status",
+        ],
+    );
+    assert_eq!(
+        patterns.capture_names(),
+        ["status", "req:status", "up:status", "up:$1", "req:$1"]
+    );
+
+    let captures_map = populate(&patterns, "GET status=200 upstream status=502");
+    assert_eq!(captures_map["status"], vec!["502", "200", "502"]);
+    assert_eq!(captures_map["req:status"], vec!["200"]);
+    assert_eq!(captures_map["up:status"], vec!["502"]);
+    assert_eq!(captures_map["up:$1"], vec!["status"]);
+    assert_eq!(captures_map["req:$1"], vec!["200"]);
+
+    let regexes = vec![Regex::new(r"a=(?<v>\w+)").unwrap(), Regex::new(r"b=(?<v>\w+)").unwrap()];
+    let patterns = Patterns::new(regexes, Flags::default()).with_namespaces(&[(0, "kv"), (1, "kv")], ["kv:v"]);
+    let captures_map = populate(&patterns, "b=2 a=1");
+    assert_eq!(captures_map["kv:v"], vec!["1", "2"]);
+}
+
+#[test]
+fn test_span_captures() {
+    let regexes = vec![
+        Regex::new(r"(src|dst)=(?<ip>\S+)").unwrap(),
+        Regex::new(r"via (?<ip>\S+)").unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, Flags::default()).with_span_captures([
+        "ip",
+        "@start:ip",
+        "@end:ip",
+        "@start:nope",
+        "@start:$1",
+    ]);
+    assert_eq!(patterns.capture_names(), ["ip", "@start:ip", "@end:ip"]);
+
+    let line = "via 10.0.0.9 src=10.0.0.1 dst=10.0.0.2";
+    let captures_map = populate(&patterns, line);
+
+    // Spans are in the same order as the matches they belong to.
+    assert_eq!(captures_map["ip"], vec!["10.0.0.1", "10.0.0.2", "10.0.0.9"]);
+    assert_eq!(captures_map["@start:ip"], vec!["17", "30", "4"]);
+    assert_eq!(captures_map["@end:ip"], vec!["25", "38", "12"]);
+
+    let patterns = Patterns::new(vec![Regex::new(r"(?<ip>\S+)").unwrap()], Flags::default());
+    assert_eq!(patterns.with_span_captures(["ip"]).capture_names(), ["ip"]);
+
+    let flags = Flags {
+        multiline: true,
+        ..Default::default()
+    };
+    let patterns = Patterns::new(vec![Regex::new(r"(?<ip>\S+)").unwrap()], flags);
+    assert_eq!(patterns.with_span_captures(["@start:ip"]).capture_names(), ["ip"]);
+}
+
+#[test]
+fn test_replacements() {
+    let regexes = vec![
+        Regex::new(r"level=(?<lvl>\w+)").unwrap(),
+        Regex::new(r"ok=(?<ok>\d)").unwrap(),
+    ];
+    let replacements = HashMap::from([
+        (String::from("WARNING"), String::from("WARN")),
+        (String::from("1"), String::from("true")),
+    ]);
+    let patterns = Patterns::new(regexes, Flags::default())
+        .with_line_capture(["_line"])
+        .with_replacements(replacements);
+
+    let captures_map = populate(&patterns, "level=WARNING ok=1");
+    assert_eq!(captures_map["lvl"], vec!["WARN"]);
+    assert_eq!(captures_map["ok"], vec!["true"]);
+    assert_eq!(captures_map["_line"], vec!["level=WARNING ok=1"]);
+
+    // Only values that are exactly equal to a key are replaced.
+    let captures_map = populate(&patterns, "level=WARNINGS ok=0");
+    assert_eq!(captures_map["lvl"], vec!["WARNINGS"]);
+    assert_eq!(captures_map["ok"], vec!["0"]);
+
+    let matches = match_captures(&patterns, "level=WARNING\nlevel=INFO");
+    assert_eq!(matches[0]["lvl"], vec!["WARN"]);
+    assert_eq!(matches[1]["lvl"], vec!["INFO"]);
+}
+
+#[test]
+fn test_populate_within() {
+    let regexes = vec![
+        Regex::new(r"level=(?<lvl>\w+)").unwrap(),
+        Regex::new(r"ip=(?<ip>\S+)").unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, Flags::default());
+
+    let line = "level=INFO ip=10.0.0.1";
+    let mut buffer = CapturesBuffer::new(&patterns);
+    assert!(patterns.populate_within(line, &mut buffer, Duration::from_secs(60)));
+    let captures_map = buffer.captures(line).to_map();
+    assert_eq!(captures_map["lvl"], vec!["INFO"]);
+    assert_eq!(captures_map["ip"], vec!["10.0.0.1"]);
+
+    // The deadline has already passed by the time the first match is found.
+    let mut buffer = CapturesBuffer::new(&patterns);
+    assert!(!patterns.populate_within(line, &mut buffer, Duration::ZERO));
+    assert_eq!(buffer.captures(line).to_map()["ip"], Vec::<&str>::new());
+}
+
+#[test]
+fn test_captures_buffer() {
+    let patterns = Patterns::new(vec![Regex::new(r"ip=(?<ip>[^ ]+)").unwrap()], Flags::default());
+    let mut buffer = CapturesBuffer::new(&patterns);
+
+    for (line, expected) in [
+        ("ip=127.0.0.1 ip=10.0.0.1", vec!["127.0.0.1", "10.0.0.1"]),
+        ("foo", vec![]),
+        ("ip=10.0.0.2", vec!["10.0.0.2"]),
+    ] {
+        let line = String::from(line);
+
+        // The matches of the previous line are replaced rather than added to.
+        patterns.populate(&line, &mut buffer);
+        assert_eq!(buffer.captures(&line).to_map()["ip"], expected);
+    }
+}
+
+#[test]
+fn test_ignore_case() {
+    let flags = Flags {
+        ignore_case: true,
+        ..Flags::default()
+    };
+    let regexes = vec![
+        compile("(?P<lvl>error)", flags).unwrap(),
+        compile("(?-i)(?P<src>SRC)", flags).unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, flags);
+
+    let captures_map = populate(&patterns, "ERROR from src");
+    assert_eq!(captures_map["lvl"], vec!["ERROR"]);
+    assert!(captures_map["src"].is_empty());
+
+    let captures_map = populate(&patterns, "Error from SRC");
+    assert_eq!(captures_map["lvl"], vec!["Error"]);
+    assert_eq!(captures_map["src"], vec!["SRC"]);
+
+    let patterns = Patterns::new(
+        vec![compile("(?P<lvl>error)", Flags::default()).unwrap()],
+        Flags::default(),
+    );
+    let captures_map = populate(&patterns, "ERROR");
+    assert!(captures_map["lvl"].is_empty());
+}
+
+#[test]
+fn test_verbose() {
+    let flags = Flags {
+        verbose: true,
+        ..Flags::default()
+    };
+    let verbose = r"
+        level=(?<lvl>\w+)  # the severity
+        \s+
+        msg=(?<msg>.*)     # everything else
+    ";
+    let regexes = vec![
+        compile(verbose, flags).unwrap(),
+        compile(r"ip = (?<ip>\S+)", flags).unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, flags);
+
+    let captures_map = populate(&patterns, "level=warn msg=disk almost full ip=10.0.0.1");
+    assert_eq!(captures_map["lvl"], vec!["warn"]);
+    assert_eq!(captures_map["msg"], vec!["disk almost full ip=10.0.0.1"]);
+    assert_eq!(captures_map["ip"], vec!["10.0.0.1"]);
+
+    // Without the flag, whitespace is significant and `#` is matched literally.
+    let patterns = Patterns::new(vec![compile(verbose, Flags::default()).unwrap()], Flags::default());
+    let captures_map = populate(&patterns, "level=warn msg=disk almost full");
+    assert!(captures_map["lvl"].is_empty());
+}
+
+#[test]
+fn test_match_captures_multiline() {
+    let flags = Flags {
+        multiline: true,
+        ..Flags::default()
+    };
+    let regexes = vec![
+        compile(r"^Exception: (?<exc>[^\n]+)\n(?<trace>(?:[ \t]+at [^\n]+\n?)+)", flags).unwrap(),
+        compile(r"^level=(?<lvl>\w+)$", flags).unwrap(),
+    ];
+    let patterns = Patterns::new(regexes, flags);
+
+    let text = "level=info\nException: boom\n  at foo()\n  at bar()\nlevel=warn\n";
+    let matches = match_captures(&patterns, text);
+    assert_eq!(matches.len(), 3);
+
+    assert_eq!(matches[0]["lvl"], vec!["info"]);
+    assert!(matches[0]["exc"].is_empty());
+
+    assert_eq!(matches[1]["exc"], vec!["boom"]);
+    assert_eq!(matches[1]["trace"], vec!["  at foo()\n  at bar()\n"]);
+    assert!(matches[1]["lvl"].is_empty());
+
+    assert_eq!(matches[2]["lvl"], vec!["warn"]);
+}
+
+#[test]
+fn test_shared_capture_names() {
+    let regexes = || {
+        vec![
+            Regex::new(r"dst=(?<ip>\S+)").unwrap(),
+            Regex::new(r"src=(?<ip>\S+)").unwrap(),
+            Regex::new(r"via=(?<ip>\S+)").unwrap(),
+        ]
+    };
+    let line = "src=10.0.0.1 via=10.0.0.9 dst=10.0.0.2 src=10.0.0.3 dst=10.0.0.4";
+
+    for prefilter in [true, false] {
+        let patterns = Patterns::new(regexes(), Flags::default()).with_prefilter(prefilter);
+        assert_eq!(patterns.capture_names(), ["ip"]);
+
+        let captures_map = populate(&patterns, line);
+
+        // Pattern order first, then the order of matches within the line.
+        assert_eq!(
+            captures_map["ip"],
+            vec!["10.0.0.2", "10.0.0.4", "10.0.0.1", "10.0.0.3", "10.0.0.9"]
+        );
+    }
+
+    // Patterns that don't match leave no gaps.
+    let patterns = Patterns::new(regexes(), Flags::default());
+    let captures_map = populate(&patterns, "via=10.0.0.9 src=10.0.0.1");
+    assert_eq!(captures_map["ip"], vec!["10.0.0.1", "10.0.0.9"]);
+}
+
+#[test]
+fn test_positional_groups() {
+    let regexes = vec![
+        Regex::new(r"(\w+)=(?<val>\d+)").unwrap(),
+        Regex::new(r"user (\w+)").unwrap(),
+    ];
+    let patterns =
+        Patterns::new(regexes, Flags::default()).with_positional_groups(["$1", "$0", "val", "$01", "1", "$3"]);
+
+    assert_eq!(patterns.capture_names(), ["val", "$1", "$0", "$3"]);
+
+    let line = "a=1 b=2 user root";
+    let captures_map = populate(&patterns, line);
+
+    assert_eq!(captures_map["$1"], vec!["a", "b", "root"]);
+    assert_eq!(captures_map["$0"], vec!["a=1", "b=2", "user root"]);
+    assert_eq!(captures_map["val"], vec!["1", "2"]);
+    assert!(captures_map["$3"].is_empty());
+}
+
+#[test]
+fn test_line_capture() {
+    let patterns = Patterns::new(vec![Regex::new(r"level=(?<lvl>\w+)").unwrap()], Flags::default())
+        .with_line_capture(["lvl", "_line"]);
+
+    assert_eq!(patterns.capture_names(), ["lvl", "_line"]);
+
+    let line = "level=info msg=hello";
+    let captures_map = populate(&patterns, line);
+    assert_eq!(captures_map["_line"], vec![line]);
+
+    let patterns =
+        Patterns::new(vec![Regex::new(r"msg=(?<_line>\w+)").unwrap()], Flags::default()).with_line_capture(["_line"]);
+    assert_eq!(patterns.capture_names(), ["_line"]);
+
+    let captures_map = populate(&patterns, line);
+    assert_eq!(captures_map["_line"], vec!["hello"]);
+
+    let patterns =
+        Patterns::new(vec![Regex::new(r"level=(?<lvl>\w+)").unwrap()], Flags::default()).with_line_capture(["lvl"]);
+    assert_eq!(patterns.capture_names(), ["lvl"]);
+}
@@ -0,0 +1,79 @@
+use encoding_rs::{Decoder, Encoding};
+use std::io::{self, BufRead, Read};
+
+/// Size in bytes of each chunk of raw input that is decoded at a time.
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A reader that transcodes the raw bytes of `inner` from an [Encoding] into UTF-8 as they're
+/// read, which is used when input is read as a whole rather than split into records. Byte
+/// sequences that are malformed in the encoding are replaced with U+FFFD. A byte order mark for the
+/// encoding is removed.
+pub struct DecodingReader<R> {
+    inner: R,
+    decoder: Decoder,
+    raw: Vec<u8>,
+    decoded: Vec<u8>,
+    /// The position within `decoded` up to which bytes have been consumed.
+    pos: usize,
+    /// Whether `inner` has been exhausted and the decoder flushed.
+    done: bool,
+}
+
+impl<R: Read> DecodingReader<R> {
+    pub fn new(inner: R, encoding: &'static Encoding) -> Self {
+        Self {
+            inner,
+            decoder: encoding.new_decoder_with_bom_removal(),
+            raw: vec![0; CHUNK_SIZE],
+            decoded: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Decodes the next chunk of raw input into `decoded`, which may produce no output if the
+    /// chunk ends partway through a character. The final call flushes the decoder.
+    fn decode_chunk(&mut self) -> io::Result<()> {
+        let n = loop {
+            match self.inner.read(&mut self.raw) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        let last = n == 0;
+
+        // Sized so that the whole chunk always fits, meaning that all of it is consumed at once.
+        let max_len = self.decoder.max_utf8_buffer_length(n).unwrap_or(n * 3 + 16);
+        self.decoded.resize(max_len, 0);
+
+        let (_, _, written, _) = self.decoder.decode_to_utf8(&self.raw[..n], &mut self.decoded, last);
+        self.decoded.truncate(written);
+        self.pos = 0;
+        self.done = last;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for DecodingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos >= self.decoded.len() && !self.done {
+            self.decode_chunk()?;
+        }
+        Ok(&self.decoded[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.decoded.len());
+    }
+}
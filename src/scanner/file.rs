@@ -1,42 +1,119 @@
+use super::{InputFormat, Records};
 use anyhow::{format_err, Context, Result};
 use std::{
     fs::File,
-    io::{BufRead, BufReader, Lines},
-    path::Path,
+    io::{self, BufRead, BufReader},
+    iter,
+    path::{Path, PathBuf},
 };
 
+/// The input file path that refers to standard input, as in `cat a - b`.
+pub const STDIN_PATH: &str = "-";
+
+/// Yielded by [MultiFileScanner::init_events] so that consumers can tell which file each line was
+/// read from, e.g. to reset state that shouldn't carry over from one file to the next.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScanEvent {
+    /// The following lines, if any, are read from the file at the given path. Every file is
+    /// announced even if it's empty.
+    NewFile(PathBuf),
+    /// A line of the most recently announced file along with the byte offset at which it begins
+    /// within that file.
+    Line { line: String, offset: u64 },
+    /// Reading failed part way through, as opposed to reaching the end of the input, which means
+    /// that the remaining input was lost. Nothing follows.
+    ReadError(String),
+}
+
 /// A type that implements [Iterator] to iterate through each
-/// line of the input file(s) in a buffered manner.
+/// line of the input file(s) in a buffered manner. A path of [STDIN_PATH]
+/// reads standard input at that position in the sequence.
 pub struct MultiFileScanner {
     current_buf_reader_idx: usize,
-    buf_readers: Vec<Lines<BufReader<File>>>,
+    buf_readers: Vec<Records<Box<dyn BufRead>>>,
+    file_paths: Vec<PathBuf>,
+    /// The index of the last file that was announced by [MultiFileScanner::next_event].
+    announced_idx: Option<usize>,
 }
 
 impl MultiFileScanner {
-    /// Takes in a list of paths to files to read through.
-    fn new<F: AsRef<Path>>(file_paths: &[F]) -> Result<Self> {
+    /// Takes in a list of paths to files to read through whose lines are split and decoded
+    /// according to `format`.
+    fn new<F: AsRef<Path>>(file_paths: &[F], format: InputFormat) -> Result<Self> {
+        Self::with_stdin(file_paths, format, io::stdin().lock())
+    }
+
+    /// Like [MultiFileScanner::new] except that `stdin` is read in place of standard input. Only
+    /// one of the `file_paths` may be [STDIN_PATH].
+    pub(super) fn with_stdin<F, R>(file_paths: &[F], format: InputFormat, stdin: R) -> Result<Self>
+    where
+        F: AsRef<Path>,
+        R: BufRead + 'static,
+    {
         if file_paths.is_empty() {
             return Err(format_err!("MultiFileScanner cannot be created without input files"));
         }
+        let mut stdin = Some(stdin);
         let mut buf_readers = Vec::with_capacity(file_paths.len());
 
         for file_path in file_paths {
-            let buf_reader = File::open(file_path)
-                .map(BufReader::new)
-                .context("failed to open an input file")?;
-            buf_readers.push(buf_reader.lines());
+            let records = if file_path.as_ref() == Path::new(STDIN_PATH) {
+                let stdin = stdin
+                    .take()
+                    .ok_or_else(|| format_err!("standard input '{STDIN_PATH}' can only be provided once"))?;
+                format.records(stdin)
+            } else {
+                File::open(file_path)
+                    .map(BufReader::new)
+                    .map(|reader| format.records(reader))
+                    .context("failed to open an input file")?
+            };
+            buf_readers.push(records);
         }
         let current_buf_reader_idx = usize::default();
 
         Ok(MultiFileScanner {
             current_buf_reader_idx,
             buf_readers,
+            file_paths: file_paths.iter().map(|path| path.as_ref().to_path_buf()).collect(),
+            announced_idx: None,
         })
     }
 
-    pub fn init<F: AsRef<Path>>(file_paths: &[F]) -> Result<Box<dyn Iterator<Item = String>>> {
-        let scanner = Self::new(file_paths).map(Box::new)?;
-        Ok(scanner)
+    /// Yields the lines of each file in turn, each file announced with a [ScanEvent::NewFile] before
+    /// its lines.
+    pub fn init_events<F: AsRef<Path>>(
+        file_paths: &[F],
+        format: InputFormat,
+    ) -> Result<Box<dyn Iterator<Item = ScanEvent>>> {
+        let mut scanner = Self::new(file_paths, format)?;
+        Ok(Box::new(iter::from_fn(move || scanner.next_event())))
+    }
+
+    /// Like [Iterator::next] but announces each file before yielding its lines. A failure to read is
+    /// yielded as a final [ScanEvent::ReadError] rather than moving on to the next file.
+    pub(super) fn next_event(&mut self) -> Option<ScanEvent> {
+        loop {
+            if self.announced_idx != Some(self.current_buf_reader_idx) {
+                let path = self.file_paths.get(self.current_buf_reader_idx)?;
+                self.announced_idx = Some(self.current_buf_reader_idx);
+                return Some(ScanEvent::NewFile(path.clone()));
+            }
+            let buf_reader = self.buf_readers.get_mut(self.current_buf_reader_idx)?;
+            match buf_reader.next() {
+                Some(Ok(line)) => {
+                    let offset = buf_reader.offset();
+                    return Some(ScanEvent::Line { line, offset });
+                }
+                Some(Err(e)) => {
+                    let path = &self.file_paths[self.current_buf_reader_idx];
+                    log::warn!("failed to read {}: {e}", path.display());
+                    self.current_buf_reader_idx = self.buf_readers.len();
+                    return Some(ScanEvent::ReadError(e.to_string()));
+                }
+                None => self.current_buf_reader_idx += 1,
+            }
+        }
     }
 }
 
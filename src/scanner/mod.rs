@@ -1,7 +1,18 @@
+/// Concerned with transcoding input that isn't UTF-8.
+pub mod decode;
+pub use decode::DecodingReader;
+
 /// Concerned with reading input lines from multiple file sources.
 pub mod file;
-pub use file::MultiFileScanner;
+pub use file::{MultiFileScanner, ScanEvent, STDIN_PATH};
+
+/// Concerned with splitting input into records on an arbitrary delimiter.
+pub mod records;
+pub use records::{InputFormat, Records};
 
 /// Concerned with reading input lines from standard input.
 pub mod stdin;
 pub use stdin::StdinScanner;
+
+#[cfg(test)]
+mod test;
@@ -0,0 +1,127 @@
+use encoding_rs::{Encoding, UTF_8};
+use std::io::{self, BufRead};
+
+/// How raw input is split into records: the byte that terminates each record, whether the
+/// carriage return of CRLF-terminated records is kept, and the encoding that input is decoded from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InputFormat {
+    pub delimiter: u8,
+    pub keep_cr: bool,
+    pub encoding: &'static Encoding,
+}
+
+impl InputFormat {
+    /// Records terminated by `delimiter` of UTF-8 input whose carriage returns are removed.
+    pub fn new(delimiter: u8) -> Self {
+        Self {
+            delimiter,
+            keep_cr: false,
+            encoding: UTF_8,
+        }
+    }
+
+    /// Whether to keep the trailing carriage return of newline-terminated records.
+    pub fn keep_cr(mut self, keep_cr: bool) -> Self {
+        self.keep_cr = keep_cr;
+        self
+    }
+
+    /// The encoding that input is decoded from into UTF-8.
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    /// The records of `reader` in this format. Input that isn't UTF-8 is split into records before
+    /// each is transcoded, so the encoding must be ASCII-compatible.
+    pub fn records<R: BufRead + 'static>(&self, reader: R) -> Records<Box<dyn BufRead>> {
+        let reader: Box<dyn BufRead> = Box::new(reader);
+        let records = Records::new(reader, self.delimiter).keep_cr(self.keep_cr);
+
+        if self.encoding == UTF_8 {
+            records
+        } else {
+            records.encoding(self.encoding)
+        }
+    }
+}
+
+/// An iterator over the records of a reader where each record is terminated by `delimiter`. This is
+/// analogous to [BufRead::lines] but for arbitrary single-byte delimiters. The delimiter isn't
+/// included in the yielded records and if the delimiter is a newline then a trailing carriage return
+/// is removed as well, unless [Records::keep_cr] says otherwise, so that CRLF-terminated input is
+/// handled the same as LF-terminated input.
+pub struct Records<R> {
+    reader: R,
+    delimiter: u8,
+    keep_cr: bool,
+    /// The encoding that each record is decoded from, if it isn't UTF-8.
+    encoding: Option<&'static Encoding>,
+    /// The number of bytes read so far.
+    consumed: u64,
+    /// The byte offset at which the most recently yielded record begins.
+    offset: u64,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, delimiter: u8) -> Self {
+        Self {
+            reader,
+            delimiter,
+            keep_cr: false,
+            encoding: None,
+            consumed: 0,
+            offset: 0,
+        }
+    }
+
+    /// The byte offset within the reader at which the most recently yielded record begins. The
+    /// offset refers to the raw bytes of the reader, so it accounts for carriage returns that were
+    /// removed and isn't affected by decoding.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Whether to keep the trailing carriage return of newline-terminated records.
+    pub fn keep_cr(mut self, keep_cr: bool) -> Self {
+        self.keep_cr = keep_cr;
+        self
+    }
+
+    /// Decode each record from `encoding` rather than requiring it to be UTF-8. Byte sequences that
+    /// are malformed in the encoding are replaced with U+FFFD. The encoding must be ASCII-compatible
+    /// so that the delimiter can't occur within a character, see [Encoding::is_ascii_compatible].
+    pub fn encoding(mut self, encoding: &'static Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = Vec::new();
+
+        match self.reader.read_until(self.delimiter, &mut buf) {
+            Ok(0) => return None,
+            Ok(n) => {
+                self.offset = self.consumed;
+                self.consumed += n as u64;
+            }
+            Err(e) => return Some(Err(e)),
+        }
+
+        if buf.last() == Some(&self.delimiter) {
+            buf.pop();
+        }
+        // The final record may be missing its terminator but still have a carriage return.
+        if self.delimiter == b'\n' && !self.keep_cr && buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        if let Some(encoding) = self.encoding {
+            return Some(Ok(encoding.decode_without_bom_handling(&buf).0.into_owned()));
+        }
+        Some(String::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+}
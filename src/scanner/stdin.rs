@@ -1,18 +1,108 @@
-use std::io::{self, Lines, StdinLock};
+use super::{InputFormat, Records, ScanEvent};
+use std::{
+    cell::Cell,
+    io::{self, BufRead, BufReader, Read},
+    iter, mem,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
 
-/// A type that implements [Iterator] to iterate through lines from standard input.
+/// The maximum number of events that [StdinScanner::spawn_events] sends at once.
+const BATCH_SIZE: usize = 256;
+
+/// The number of batches that [StdinScanner::spawn_events] reads ahead of whoever receives them.
+const READ_AHEAD: usize = 16;
+
+/// The number of bytes of standard input that [StdinScanner::spawn_events] reads at once.
+const READ_SIZE: usize = 64 * 1024;
+
+/// A type that implements [Iterator] to iterate through lines from standard input. A failure to
+/// read is logged and ends the iteration the same as the end of input; use
+/// [StdinScanner::init_events] to tell the two apart.
 pub struct StdinScanner {
-    inner: Lines<StdinLock<'static>>,
+    inner: Records<Box<dyn BufRead>>,
+    /// Set once reading fails so that a reader that keeps failing isn't read from again.
+    failed: bool,
 }
 
 impl StdinScanner {
-    fn new() -> Self {
-        let inner = io::stdin().lines();
-        Self { inner }
+    fn new(format: InputFormat) -> Self {
+        Self::with_reader(format, io::stdin().lock())
+    }
+
+    /// Like [StdinScanner::new] but reads from `reader` in place of standard input.
+    pub(super) fn with_reader<R: BufRead + 'static>(format: InputFormat, reader: R) -> Self {
+        let inner = format.records(reader);
+        Self { inner, failed: false }
     }
 
-    pub fn init() -> Box<dyn Iterator<Item = String>> {
-        Box::new(Self::new())
+    /// Yields each line, split and decoded according to `format`, as a [ScanEvent::Line] along with
+    /// its byte offset. Standard input is never announced with a [ScanEvent::NewFile]. A failure to read is
+    /// yielded as a final [ScanEvent::ReadError].
+    pub fn init_events(format: InputFormat) -> Box<dyn Iterator<Item = ScanEvent>> {
+        let mut scanner = Self::new(format);
+        Box::new(iter::from_fn(move || scanner.next_event()))
+    }
+
+    /// Like [StdinScanner::init_events] but standard input is read on a separate thread and the
+    /// events are received from the returned channel in batches, so that whoever receives them can
+    /// tell when the next line hasn't been read yet, e.g. while following a log. A batch is sent
+    /// once it's full or once everything read from standard input so far has been consumed, as
+    /// reading any further may block. The channel disconnects after the last batch.
+    pub fn spawn_events(format: InputFormat) -> Receiver<Vec<ScanEvent>> {
+        let (sender, receiver) = mpsc::sync_channel(READ_AHEAD);
+
+        thread::spawn(move || {
+            let drained = Rc::new(Cell::new(true));
+            let reader = DrainedReader {
+                inner: BufReader::with_capacity(READ_SIZE, io::stdin()),
+                drained: Rc::clone(&drained),
+            };
+            let mut scanner = Self::with_reader(format, reader);
+            let mut batch = Vec::with_capacity(BATCH_SIZE);
+
+            while let Some(event) = scanner.next_event() {
+                batch.push(event);
+
+                // A line that was only partially read when everything else was consumed may still
+                // block until the rest of it arrives, holding back the batch until then.
+                if batch.len() == BATCH_SIZE || drained.get() {
+                    let full = mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                    if sender.send(full).is_err() {
+                        return;
+                    }
+                }
+            }
+            if !batch.is_empty() {
+                let _ = sender.send(batch);
+            }
+        });
+        receiver
+    }
+
+    /// Like [Iterator::next] but distinguishes a failure to read from the end of input.
+    pub(super) fn next_event(&mut self) -> Option<ScanEvent> {
+        match self.next_record()? {
+            Ok(line) => {
+                let offset = self.inner.offset();
+                Some(ScanEvent::Line { line, offset })
+            }
+            Err(e) => Some(ScanEvent::ReadError(e.to_string())),
+        }
+    }
+
+    fn next_record(&mut self) -> Option<io::Result<String>> {
+        if self.failed {
+            return None;
+        }
+        let record = self.inner.next()?;
+
+        if let Err(e) = &record {
+            log::warn!("failed to read standard input: {e}");
+            self.failed = true;
+        }
+        Some(record)
     }
 }
 
@@ -20,9 +110,32 @@ impl Iterator for StdinScanner {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.inner.next() {
-            Some(Ok(val)) => Some(val),
-            _ => None,
-        }
+        self.next_record()?.ok()
+    }
+}
+
+/// Reads from `inner` and notes in `drained` whether everything that was read from it so far has
+/// been consumed, in which case reading any further may block.
+struct DrainedReader<R> {
+    inner: BufReader<R>,
+    drained: Rc<Cell<bool>>,
+}
+
+impl<R: Read> Read for DrainedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.drained.set(self.inner.buffer().is_empty());
+        Ok(read)
+    }
+}
+
+impl<R: Read> BufRead for DrainedReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.drained.set(self.inner.buffer().is_empty());
     }
 }
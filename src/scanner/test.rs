@@ -0,0 +1,260 @@
+use super::{DecodingReader, InputFormat, MultiFileScanner, Records, ScanEvent, StdinScanner};
+use encoding_rs::Encoding;
+use std::io::{BufReader, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_records_newline() {
+    let input = Cursor::new("foo\nbar\r\n\nbaz");
+    let records = Records::new(input, b'\n').collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(records, vec!["foo", "bar", "", "baz"]);
+}
+
+#[test]
+fn test_records_crlf() {
+    let input = "foo=1\r\nbar=2\r\n\r\nbaz\r";
+    let records = Records::new(Cursor::new(input), b'\n')
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records, vec!["foo=1", "bar=2", "", "baz"]);
+
+    let records = Records::new(Cursor::new(input), b'\n')
+        .keep_cr(true)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records, vec!["foo=1\r", "bar=2\r", "\r", "baz\r"]);
+
+    let records = MultiFileScanner::with_stdin(&["-"], InputFormat::new(b'\n'), Cursor::new(input)).unwrap();
+    assert_eq!(records.collect::<Vec<_>>(), vec!["foo=1", "bar=2", "", "baz"]);
+
+    let records =
+        MultiFileScanner::with_stdin(&["-"], InputFormat::new(b'\n').keep_cr(true), Cursor::new(input)).unwrap();
+    assert_eq!(records.collect::<Vec<_>>(), vec!["foo=1\r", "bar=2\r", "\r", "baz\r"]);
+}
+
+#[test]
+fn test_records_offset() {
+    let mut records = Records::new(Cursor::new("foo\r\nbär\n\nbaz"), b'\n');
+    let mut offsets = Vec::new();
+
+    while let Some(Ok(record)) = records.next() {
+        offsets.push((record, records.offset()));
+    }
+    assert_eq!(
+        offsets,
+        vec![
+            (String::from("foo"), 0),
+            (String::from("bär"), 5),
+            (String::new(), 10),
+            (String::from("baz"), 11),
+        ]
+    );
+}
+
+#[test]
+fn test_decoded_record_offsets() {
+    // Offsets refer to the raw bytes rather than the decoded text, in which 'é' takes two bytes.
+    let latin1 = b"caf\xe9\nx\r\n\xe9t\xe9\ny".to_vec();
+    let format = InputFormat::new(b'\n').encoding(Encoding::for_label(b"latin1").unwrap());
+    let mut records = format.records(Cursor::new(latin1));
+    let mut offsets = Vec::new();
+
+    while let Some(record) = records.next() {
+        offsets.push((record.unwrap(), records.offset()));
+    }
+    assert_eq!(
+        offsets,
+        vec![
+            ("café".to_string(), 0),
+            ("x".to_string(), 5),
+            ("été".to_string(), 8),
+            ("y".to_string(), 12)
+        ]
+    );
+}
+
+#[test]
+fn test_decoding_reader() {
+    // "café=1\nnaïve=2\n" in Latin-1, which 'latin1' labels as windows-1252.
+    let latin1 = b"caf\xe9=1\r\nna\xefve=2\n".to_vec();
+    let format = InputFormat::new(b'\n').encoding(Encoding::for_label(b"latin1").unwrap());
+    let records = format
+        .records(Cursor::new(latin1))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records, vec!["café=1", "naïve=2"]);
+
+    // Without decoding the same bytes aren't valid UTF-8.
+    let latin1 = b"caf\xe9=1\n".to_vec();
+    assert!(InputFormat::new(b'\n')
+        .records(Cursor::new(latin1))
+        .next()
+        .unwrap()
+        .is_err());
+
+    // Input read as a whole is decoded in chunks and the byte order mark is removed.
+    let utf16 = "\u{feff}a=1\nb=2\n"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect::<Vec<_>>();
+    let mut decoded = String::new();
+    DecodingReader::new(Cursor::new(utf16), encoding_rs::UTF_16LE)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "a=1\nb=2\n");
+
+    // Characters that straddle chunks of raw input are decoded whole.
+    let text = format!("a{}", "日本語\n".repeat(5000));
+    let (shift_jis, _, _) = encoding_rs::SHIFT_JIS.encode(&text);
+    let mut decoded = String::new();
+    DecodingReader::new(Cursor::new(shift_jis.into_owned()), encoding_rs::SHIFT_JIS)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, text);
+
+    // Malformed input is replaced rather than an error.
+    let mut decoded = String::new();
+    DecodingReader::new(Cursor::new(b"a\x82".to_vec()), encoding_rs::SHIFT_JIS)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, "a\u{fffd}");
+}
+
+#[test]
+fn test_records_null_data() {
+    let input = Cursor::new("foo\nbar\0baz\r\n\0\0qux\0");
+    let records = Records::new(input, b'\0').collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(records, vec!["foo\nbar", "baz\r\n", "", "qux"]);
+}
+
+#[test]
+fn test_records_invalid_utf8() {
+    let input = Cursor::new(b"foo\0\xff\0bar".to_vec());
+    let mut records = Records::new(input, b'\0');
+    assert_eq!(records.next().unwrap().unwrap(), "foo");
+    assert!(records.next().unwrap().is_err());
+}
+
+#[test]
+fn test_multi_file_scanner_with_stdin() {
+    let cargo_toml = std::fs::read_to_string("Cargo.toml").unwrap();
+    let cargo_toml = cargo_toml.lines().collect::<Vec<_>>();
+
+    let stdin = Cursor::new("foo\nbar\n");
+    let records = MultiFileScanner::with_stdin(&["Cargo.toml", "-"], InputFormat::new(b'\n'), stdin)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(records.len(), cargo_toml.len() + 2);
+    assert_eq!(records[..cargo_toml.len()], cargo_toml);
+    assert_eq!(records[cargo_toml.len()..], ["foo", "bar"]);
+
+    let stdin = Cursor::new("foo\nbar\n");
+    let records = MultiFileScanner::with_stdin(&["-", "Cargo.toml"], InputFormat::new(b'\n'), stdin)
+        .unwrap()
+        .collect::<Vec<_>>();
+    assert_eq!(records[..2], ["foo", "bar"]);
+    assert_eq!(records[2..], cargo_toml);
+
+    assert!(MultiFileScanner::with_stdin(&["-", "Cargo.toml", "-"], InputFormat::new(b'\n'), Cursor::new("")).is_err());
+}
+
+#[test]
+fn test_multi_file_scanner_events() {
+    let mut scanner =
+        MultiFileScanner::with_stdin(&["-", "/dev/null"], InputFormat::new(b'\n'), Cursor::new("foo\nbar\n")).unwrap();
+    let mut events = Vec::new();
+    while let Some(event) = scanner.next_event() {
+        events.push(event);
+    }
+    assert_eq!(
+        events,
+        vec![
+            ScanEvent::NewFile(PathBuf::from("-")),
+            ScanEvent::Line {
+                line: String::from("foo"),
+                offset: 0
+            },
+            ScanEvent::Line {
+                line: String::from("bar"),
+                offset: 4
+            },
+            ScanEvent::NewFile(PathBuf::from("/dev/null")),
+        ]
+    );
+}
+
+#[test]
+fn test_multi_file_scanner_read_error() {
+    let path = std::env::temp_dir().join(format!("grits-invalid-utf8-{}", std::process::id()));
+    std::fs::write(&path, b"a\n\xff\nb\n").unwrap();
+
+    let paths = [path.as_path(), Path::new("Cargo.toml")];
+    let mut scanner = MultiFileScanner::with_stdin(&paths, InputFormat::new(b'\n'), Cursor::new("")).unwrap();
+    let mut events = Vec::new();
+    while let Some(event) = scanner.next_event() {
+        events.push(event);
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // The rest of the file isn't silently skipped nor are the files that follow it read.
+    assert_eq!(events.len(), 3, "{events:?}");
+    assert_eq!(events[0], ScanEvent::NewFile(path.clone()));
+    assert_eq!(
+        events[1],
+        ScanEvent::Line {
+            line: String::from("a"),
+            offset: 0
+        }
+    );
+    assert!(matches!(events[2], ScanEvent::ReadError(_)), "{events:?}");
+}
+
+/// A reader that yields `data` and then fails rather than reaching the end of input.
+struct FailingReader {
+    data: Cursor<&'static str>,
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.data.read(buf)? {
+            0 => Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "pipe closed")),
+            n => Ok(n),
+        }
+    }
+}
+
+#[test]
+fn test_stdin_scanner_read_error() {
+    let reader = BufReader::new(FailingReader {
+        data: Cursor::new("foo\nbar\n"),
+    });
+    let mut scanner = StdinScanner::with_reader(InputFormat::new(b'\n'), reader);
+    let mut events = Vec::new();
+    while let Some(event) = scanner.next_event() {
+        events.push(event);
+    }
+    assert_eq!(
+        events,
+        vec![
+            ScanEvent::Line {
+                line: String::from("foo"),
+                offset: 0
+            },
+            ScanEvent::Line {
+                line: String::from("bar"),
+                offset: 4
+            },
+            ScanEvent::ReadError(String::from("pipe closed")),
+        ]
+    );
+
+    let reader = BufReader::new(FailingReader {
+        data: Cursor::new("foo\n"),
+    });
+    let lines = StdinScanner::with_reader(InputFormat::new(b'\n'), reader).collect::<Vec<_>>();
+    assert_eq!(lines, ["foo"]);
+
+    let mut scanner = StdinScanner::with_reader(InputFormat::new(b'\n'), Cursor::new("foo\n"));
+    assert!(matches!(scanner.next_event(), Some(ScanEvent::Line { .. })));
+    assert_eq!(scanner.next_event(), None);
+}
@@ -0,0 +1,143 @@
+use super::{
+    parse::{self, Attribute, ParseOptions},
+    token::{ATTRIBUTE_DELIMETER, LITERAL_DOUBLE_QUOTE, LITERAL_SINGLE_QUOTE, PARAM_CLOSE, PARAM_OPEN},
+};
+use anyhow::{format_err, Context, Result};
+use std::collections::HashMap;
+
+/// The character that precedes the name of an alias amongst attributes, e.g. `{(@error):lvl}`.
+pub const ALIAS: char = '@';
+
+/// Named lists of attributes, e.g. `error` for `red|bold`, that templates can reference amongst
+/// their attributes as `@error` in place of the attributes themselves.
+#[derive(Debug, Default, Clone)]
+pub struct Aliases {
+    attributes: HashMap<String, Vec<Attribute>>,
+}
+
+impl Aliases {
+    /// Parses `definitions` of the form `NAME=ATTRIBUTES`, e.g. `error=red|bold`. Definitions may
+    /// reference one another regardless of order but may not be recursive.
+    #[cfg(test)]
+    pub fn parse<S: AsRef<str>>(definitions: &[S]) -> Result<Self> {
+        Self::parse_with_escape(definitions, super::ESCAPE)
+    }
+
+    /// Parses `definitions` of the form `NAME=ATTRIBUTES`, e.g. `error=red|bold`, where `escape`
+    /// rather than [ESCAPE](super::ESCAPE) is the escape character. Definitions may reference one another regardless
+    /// of order but may not be recursive.
+    pub fn parse_with_escape<S: AsRef<str>>(definitions: &[S], escape: char) -> Result<Self> {
+        let mut raw = HashMap::new();
+        let mut names = Vec::with_capacity(definitions.len());
+
+        for definition in definitions {
+            let definition = definition.as_ref();
+            let Some((name, attributes)) = definition.split_once('=') else {
+                return Err(format_err!(
+                    "expected alias definition of the form 'NAME=ATTRIBUTES': {definition}"
+                ));
+            };
+            let name = name.trim();
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                return Err(format_err!(
+                    "alias names may only contain alphanumeric characters, '_', and '-': {name}"
+                ));
+            }
+            if raw.insert(name, attributes).is_some() {
+                return Err(format_err!("alias '{ALIAS}{name}' is defined more than once"));
+            }
+            names.push(name);
+        }
+
+        let mut aliases = Self::default();
+        for name in names {
+            aliases.resolve(name, &raw, escape, &mut Vec::new())?;
+        }
+        Ok(aliases)
+    }
+
+    /// The attributes that the alias `name` stands for.
+    pub fn get(&self, name: &str) -> Option<&[Attribute]> {
+        self.attributes.get(name).map(Vec::as_slice)
+    }
+
+    /// Parses the attributes of the alias `name` after those of the aliases it references. `stack`
+    /// holds the aliases whose resolution is in progress in order to detect recursion.
+    fn resolve<'a>(
+        &mut self,
+        name: &'a str,
+        raw: &HashMap<&'a str, &'a str>,
+        escape: char,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<()> {
+        if self.attributes.contains_key(name) {
+            return Ok(());
+        }
+        if stack.contains(&name) {
+            let cycle = stack
+                .iter()
+                .skip_while(|alias| **alias != name)
+                .chain([&name])
+                .map(|alias| format!("{ALIAS}{alias}"))
+                .collect::<Vec<_>>();
+            return Err(format_err!("alias is defined recursively: {}", cycle.join(" -> ")));
+        }
+        let attributes = raw[name];
+
+        stack.push(name);
+        for reference in references(attributes, escape) {
+            let Some((reference, _)) = raw.get_key_value(reference) else {
+                return Err(format_err!(
+                    "alias '{ALIAS}{name}' references an undefined alias '{ALIAS}{reference}'"
+                ));
+            };
+            self.resolve(reference, raw, escape, stack)?;
+        }
+        stack.pop();
+
+        // The attributes are parsed as those of a placeholder anchor.
+        let anchors = parse::parse_with_options(
+            &format!("{{({attributes}):alias}}"),
+            ParseOptions::new(self).with_escape(escape),
+        )
+        .with_context(|| format!("invalid definition of alias '{ALIAS}{name}': {attributes}"))?;
+        let parsed = anchors
+            .into_iter()
+            .next()
+            .map(|anchor| anchor.attributes)
+            .unwrap_or_default();
+
+        self.attributes.insert(name.to_string(), parsed);
+        Ok(())
+    }
+}
+
+/// The names of the aliases that appear amongst `attributes`, ignoring the contents of parameters.
+/// Characters are escaped by `escape`.
+fn references(attributes: &str, escape: char) -> Vec<&str> {
+    let mut references = Vec::new();
+    let mut in_params = false;
+    let mut open_quote = None;
+    let mut chars = attributes.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match (open_quote, c) {
+            (_, c) if c == escape => {
+                chars.next();
+            }
+            (Some(quote), c) if c == quote => open_quote = None,
+            (Some(_), _) => (),
+            (None, LITERAL_SINGLE_QUOTE | LITERAL_DOUBLE_QUOTE) if in_params => open_quote = Some(c),
+            (None, PARAM_OPEN) => in_params = true,
+            (None, PARAM_CLOSE) => in_params = false,
+            (None, ALIAS) if !in_params => {
+                let rest = &attributes[i + ALIAS.len_utf8()..];
+                let end = rest.find([ATTRIBUTE_DELIMETER, PARAM_OPEN, ' ']).unwrap_or(rest.len());
+                references.push(rest[..end].trim());
+            }
+            _ => (),
+        }
+    }
+    references
+}
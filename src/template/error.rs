@@ -1,86 +1,176 @@
 use super::{
-    parse::rules::VALID_ANCHOR_CHARSET,
-    token::{ANCHOR_CLOSE, ATTRIBUTE_CLOSE, ATTRIBUTE_END, ESCAPE, REQUIRED},
+    parse::MAX_DEFAULT_NESTING,
+    token::{ATTRIBUTE_CLOSE, ATTRIBUTE_DELIMETER, ATTRIBUTE_END, COUNT, INDEX_ALL, INDEX_SEPARATOR, REQUIRED},
 };
+use crate::{cli::Theme, tty::error_style};
 use indoc::{formatdoc, indoc};
 use std::fmt::{self, Display};
 
+/// Distinguishes the kinds of [ParseError] so that they can be handled programmatically, e.g. by
+/// editor integrations, rather than only displayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseErrorKind {
+    /// A template ends with an escape that isn't followed by a character.
+    MissingEscapee,
+    /// An anchor name is blank or contains disallowed characters.
+    InvalidAnchorName,
+    /// An anchor is missing its closing brace.
+    UnclosedAnchor,
+    /// The contents of an index aren't a number, a range, or `*`.
+    InvalidIndex,
+    /// An index is malformed, e.g. it doesn't immediately follow the anchor name.
+    InvalidIndexingOperation,
+    /// The `||` that introduces a default value is malformed.
+    InvalidDefaultValueOperation,
+    /// A default string literal is missing its closing quote.
+    UnclosedDefaultLiteral,
+    /// Anchors are nested within default string literals too deeply.
+    DefaultLiteralTooNested,
+    /// A string parameter of an attribute is missing its closing quote.
+    UnclosedStringParameter,
+    /// A default value contains a disallowed character.
+    DisallowedDefaultCharacter,
+    /// An anchor that is marked as required has default values.
+    DefaultWithRequired,
+    /// An anchor that counts the matches of its capture has an index or default values.
+    CountWithIndexOrDefault,
+    /// The template ends in the middle of an index.
+    UnterminatedIndex,
+    /// The template ends in the middle of default values.
+    UnterminatedDefault,
+    /// Attributes are missing their closing parenthesis.
+    UnclosedAttributes,
+    /// Something other than a delimiter follows the parameters of an attribute.
+    AttributeAfterParameters,
+    /// Attributes aren't followed by a `:`.
+    MissingAttributeEnd,
+    /// An attribute alias is given parameters.
+    AliasWithArguments,
+    /// An attribute alias isn't defined.
+    UndefinedAlias,
+}
+
 #[derive(Debug)]
 pub struct ParseError {
+    kind: ParseErrorKind,
     message: String,
     // Partial or full template string to include in output message
     partial_template: String,
-    // The index of the beginning char that caused the error
+    // The index of the beginning char that caused the error. This counts chars rather than bytes.
     char_index: usize,
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(None))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// What went wrong.
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    /// The human-readable description of what went wrong, without the template or the position.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The position amongst the chars of the template, rather than its bytes, at which the error
+    /// occurred. For templates nested within default string literals this is relative to the
+    /// nested template.
+    pub fn char_index(&self) -> usize {
+        self.char_index
+    }
+
+    /// The template, or the nested template, in which the error occurred.
+    pub fn template(&self) -> &str {
+        &self.partial_template
+    }
+
+    /// Renders the error as it is displayed. If there is a `theme` then the caret marking the
+    /// position of the error and the message are styled for it so that they stand out.
+    pub fn render(&self, theme: Option<Theme>) -> String {
         let ParseError {
             message,
             partial_template,
             char_index,
+            ..
         } = self;
 
-        let mut error_position_display = [' '].repeat(partial_template.len());
+        let width = partial_template.chars().count().max(char_index + 1);
+        let mut error_position_display = [' '].repeat(width);
         error_position_display[*char_index] = '^';
+        let column = char_index + 1;
 
         let error_position: String = error_position_display.into_iter().collect();
+        let error_position = error_position.replacen('^', &error_style("^", theme), 1);
+        let message = error_style(message, theme);
 
-        let output = formatdoc! {"
-            Something went wrong while parsing the provided output template:
+        formatdoc! {"
+            Something went wrong while parsing the provided output template at column {column}:
                 {partial_template}
                 {error_position}
             {message}
-        "};
-        write!(f, "{output}")
+        "}
     }
-}
-
-impl std::error::Error for ParseError {}
 
-impl ParseError {
-    pub fn missing_escapee(char_index: usize, chars: &[char]) -> Self {
+    pub fn missing_escapee(char_index: usize, chars: &[char], escape: char) -> Self {
         ParseError {
+            kind: ParseErrorKind::MissingEscapee,
             char_index,
             partial_template: chars.iter().collect(),
-            message: format!("A character immediately following the '{ESCAPE}' escape is required."),
+            message: format!("A character immediately following the '{escape}' escape is required."),
         }
     }
 
-    pub fn invalid_anchor_name(char_index: usize, chars: &[char]) -> Self {
+    pub fn invalid_anchor_name(char_index: usize, chars: &[char], charset: &str) -> Self {
         ParseError {
+            kind: ParseErrorKind::InvalidAnchorName,
             char_index,
             partial_template: chars.iter().collect(),
             message: format!(
-                "Anchor name cannot be blank and must satisfy the following regular expression: {VALID_ANCHOR_CHARSET}"
+                "Anchor name cannot be blank, must consist of the characters [{charset}], and can't begin with '.' or '-'."
             ),
         }
     }
 
-    pub fn unclosed_anchor(char_index: usize, chars: &[char]) -> Self {
+    pub fn unclosed_anchor(char_index: usize, chars: &[char], close: char) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnclosedAnchor,
             char_index,
             partial_template: chars.iter().collect(),
-            message: format!("Expected a '{ANCHOR_CLOSE}' character to close anchor declaration."),
+            message: format!("Expected a '{close}' character to close anchor declaration."),
         }
     }
 
     pub fn invalid_index(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::InvalidIndex,
             char_index,
             partial_template: chars.iter().collect(),
-            message: String::from("Expected index to be numeric."),
+            message: format!(
+                "Expected index to be numeric, a range, or '{INDEX_ALL}'. Ranges and '{INDEX_ALL}' may be followed by a quoted separator e.g. '{INDEX_ALL}{INDEX_SEPARATOR}\", \"'."
+            ),
         }
     }
 
     pub fn invalid_indexing_operation(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::InvalidIndexingOperation,
             char_index,
             partial_template: chars.iter().collect(),
             message: indoc! {"
-                Invalid index operation. Example of a valid index operation:
+                Invalid index operation. Examples of valid index operations:
                     - '${foo[0]}'
+                    - '${foo[-1]}'
+                    - '${foo[1..3]}'
+                    - '${foo[*]}'
+                    - '${foo[*:\", \"]}'
             "}
             .to_string(),
         }
@@ -88,6 +178,7 @@ impl ParseError {
 
     pub fn invalid_default_value_operation(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::InvalidDefaultValueOperation,
             char_index,
             partial_template: chars.iter().collect(),
             message: indoc! {"
@@ -95,7 +186,9 @@ impl ParseError {
                     - Default to string literal: '${foo || \"bar\"}'
                     - Default to another anchor: '${foo || bar}'
                     - Default to another indexed-anchor: '${foo || bar[0]}'
+                    - Default to an environment variable: '${foo || $BAR}'
                     - Chaining defaults: '${foo || bar || baz}'
+                    - Default on blank values too: '${foo ?? \"bar\"}'
             "}
             .to_string(),
         }
@@ -103,14 +196,27 @@ impl ParseError {
 
     pub fn default_str_literal_missing_closing_quote(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnclosedDefaultLiteral,
             char_index,
             partial_template: chars.iter().collect(),
             message: String::from("Default string literal missing closing quote."),
         }
     }
 
+    pub fn default_literal_too_nested(char_index: usize, chars: &[char]) -> Self {
+        ParseError {
+            kind: ParseErrorKind::DefaultLiteralTooNested,
+            char_index,
+            partial_template: chars.iter().collect(),
+            message: format!(
+                "Anchors can only be nested within default string literals up to {MAX_DEFAULT_NESTING} levels deep."
+            ),
+        }
+    }
+
     pub fn string_parameter_missing_closing_quote(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnclosedStringParameter,
             char_index,
             partial_template: chars.iter().collect(),
             message: String::from("Attribute's string parameter is missing a closing quote."),
@@ -119,6 +225,7 @@ impl ParseError {
 
     pub fn default_parsing_disallowed_char(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::DisallowedDefaultCharacter,
             char_index,
             partial_template: chars.iter().collect(),
             message: String::from(
@@ -129,6 +236,7 @@ impl ParseError {
 
     pub fn default_disallowed_with_required(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::DefaultWithRequired,
             char_index,
             partial_template: chars.iter().collect(),
             message: format!(
@@ -137,8 +245,20 @@ impl ParseError {
         }
     }
 
+    pub fn count_with_index_or_default(char_index: usize, chars: &[char]) -> Self {
+        ParseError {
+            kind: ParseErrorKind::CountWithIndexOrDefault,
+            char_index,
+            partial_template: chars.iter().collect(),
+            message: format!(
+                "Indices and default values are disallowed when an anchor counts the matches of its capture with a leading '{COUNT}'",
+            ),
+        }
+    }
+
     pub fn index_parsing_eol(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnterminatedIndex,
             char_index,
             partial_template: chars.iter().collect(),
             message: String::from("Invalid syntax: template string ends prematurely while parsing an index operation."),
@@ -147,6 +267,7 @@ impl ParseError {
 
     pub fn default_parsing_eol(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnterminatedDefault,
             char_index,
             partial_template: chars.iter().collect(),
             message: String::from("Invalid syntax: template string ends prematurely while parsing default values."),
@@ -155,14 +276,27 @@ impl ParseError {
 
     pub fn attribute_unclosed(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::UnclosedAttributes,
             char_index,
             partial_template: chars.iter().collect(),
             message: format!("Invalid syntax: expected a closing '{ATTRIBUTE_CLOSE}' after attributes."),
         }
     }
 
+    pub fn attribute_after_parameters(char_index: usize, chars: &[char]) -> Self {
+        ParseError {
+            kind: ParseErrorKind::AttributeAfterParameters,
+            char_index,
+            partial_template: chars.iter().collect(),
+            message: format!(
+                "Invalid syntax: expected a '{ATTRIBUTE_DELIMETER}' or '{ATTRIBUTE_CLOSE}' after an attribute's parameters."
+            ),
+        }
+    }
+
     pub fn attribute_end(char_index: usize, chars: &[char]) -> Self {
         ParseError {
+            kind: ParseErrorKind::MissingAttributeEnd,
             char_index,
             partial_template: chars.iter().collect(),
             message: format!(
@@ -170,6 +304,24 @@ impl ParseError {
             ),
         }
     }
+
+    pub fn alias_with_arguments(char_index: usize, chars: &[char], name: &str) -> Self {
+        ParseError {
+            kind: ParseErrorKind::AliasWithArguments,
+            char_index,
+            partial_template: chars.iter().collect(),
+            message: format!("Attribute alias '{name}' doesn't take any arguments."),
+        }
+    }
+
+    pub fn undefined_alias(char_index: usize, chars: &[char], name: &str) -> Self {
+        ParseError {
+            kind: ParseErrorKind::UndefinedAlias,
+            char_index,
+            partial_template: chars.iter().collect(),
+            message: format!("Undefined attribute alias '{name}'."),
+        }
+    }
 }
 
 #[test]
@@ -177,15 +329,79 @@ fn test_parse_error_display() {
     use indoc::indoc;
 
     let template: Vec<char> = "output=${foo} \\".chars().collect();
-    let error = ParseError::missing_escapee(14, &template);
+    let error = ParseError::missing_escapee(14, &template, super::token::ESCAPE);
 
     assert_eq!(
         format!("{error}"),
         indoc! {"
-            Something went wrong while parsing the provided output template:
+            Something went wrong while parsing the provided output template at column 15:
                 output=${foo} \\
                               ^
             A character immediately following the '\\' escape is required.
         "},
-    )
+    );
+
+    let colorized = error.render(Some(Theme::Dark));
+    assert!(colorized.contains(&error_style("^", Some(Theme::Dark))));
+    assert_eq!(
+        colorized.replace("\x1b[1;31m", "").replace("\x1b[0m", ""),
+        format!("{error}")
+    );
+
+    let colorized = error.render(Some(Theme::Light));
+    assert!(colorized.contains(&error_style("^", Some(Theme::Light))));
+    assert_ne!(
+        error_style("^", Some(Theme::Light)),
+        error_style("^", Some(Theme::Dark))
+    );
+}
+
+#[test]
+fn test_parse_error_display_multibyte() {
+    let template: Vec<char> = "λ→{foo[x]} ok".chars().collect();
+    let error = ParseError::invalid_index(6, &template);
+    let display = format!("{error}");
+    let lines = display.lines().collect::<Vec<_>>();
+
+    assert_eq!(
+        lines[0],
+        "Something went wrong while parsing the provided output template at column 7:"
+    );
+    assert_eq!(lines[1], "    λ→{foo[x]} ok");
+    assert_eq!(lines[2].trim_end(), "          ^");
+    assert_eq!(lines[2].chars().count(), lines[1].chars().count());
+}
+
+#[test]
+fn test_parse_error_accessors() {
+    use super::{Aliases, OutputTemplate};
+
+    let cases = [
+        ("out={foo", ParseErrorKind::UnclosedAnchor, 7),
+        ("out={foo[x]}", ParseErrorKind::InvalidIndex, 10),
+        ("λ={!foo || bar}", ParseErrorKind::DefaultWithRequired, 8),
+        ("out={(red):foo} \\", ParseErrorKind::MissingEscapee, 16),
+    ];
+    for (template, kind, char_index) in cases {
+        let err = OutputTemplate::parse(template).unwrap_err();
+        let err = err.downcast_ref::<ParseError>().expect(template);
+        assert_eq!(err.kind(), kind, "{template}");
+        assert_eq!(err.char_index(), char_index, "{template}");
+        assert_eq!(err.template(), template);
+        assert!(format!("{err}").contains(err.message()));
+    }
+
+    let aliases = Aliases::parse(&["error=red|bold"]).unwrap();
+    let cases = [
+        ("out={(@warn):foo}", ParseErrorKind::UndefinedAlias, 6),
+        ("out={(bold|@error(1)):foo}", ParseErrorKind::AliasWithArguments, 11),
+    ];
+    for (template, kind, char_index) in cases {
+        let err = OutputTemplate::parse_with_aliases(template, &aliases).unwrap_err();
+        let err = err.downcast_ref::<ParseError>().expect(template);
+        assert_eq!(err.kind(), kind, "{template}");
+        assert_eq!(err.char_index(), char_index, "{template}");
+        assert_eq!(err.template(), template);
+        assert_eq!(format!("{err}").lines().nth(2).unwrap().trim(), "^");
+    }
 }
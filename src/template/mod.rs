@@ -1,11 +1,18 @@
-use anyhow::Result;
-use std::collections::HashMap;
+use crate::captures::{Captures, Matches};
+use anyhow::{format_err, Result};
+use std::{borrow::Cow, cell::Cell, env, iter};
 
 /// Tokens with special meaning used in the template string
 mod token;
 
 pub mod parse;
-pub use parse::{Anchor, Attribute, DefaultValue};
+use parse::rules::Rules;
+pub use parse::{Anchor, Attribute, DefaultValue, Index, ParseOptions};
+pub use token::{ANCHOR_CLOSE, ANCHOR_OPEN, ESCAPE};
+
+/// Concerned with named lists of attributes that templates can reference.
+pub mod alias;
+pub use alias::Aliases;
 
 #[cfg(test)]
 mod test;
@@ -13,18 +20,49 @@ mod test;
 /// Errors specific to template string parsing
 pub mod error;
 
+/// The number of bytes that the output is expected to need for each anchor in addition to the
+/// literal sections of the template.
+const ANCHOR_CAPACITY: usize = 16;
+
+thread_local! {
+    /// The slots of the template being transformed. The vector is reused by every transformation on
+    /// the same thread so that slots aren't allocated for every line.
+    static SLOTS: Cell<Vec<Option<Option<Matches<'static>>>>> = const { Cell::new(Vec::new()) };
+}
+
 /// The actual template concerned with generating the output string.
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct OutputTemplate {
     targets: Vec<InterpolationTarget>,
+    /// The number of distinct capture names referenced by anchors. Each anchor is assigned the slot
+    /// of its capture name so that a capture referenced by several anchors is only looked up once
+    /// per transformation.
+    slots: usize,
+    /// The number of bytes reserved for the output up front so that it's seldom grown.
+    capacity: usize,
+    /// Whether style attributes are skipped because colorization is disabled.
+    no_color: bool,
+}
+
+/// An anchor that didn't have a match when transforming, as reported by
+/// [OutputTemplate::transform_with_report].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnmatchedAnchor<'t> {
+    /// The name of the anchor.
+    pub name: &'t str,
+    /// Whether or not one of the default values of the anchor was used in its place. If not then
+    /// the anchor was interpolated as an empty string.
+    pub defaulted: bool,
+    /// Whether or not the anchor is required in which case the entire output is empty.
+    pub required: bool,
 }
 
 /// Utility type that defines a segment of the output which is defined either by a literal or an
-/// anchor.
-#[derive(Debug)]
+/// anchor along with the slot of its capture name.
+#[derive(Debug, Clone)]
 enum InterpolationTarget {
     Literal(String),
-    Anchor(Anchor),
+    Anchor(Anchor, usize),
 }
 
 impl OutputTemplate {
@@ -32,56 +70,252 @@ impl OutputTemplate {
     /// ready to produce an output.
     pub fn parse(template: &str) -> Result<Self> {
         let anchors = parse::parse(template)?;
+        Ok(Self::from_anchors(
+            template,
+            &anchors,
+            &ParseOptions::new(&Aliases::default()),
+        ))
+    }
 
-        let mut targets = Vec::new();
+    /// Like [parse](OutputTemplate::parse) but attributes may also reference `aliases`, e.g.
+    /// `{(@error):lvl}`.
+    #[cfg(test)]
+    pub fn parse_with_aliases(template: &str, aliases: &Aliases) -> Result<Self> {
+        Self::parse_with_options(template, ParseOptions::new(aliases))
+    }
 
-        let mut left_cursor = 0;
-        let mut right_cursor = 0;
-
-        for anchor in &anchors {
-            let start = left_cursor;
-            for i in start..template.len() {
-                right_cursor = i;
-                if right_cursor == anchor.start {
-                    if left_cursor != right_cursor {
-                        let section = String::from(&template[left_cursor..right_cursor]);
-                        targets.push(InterpolationTarget::Literal(section));
-                    }
-                    targets.push(InterpolationTarget::Anchor(anchor.clone()));
+    /// Like [parse](OutputTemplate::parse) but according to `options`, i.e. attributes may also
+    /// reference aliases and the escape character and anchor delimiters may be other than [ESCAPE],
+    /// [ANCHOR_OPEN], and [ANCHOR_CLOSE].
+    pub fn parse_with_options(template: &str, options: ParseOptions<'_>) -> Result<Self> {
+        let anchors = parse::parse_with_options(template, options)?;
+        Ok(Self::from_anchors(template, &anchors, &options))
+    }
+
+    /// Parses a `template` that is nested within `nesting` default string literals.
+    fn parse_nested(template: &str, nesting: usize, options: ParseOptions<'_>) -> Result<Self> {
+        let anchors = parse::parse_nested(template, nesting, options)?;
+        Ok(Self::from_anchors(template, &anchors, &options))
+    }
 
-                    left_cursor = anchor.end;
-                    right_cursor = left_cursor;
+    /// Enables or disables colorization, which is enabled by default. If disabled then attributes
+    /// that only style the text, such as colors, are skipped whereas attributes that transform
+    /// the text still apply. See [TtyContext::color_enabled](crate::tty::TtyContext::color_enabled).
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.set_color(color);
+        self
+    }
+
+    /// Sets colorization for this template as well as those nested in its default values.
+    fn set_color(&mut self, color: bool) {
+        self.no_color = !color;
+
+        for target in &mut self.targets {
+            let InterpolationTarget::Anchor(anchor, _) = target else {
+                continue;
+            };
+            for default_val in &mut anchor.defaults {
+                let default_val = match default_val {
+                    DefaultValue::Attributed { value, .. } => value.as_mut(),
+                    default_val => default_val,
+                };
+                if let DefaultValue::Interpolated { template, .. } = default_val {
+                    template.set_color(color);
                 }
             }
         }
-        if right_cursor != template.len() {
-            let section = String::from(&template[left_cursor..]);
-            if !section.is_empty() {
+    }
+
+    /// Splits the `template` into literal sections and the parsed `anchors`. Literal sections are
+    /// produced according to the escape and anchor delimiters of `options`, see [literal].
+    fn from_anchors(template: &str, anchors: &[Anchor], options: &ParseOptions<'_>) -> Self {
+        // Anchors are positioned by char whereas the template is sliced by byte.
+        let offsets = template
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(iter::once(template.len()))
+            .collect::<Vec<_>>();
+
+        let mut targets = Vec::new();
+        let mut slot_names = Vec::<&str>::new();
+        let mut cursor = 0;
+
+        for anchor in anchors {
+            let start = offsets[anchor.start];
+            if cursor != start {
+                let section = literal(&template[cursor..start], options);
                 targets.push(InterpolationTarget::Literal(section));
             }
+            let slot = match slot_names.iter().position(|name| *name == anchor.name) {
+                Some(slot) => slot,
+                None => {
+                    slot_names.push(&anchor.name);
+                    slot_names.len() - 1
+                }
+            };
+            targets.push(InterpolationTarget::Anchor(anchor.clone(), slot));
+            cursor = offsets[anchor.end];
+        }
+        if cursor != template.len() {
+            let section = literal(&template[cursor..], options);
+            targets.push(InterpolationTarget::Literal(section));
+        }
+        let capacity = targets
+            .iter()
+            .map(|target| match target {
+                InterpolationTarget::Literal(section) => section.len(),
+                InterpolationTarget::Anchor(..) => ANCHOR_CAPACITY,
+            })
+            .sum();
+
+        Self {
+            targets,
+            slots: slot_names.len(),
+            capacity,
+            no_color: false,
+        }
+    }
+
+    /// The anchors of the template in the order they appear.
+    pub fn anchors(&self) -> impl Iterator<Item = &Anchor> {
+        self.targets.iter().filter_map(|target| match target {
+            InterpolationTarget::Anchor(anchor, _) => Some(anchor),
+            InterpolationTarget::Literal(_) => None,
+        })
+    }
+
+    /// The names of every anchor referenced by the template, including those used as defaults and
+    /// those nested within default string literals.
+    pub fn anchor_names(&self) -> impl Iterator<Item = &str> {
+        let mut names = Vec::new();
+        self.collect_anchor_names(&mut names);
+        names.into_iter()
+    }
+
+    fn collect_anchor_names<'t>(&'t self, names: &mut Vec<&'t str>) {
+        for anchor in self.anchors() {
+            if !anchor.name.is_empty() {
+                names.push(&anchor.name);
+            }
+            for default_val in &anchor.defaults {
+                collect_default_names(default_val, names);
+            }
+        }
+    }
+
+    /// Refers anchors whose names contain `.` or `-` to the capture group of the same name with `_`
+    /// in their place, e.g. `{http.status}` to `(?<http_status>...)`, unless one of `group_names` is
+    /// the name as written. Applies to default values and templates nested within them as well.
+    pub fn resolve_capture_names(&mut self, group_names: &[&str]) {
+        for target in &mut self.targets {
+            let InterpolationTarget::Anchor(anchor, _) = target else {
+                continue;
+            };
+            if let Some(name) = Rules::capture_name(&anchor.name, group_names) {
+                anchor.name = name;
+            }
+            for default_val in &mut anchor.defaults {
+                resolve_default_names(default_val, group_names);
+            }
         }
-        Ok(Self { targets })
     }
 
     /// The actual transformation logic. The original template string that is provided
-    /// is used in conjunction with the `interpolation_map` to produce the transformed
-    /// output. The key of the map is the name of anchor while the associated value is
-    /// a vector containing the possible values used to interpolate the anchor-sites
-    /// in the template string, depending on whether an index is specified.
-    pub fn transform(&self, interpolation_map: &HashMap<&str, Vec<&str>>) -> String {
-        let mut out = String::new();
+    /// is used in conjunction with the `captures` to produce the transformed
+    /// output. Each anchor is interpolated with the matches of the capture of the same
+    /// name, depending on whether an index is specified.
+    pub fn transform(&self, captures: &dyn Captures) -> String {
+        self.transform_impl(captures, None)
+    }
+
+    /// Like [transform](OutputTemplate::transform) except that an anchor that has neither a match
+    /// nor a default value that produces one is an error rather than being interpolated as an empty
+    /// string. Anchors that are required still cause the output to be empty.
+    pub fn transform_strict(&self, captures: &dyn Captures) -> Result<String> {
+        let (out, unmatched) = self.transform_with_report(captures);
+
+        if unmatched.iter().any(|anchor| anchor.required) {
+            return Ok(out);
+        }
+        if let Some(anchor) = unmatched.iter().find(|anchor| !anchor.defaulted) {
+            return Err(format_err!("no match for anchor '{}'", anchor.name));
+        }
+        Ok(out)
+    }
+
+    /// Like [transform](OutputTemplate::transform) but also reports every anchor that didn't have
+    /// a match, in the order they appear, along with whether a default value was used in its place.
+    /// If a required anchor doesn't have a match then it is the last anchor reported.
+    pub fn transform_with_report(&self, captures: &dyn Captures) -> (String, Vec<UnmatchedAnchor<'_>>) {
+        let mut unmatched = Vec::new();
+        let out = self.transform_impl(captures, Some(&mut unmatched));
+        (out, unmatched)
+    }
+
+    /// Performs the transformation, collecting anchors that didn't have a match into `unmatched` if
+    /// provided.
+    fn transform_impl<'t>(
+        &'t self,
+        captures: &dyn Captures,
+        unmatched: Option<&mut Vec<UnmatchedAnchor<'t>>>,
+    ) -> String {
+        // Templates nested within default values find the vector taken and start with a new one.
+        let mut slots = recycle_slots(SLOTS.take());
+        slots.resize(self.slots, None);
+
+        let out = self.interpolate(captures, &mut slots, unmatched);
+        SLOTS.set(recycle_slots(slots));
+        out
+    }
+
+    /// Interpolates every target. `slots` holds the matches of each capture name, looked up the
+    /// first time an anchor needs them.
+    fn interpolate<'t, 'c>(
+        &'t self,
+        captures: &'c dyn Captures,
+        slots: &mut [Option<Option<Matches<'c>>>],
+        mut unmatched: Option<&mut Vec<UnmatchedAnchor<'t>>>,
+    ) -> String {
+        let mut out = String::with_capacity(self.capacity);
 
         for target in &self.targets {
             match target {
-                InterpolationTarget::Anchor(anchor) => {
+                InterpolationTarget::Anchor(anchor, slot) => {
                     let name = anchor.name.as_str();
-                    let index = anchor.index.unwrap_or_default();
+                    let vals = *slots[*slot].get_or_insert_with(|| captures.get(name));
+
+                    // A count is interpolated even if it's zero unless the anchor is required.
+                    if anchor.count_of {
+                        let count = vals.as_ref().map_or(0, Matches::len);
 
-                    if let Some(val) = interpolation_map.get(name).and_then(|vals| vals.get(index)) {
+                        if count == 0 && anchor.required {
+                            if let Some(unmatched) = unmatched {
+                                unmatched.push(UnmatchedAnchor {
+                                    name,
+                                    defaulted: false,
+                                    required: true,
+                                });
+                            }
+                            return String::new();
+                        }
+                        let val = count.to_string();
                         if anchor.attributes.is_empty() {
-                            out.push_str(val);
+                            out.push_str(&val);
                         } else {
-                            let stylized = Attribute::apply(val, &anchor.attributes);
+                            let stylized = Attribute::apply_with_color(&val, &anchor.attributes, !self.no_color);
+                            out.push_str(&stylized);
+                        }
+                        continue;
+                    }
+
+                    let val = index_vals(vals, anchor.index.as_ref())
+                        .filter(|val| !anchor.default_on_blank || !val.is_empty());
+
+                    if let Some(val) = val {
+                        if anchor.attributes.is_empty() {
+                            out.push_str(&val);
+                        } else {
+                            let stylized = Attribute::apply_with_color(&val, &anchor.attributes, !self.no_color);
                             out.push_str(&stylized);
                         }
                         continue;
@@ -89,34 +323,50 @@ impl OutputTemplate {
 
                     // No match, return empty string.
                     if anchor.required {
+                        if let Some(unmatched) = unmatched {
+                            unmatched.push(UnmatchedAnchor {
+                                name,
+                                defaulted: false,
+                                required: true,
+                            });
+                        }
                         return String::new();
                     }
 
+                    let mut defaulted = false;
+
                     for default_val in &anchor.defaults {
-                        match default_val {
-                            DefaultValue::Literal(val) => {
-                                if anchor.attributes.is_empty() {
-                                    out.push_str(val);
-                                } else {
-                                    let stylized = Attribute::apply(val, &anchor.attributes);
-                                    out.push_str(&stylized);
-                                }
-                                break;
-                            }
-                            DefaultValue::Anchor { name, index } => {
-                                let name = name.as_str();
-                                let index = index.unwrap_or_default();
-                                if let Some(val) = interpolation_map.get(name).and_then(|vals| vals.get(index)) {
-                                    if anchor.attributes.is_empty() {
-                                        out.push_str(val);
-                                    } else {
-                                        let stylized = Attribute::apply(val, &anchor.attributes);
-                                        out.push_str(&stylized);
-                                    }
-                                    break;
-                                }
-                            }
+                        // Default values with attributes of their own are styled with those
+                        // rather than with the attributes of the anchor.
+                        let (default_val, attributes) = match default_val {
+                            DefaultValue::Attributed { value, attributes } => (value.as_ref(), attributes),
+                            _ => (default_val, &anchor.attributes),
+                        };
+                        // Literals are used as written even if blank since they're given explicitly.
+                        let val = resolve_default(default_val, captures).filter(|val| {
+                            !anchor.default_on_blank
+                                || !val.is_empty()
+                                || matches!(default_val, DefaultValue::Literal(_))
+                        });
+                        let Some(val) = val else {
+                            continue;
+                        };
+                        if attributes.is_empty() {
+                            out.push_str(&val);
+                        } else {
+                            let stylized = Attribute::apply_with_color(&val, attributes, !self.no_color);
+                            out.push_str(&stylized);
                         }
+                        defaulted = true;
+                        break;
+                    }
+
+                    if let Some(unmatched) = unmatched.as_mut() {
+                        unmatched.push(UnmatchedAnchor {
+                            name,
+                            defaulted,
+                            required: false,
+                        });
                     }
                 }
                 InterpolationTarget::Literal(val) => out.push_str(val),
@@ -125,3 +375,103 @@ impl OutputTemplate {
         out
     }
 }
+
+/// Empties `slots` so that its allocation can hold the slots of another transformation.
+fn recycle_slots<'a, 'b>(mut slots: Vec<Option<Option<Matches<'a>>>>) -> Vec<Option<Option<Matches<'b>>>> {
+    slots.clear();
+    // Mapping an empty vector between types of the same layout reuses its allocation.
+    slots.into_iter().map(|_| None).collect()
+}
+
+/// Collects the names of the anchors referenced by `default_val` into `names`.
+fn collect_default_names<'t>(default_val: &'t DefaultValue, names: &mut Vec<&'t str>) {
+    match default_val {
+        DefaultValue::Anchor { name, .. } => names.push(name),
+        DefaultValue::Interpolated { template, .. } => template.collect_anchor_names(names),
+        DefaultValue::Attributed { value, .. } => collect_default_names(value, names),
+        DefaultValue::Literal(_) | DefaultValue::Env(_) => (),
+    }
+}
+
+fn resolve_default_names(default_val: &mut DefaultValue, group_names: &[&str]) {
+    match default_val {
+        DefaultValue::Anchor { name, .. } => {
+            if let Some(resolved) = Rules::capture_name(name, group_names) {
+                *name = resolved;
+            }
+        }
+        DefaultValue::Interpolated { template, .. } => template.resolve_capture_names(group_names),
+        DefaultValue::Attributed { value, .. } => resolve_default_names(value, group_names),
+        DefaultValue::Literal(_) | DefaultValue::Env(_) => (),
+    }
+}
+
+/// Produces the text of a literal `section` of the template, collapsing the doubled anchor
+/// delimiters of `options`, e.g. `{{` and `}}`, into a single delimiter. Characters escaped by the
+/// escape of `options` are left as is.
+fn literal(section: &str, options: &ParseOptions<'_>) -> String {
+    let mut out = String::with_capacity(section.len());
+    let mut chars = section.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        out.push(c);
+
+        if c == options.escape {
+            out.extend(chars.next());
+        } else if (c == options.anchor_open || c == options.anchor_close) && chars.peek() == Some(&c) {
+            chars.next();
+        }
+    }
+    out
+}
+
+/// Whether `c` may be used as the escape character in place of [ESCAPE]. Characters with special
+/// meaning within templates, alphanumeric characters, and whitespace may not be.
+pub fn is_valid_escape(c: char) -> bool {
+    !(token::RESERVED.contains(&c) || c == alias::ALIAS || c.is_alphanumeric() || c.is_whitespace())
+}
+
+/// Whether `c` may delimit anchors in place of [ANCHOR_OPEN] or [ANCHOR_CLOSE]. Like the escape,
+/// characters with special meaning within templates, alphanumeric characters, and whitespace may
+/// not, though either brace may.
+pub fn is_valid_anchor_delimiter(c: char) -> bool {
+    c == ANCHOR_OPEN || c == ANCHOR_CLOSE || is_valid_escape(c)
+}
+
+/// Resolves the value of a default value, if it has one. Literals always have a value whereas
+/// anchors and environment variables may not.
+fn resolve_default<'a>(default_val: &'a DefaultValue, captures: &'a dyn Captures) -> Option<Cow<'a, str>> {
+    match default_val {
+        DefaultValue::Literal(val) => Some(Cow::Borrowed(val)),
+        DefaultValue::Interpolated { template, .. } => Some(Cow::Owned(template.transform(captures))),
+        DefaultValue::Anchor { name, index } => lookup(captures, name, index.as_ref()),
+        DefaultValue::Env(var) => env::var(var).ok().filter(|v| !v.is_empty()).map(Cow::Owned),
+        DefaultValue::Attributed { value, .. } => resolve_default(value, captures),
+    }
+}
+
+/// Looks up the value used to interpolate the anchor `name` given its optional `index`. Returns
+/// `None` if there isn't an associated match.
+fn lookup<'a>(captures: &'a dyn Captures, name: &str, index: Option<&Index>) -> Option<Cow<'a, str>> {
+    index_vals(captures.get(name), index)
+}
+
+/// Selects the value used to interpolate an anchor from the matches of its capture, `vals`, given
+/// its optional `index`. Returns `None` if there isn't an associated match.
+fn index_vals<'a>(vals: Option<Matches<'a>>, index: Option<&Index>) -> Option<Cow<'a, str>> {
+    let vals = vals?;
+
+    match index {
+        None => vals.first(),
+        Some(Index::One(i)) => Index::resolve(*i, vals.len()).and_then(|i| vals.get(i)),
+        Some(Index::All(_)) if vals.is_empty() => None,
+        Some(Index::All(separator)) => Some(Cow::Owned(vals.join(separator))),
+        Some(Index::Range { start, end, separator }) => {
+            let (start, end) = Index::resolve_range(*start, *end, vals.len());
+            if start == end {
+                return None;
+            }
+            Some(Cow::Owned(vals.slice(start..end).join(separator)))
+        }
+    }
+}
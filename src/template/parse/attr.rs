@@ -1,16 +1,56 @@
-use super::super::token::{LITERAL_DOUBLE_QUOTE, LITERAL_SINGLE_QUOTE, PARAM_DELIMETER};
+use super::super::token::{ENV_VAR, ESCAPE, LITERAL_DOUBLE_QUOTE, LITERAL_SINGLE_QUOTE, PARAM_DELIMETER};
 use anyhow::{format_err, Context, Result};
-use crossterm::style::Stylize;
+use chrono::NaiveDateTime;
+use crossterm::style::{Color, StyledContent, Stylize};
 use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::{env, fmt::Write, sync::OnceLock};
 
 #[derive(Debug, Clone)]
 pub struct Attribute {
     pub kind: AttributeKind,
     pub must_match: Option<Regex>,
+    /// Set when a conditional attribute's pattern comes from an environment variable, e.g.
+    /// `?red($HIGHLIGHT)`, rather than a literal.
+    pub must_match_env: Option<EnvPattern>,
+    /// Set for conditional attributes written with `??`, which only apply if the nearest preceding
+    /// conditional attribute, and any others chained to it, didn't match. This allows a run of
+    /// conditional attributes to form a scale in which only the first match applies.
+    pub chained: bool,
+}
+
+/// A regular expression read from an environment variable. The pattern is read and compiled the
+/// first time it's needed and cached thereafter.
+#[derive(Debug, Clone)]
+pub struct EnvPattern {
+    pub var: String,
+    compiled: OnceLock<Option<Regex>>,
+}
+
+impl EnvPattern {
+    pub fn new(var: &str) -> Self {
+        Self {
+            var: var.to_string(),
+            compiled: OnceLock::new(),
+        }
+    }
+
+    /// The compiled pattern or `None` if the variable is unset or isn't a valid regular
+    /// expression, in which case the attribute never applies.
+    pub fn regex(&self) -> Option<&Regex> {
+        self.compiled
+            .get_or_init(|| {
+                let pattern = env::var(&self.var).ok()?;
+                Regex::new(&pattern)
+                    .map_err(|e| log::warn!("${} is not a valid regular expression: {e}", self.var))
+                    .ok()
+            })
+            .as_ref()
+    }
 }
 
 /// Attributes that can be applied to an anchor.
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum AttributeKind {
     Black,
     Red,
@@ -33,6 +73,27 @@ pub enum AttributeKind {
     Reverse,
     CrossedOut,
 
+    /// A color outside of the 8 named ANSI colors applied to either the foreground or, if
+    /// `background`, the background. Comes in three flavors, each with a `bg_` prefixed variant
+    /// for the background:
+    /// - `rgb(40, 40, 40)`
+    /// - `hex("#282828")`
+    /// - `color256(235)`
+    Color {
+        color: Color,
+        background: bool,
+    },
+
+    /// Colors the foreground of each character with a color that is interpolated from the `start`
+    /// color of the first character to the `end` color of the last, each given as a hex color.
+    /// Characters are counted as Unicode scalar values. If several gradients apply then the last
+    /// one does. Applied after every other style so that each character keeps them.
+    /// - `gradient('#ff0000', '#00ff00')`
+    Gradient {
+        start: (u8, u8, u8),
+        end: (u8, u8, u8),
+    },
+
     /// Comes in three flavors:
     /// - `lalign` (left align)
     /// - `ralign` (right align)
@@ -44,6 +105,103 @@ pub enum AttributeKind {
         direction: Alignment,
         width: usize,
     },
+
+    /// Pads or truncates text to exactly the given display width. Comes in four flavors:
+    /// - `fit`/`lfit` (left align)
+    /// - `rfit` (right align)
+    /// - `cfit` (center align)
+    ///
+    /// The first argument is the width. If the second argument is `ellipsis` then truncated text
+    /// ends with `…`.
+    /// - `fit(10)`
+    /// - `rfit(10, 'ellipsis')`
+    Fit {
+        width: usize,
+        align: Alignment,
+        ellipsis: bool,
+    },
+
+    /// Repeats text the given number of times. Repetition precedes styling so that the repeated
+    /// text is styled as a whole.
+    /// - `repeat(20)`
+    Repeat {
+        count: usize,
+    },
+
+    /// Wraps text at the given number of columns by breaking lines at spaces, or within words
+    /// that are wider than the width on their own. Lines that text already spans are wrapped
+    /// individually. The space at which a line is broken is removed.
+    /// - `wrap(80)`
+    Wrap {
+        width: usize,
+    },
+
+    /// Prefixes each non-empty line of text with the given number of spaces, e.g. to nest a value
+    /// that spans several lines beneath the rest of the output. Lines are aligned and styled on
+    /// their own so that the indentation itself isn't styled. See [Phase::Indent].
+    /// - `indent(4)`
+    Indent {
+        spaces: usize,
+    },
+
+    /// Renders a number as a bar of `█` followed by `░` that is `width` columns
+    /// wide, filled in proportion to the number relative to `max`. Numbers are clamped between 0
+    /// and `max`. Values that aren't numbers render an empty bar.
+    /// - `bar(20, 100)`
+    Bar {
+        width: usize,
+        max: f64,
+    },
+
+    /// Replaces text with the first `len` hex characters of its SHA-256 digest so that values can be
+    /// redacted while remaining correlatable. The length defaults to 8 and is at most 64.
+    /// - `hash`
+    /// - `hash(12)`
+    Hash {
+        len: usize,
+    },
+
+    /// Keeps the first `keep_start` and last `keep_end` characters of text and replaces the rest
+    /// with `ch`, which defaults to `*`. Text that isn't longer than the characters to keep is
+    /// masked entirely.
+    /// - `mask(4, 4)`
+    /// - `mask(4, 4, '#')`
+    Mask {
+        keep_start: usize,
+        keep_end: usize,
+        ch: char,
+    },
+
+    /// Reformats a timestamp. The first argument is the strftime format of the value and the
+    /// second is the strftime format to output. Values that can't be parsed are left as is.
+    /// - `date("%Y-%m-%dT%H:%M:%S", "%H:%M")`
+    Date {
+        input_fmt: String,
+        output_fmt: String,
+    },
+
+    /// Formats a number by grouping integer digits in threes. The first argument is the grouping
+    /// character, which defaults to ',', and the optional second is the number of decimal places.
+    /// Values that aren't numbers are left as is.
+    /// - `num(',', 2)`
+    Num {
+        group: char,
+        precision: Option<usize>,
+    },
+
+    /// Formats a byte count in the largest unit in which it is at least 1, e.g. `1.0 MiB`. Uses
+    /// binary units by default or SI units if the argument is `si`. Values that aren't numbers
+    /// are left as is.
+    /// - `bytesize`
+    /// - `bytesize('si')`
+    ByteSize {
+        binary: bool,
+    },
+
+    /// Prefixes a number with an explicit sign so that positive numbers and zero read as `+42` and
+    /// `+0`. Values that aren't numbers are left as is.
+    /// - `signed`
+    Signed,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -53,34 +211,80 @@ pub enum Alignment {
     Center,
 }
 
+/// The phases in which attributes are applied, in order. Within a phase, attributes are applied
+/// in the order they are written.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Phase {
+    /// Attributes that transform the text itself, e.g. `date`, `num`, `bytesize`, `signed`,
+    /// `repeat`, `bar`, `hash`, and `mask`.
+    Transform,
+    /// Attributes that adjust the width of the text, i.e. `lalign` and `fit` and their variants.
+    /// These follow transformations so that they see the final text and precede styles as
+    /// ANSI-escape sequences would otherwise count towards the width.
+    Width,
+    /// Attributes that only style the text, i.e. colors and text decorations.
+    Style,
+    /// Attributes that indent each line of the text, i.e. `indent`. Text that is indented is split
+    /// into lines after the transforms and the remaining phases are applied to each line on its
+    /// own, so this phase effectively encloses them.
+    Indent,
+}
+
+impl AttributeKind {
+    /// The phase in which the attribute is applied. See [Phase].
+    pub fn phase(&self) -> Phase {
+        match self {
+            Self::Date { .. }
+            | Self::Num { .. }
+            | Self::ByteSize { .. }
+            | Self::Signed
+            | Self::Repeat { .. }
+            | Self::Wrap { .. }
+            | Self::Bar { .. }
+            | Self::Hash { .. }
+            | Self::Mask { .. } => Phase::Transform,
+            Self::Align { .. } | Self::Fit { .. } => Phase::Width,
+            Self::Indent { .. } => Phase::Indent,
+            _ => Phase::Style,
+        }
+    }
+}
+
 impl Attribute {
     /// TODO: Clean this up
-    pub fn parse(val: String, raw_args: Option<String>) -> Result<Self> {
+    pub fn parse(val: String, raw_args: Option<String>, escape: char) -> Result<Self> {
         let mut conditional = false;
+        let mut chained = false;
 
-        let attr_name = if let Some(stripped) = val.strip_prefix("?") {
+        let attr_name = if let Some(stripped) = val.strip_prefix("??") {
+            conditional = true;
+            chained = true;
+            stripped.to_lowercase()
+        } else if let Some(stripped) = val.strip_prefix("?") {
             conditional = true;
             stripped.to_lowercase()
         } else {
             val.to_lowercase()
         };
 
-        let args = raw_args.map_or_else(Vec::new, |rarg| {
-            rarg.split(PARAM_DELIMETER)
-                .map(|raws| {
-                    raws.trim()
-                        .trim_start_matches(LITERAL_SINGLE_QUOTE)
-                        .trim_start_matches(LITERAL_DOUBLE_QUOTE)
-                        .trim_end_matches(LITERAL_SINGLE_QUOTE)
-                        .trim_end_matches(LITERAL_DOUBLE_QUOTE)
-                        .to_string()
-                })
-                .collect::<Vec<String>>()
-        });
+        // An unquoted first argument such as `$HIGHLIGHT` names an environment variable. This has to
+        // be determined before the arguments are unquoted.
+        let env_var = raw_args
+            .as_deref()
+            .and_then(|rarg| rarg.split(PARAM_DELIMETER).next())
+            .and_then(|first| first.trim().strip_prefix(ENV_VAR))
+            .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+            .map(EnvPattern::new);
+
+        let args = raw_args.map_or_else(Vec::new, |rarg| split_args(&rarg, escape));
         let mut args_iter = args.iter();
         let mut must_match = None;
+        let mut must_match_env = None;
 
-        if conditional {
+        if conditional && env_var.is_some() {
+            args_iter.next();
+            must_match_env = env_var;
+        } else if conditional {
             if let Some(pattern) = args_iter.next() {
                 let re = Regex::new(pattern).with_context(|| {
                     format!(
@@ -116,6 +320,59 @@ impl Attribute {
             "underlined" => AttributeKind::Underlined,
             "reverse" => AttributeKind::Reverse,
             "crossedout" | "crossed_out" | "crossed-out" => AttributeKind::CrossedOut,
+            "rgb" | "bg_rgb" | "bg-rgb" => {
+                let mut channels = [0; 3];
+                for channel in &mut channels {
+                    *channel = args_iter.next().and_then(|c| c.parse::<u8>().ok()).ok_or_else(|| {
+                        format_err!(
+                            "expected '{attr_name}' to have three arguments from 0 to 255: red, green, and blue"
+                        )
+                    })?;
+                }
+                let [r, g, b] = channels;
+                AttributeKind::Color {
+                    color: Color::Rgb { r, g, b },
+                    background: attr_name.starts_with("bg"),
+                }
+            }
+            "hex" | "bg_hex" | "bg-hex" => {
+                let color = args_iter.next().and_then(|hex| parse_hex(hex)).ok_or_else(|| {
+                    format_err!("expected argument to '{attr_name}' to be a hex color such as '#282828'")
+                })?;
+                AttributeKind::Color {
+                    color,
+                    background: attr_name.starts_with("bg"),
+                }
+            }
+            "gradient" => {
+                let mut colors = [(0, 0, 0); 2];
+                for color in &mut colors {
+                    *color = args_iter
+                        .next()
+                        .and_then(|hex| parse_hex(hex))
+                        .and_then(|color| match color {
+                            Color::Rgb { r, g, b } => Some((r, g, b)),
+                            _ => None,
+                        })
+                        .ok_or_else(|| {
+                            format_err!(
+                                "expected 'gradient' to have two hex colors such as '#ff0000' as arguments: the start and the end"
+                            )
+                        })?;
+                }
+                let [start, end] = colors;
+                AttributeKind::Gradient { start, end }
+            }
+            "color256" | "bg_color256" | "bg-color256" => {
+                let index = args_iter
+                    .next()
+                    .and_then(|i| i.parse::<u8>().ok())
+                    .ok_or_else(|| format_err!("expected argument to '{attr_name}' to be a number from 0 to 255"))?;
+                AttributeKind::Color {
+                    color: Color::AnsiValue(index),
+                    background: attr_name.starts_with("bg"),
+                }
+            }
             "lalign" | "calign" | "ralign" => {
                 let width = args_iter
                     .next()
@@ -142,58 +399,722 @@ impl Attribute {
                     _ => unreachable!(),
                 }
             }
+            "fit" | "lfit" | "cfit" | "rfit" => {
+                let width = args_iter
+                    .next()
+                    .map(|w| {
+                        w.parse::<usize>().map_err(|err| {
+                            format_err!("expected first argument to '{attr_name}' to be a number: {err}")
+                        })
+                    })
+                    .ok_or_else(|| format_err!("expected at least one argument for '{attr_name}'"))??;
+
+                let ellipsis = match args_iter.next().map(|a| a.to_lowercase()) {
+                    None => false,
+                    Some(arg) if arg == "ellipsis" => true,
+                    Some(arg) => {
+                        return Err(format_err!(
+                            "expected second argument to '{attr_name}' to be 'ellipsis': {arg}"
+                        ))
+                    }
+                };
+                let align = match attr_name.as_str() {
+                    "cfit" => Alignment::Center,
+                    "rfit" => Alignment::Right,
+                    _ => Alignment::Left,
+                };
+                AttributeKind::Fit { width, align, ellipsis }
+            }
+            "repeat" => {
+                let count = args_iter
+                    .next()
+                    .map(|c| {
+                        c.parse::<usize>().map_err(|err| {
+                            format_err!("expected first argument to '{attr_name}' to be a number: {err}")
+                        })
+                    })
+                    .ok_or_else(|| format_err!("expected at least one argument for '{attr_name}'"))??;
+                AttributeKind::Repeat { count }
+            }
+            "wrap" => {
+                let width = args_iter
+                    .next()
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .filter(|w| *w > 0)
+                    .ok_or_else(|| format_err!("expected 'wrap' to have one argument: a positive width"))?;
+                AttributeKind::Wrap { width }
+            }
+            "indent" => {
+                let spaces = args_iter
+                    .next()
+                    .map(|n| {
+                        n.parse::<usize>()
+                            .map_err(|err| format_err!("expected argument to 'indent' to be a number of spaces: {err}"))
+                    })
+                    .ok_or_else(|| format_err!("expected 'indent' to have one argument: the number of spaces"))??;
+                AttributeKind::Indent { spaces }
+            }
+            "bar" => {
+                let (Some(width), Some(max)) = (args_iter.next(), args_iter.next()) else {
+                    return Err(format_err!(
+                        "expected 'bar' to have two arguments: the width and the maximum value"
+                    ));
+                };
+                let width = width
+                    .parse::<usize>()
+                    .map_err(|err| format_err!("expected first argument to 'bar' to be a number: {err}"))?;
+                let max = max
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|max| max.is_finite() && *max > 0.0)
+                    .ok_or_else(|| format_err!("expected second argument to 'bar' to be a positive number: {max}"))?;
+
+                AttributeKind::Bar { width, max }
+            }
+            "hash" => {
+                let len = match args_iter.next() {
+                    Some(len) => len
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|len| (1..=HASH_MAX_LEN).contains(len))
+                        .ok_or_else(|| {
+                            format_err!("expected argument to 'hash' to be a number from 1 to {HASH_MAX_LEN}: {len}")
+                        })?,
+                    None => HASH_DEFAULT_LEN,
+                };
+                AttributeKind::Hash { len }
+            }
+            "mask" => {
+                let (Some(keep_start), Some(keep_end)) = (args_iter.next(), args_iter.next()) else {
+                    return Err(format_err!(
+                        "expected 'mask' to have at least two arguments: the number of characters to keep at the start and at the end"
+                    ));
+                };
+                let keep_start = keep_start
+                    .parse::<usize>()
+                    .map_err(|err| format_err!("expected first argument to 'mask' to be a number: {err}"))?;
+                let keep_end = keep_end
+                    .parse::<usize>()
+                    .map_err(|err| format_err!("expected second argument to 'mask' to be a number: {err}"))?;
+                let ch = match args_iter.next() {
+                    Some(ch) => {
+                        let mut chars = ch.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => c,
+                            _ => {
+                                return Err(format_err!(
+                                    "expected third argument to 'mask' to be a single character: {ch}"
+                                ))
+                            }
+                        }
+                    }
+                    None => MASK_DEFAULT,
+                };
+                AttributeKind::Mask {
+                    keep_start,
+                    keep_end,
+                    ch,
+                }
+            }
+            "date" => {
+                let (Some(input_fmt), Some(output_fmt)) = (args_iter.next(), args_iter.next()) else {
+                    return Err(format_err!(
+                        "expected 'date' to have two arguments: the input format and the output format"
+                    ));
+                };
+                AttributeKind::Date {
+                    input_fmt: input_fmt.to_string(),
+                    output_fmt: output_fmt.to_string(),
+                }
+            }
+            "num" => {
+                let group = match args_iter.next() {
+                    Some(group) => {
+                        let mut chars = group.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(c), None) => c,
+                            _ => {
+                                return Err(format_err!(
+                                    "expected first argument to 'num' to be a single character: {group}"
+                                ))
+                            }
+                        }
+                    }
+                    None => ',',
+                };
+                let precision = args_iter
+                    .next()
+                    .map(|p| {
+                        p.parse::<usize>()
+                            .map_err(|err| format_err!("expected second argument to 'num' to be a number: {err}"))
+                    })
+                    .transpose()?;
+
+                AttributeKind::Num { group, precision }
+            }
+            "bytesize" => {
+                let binary = match args_iter.next().map(|a| a.to_lowercase()) {
+                    None => true,
+                    Some(units) if units == "binary" => true,
+                    Some(units) if units == "si" => false,
+                    Some(units) => {
+                        return Err(format_err!(
+                            "expected argument to 'bytesize' to be either 'si' or 'binary': {units}"
+                        ))
+                    }
+                };
+                AttributeKind::ByteSize { binary }
+            }
+            "signed" => AttributeKind::Signed,
             _ => return Err(format_err!("unrecognized attribute '{val}'")),
         };
 
-        Ok(Self { kind, must_match })
+        Ok(Self {
+            kind,
+            must_match,
+            must_match_env,
+            chained,
+        })
+    }
+
+    /// Whether the attribute applies to `txt`, which is always the case for attributes that
+    /// aren't conditional.
+    fn is_applicable(&self, txt: &str) -> bool {
+        if let Some(re) = self.must_match.as_ref() {
+            return re.is_match(txt);
+        }
+        if let Some(pattern) = self.must_match_env.as_ref() {
+            return pattern.regex().is_some_and(|re| re.is_match(txt));
+        }
+        true
     }
 
     /// Applies select attributes to a given text data.
+    #[cfg(test)]
     pub fn apply(txt: &str, attributes: &[Self]) -> String {
-        let mut val = txt.to_string().stylize();
-        for attribute in attributes {
-            if let Some(re) = attribute.must_match.as_ref() {
-                if !re.is_match(txt) {
-                    continue;
+        Self::apply_with_color(txt, attributes, true)
+    }
+
+    /// Applies select attributes to a given text data. If `color` is `false` then style attributes are
+    /// skipped entirely while attributes that transform the text still apply. Conditional style
+    /// attributes are still evaluated so that they affect the attributes chained to them the same
+    /// way regardless of `color`.
+    ///
+    /// Whether each attribute applies, including which attribute of a chain applies, is decided
+    /// in the order the attributes are written whereas the attributes are applied phase by phase.
+    /// See [Phase].
+    pub fn apply_with_color(txt: &str, attributes: &[Self], color: bool) -> String {
+        let applicable = Self::applicable(txt, attributes);
+        let applicable = attributes
+            .iter()
+            .zip(&applicable)
+            .filter_map(|(attribute, applicable)| applicable.then_some(attribute))
+            .collect::<Vec<_>>();
+
+        let apply_phase = |mut val: StyledContent<String>, phase: Phase| {
+            if !color && phase == Phase::Style {
+                return val;
+            }
+            for attribute in applicable.iter().filter(|attribute| attribute.kind.phase() == phase) {
+                val = attribute.apply_one(val);
+            }
+            val
+        };
+        let val = apply_phase(txt.to_string().stylize(), Phase::Transform);
+
+        // The gradient is applied once everything else has been so that each character carries the
+        // styles of the entire text.
+        let gradient = applicable.iter().rev().find_map(|attribute| match attribute.kind {
+            AttributeKind::Gradient { start, end } if color => Some((start, end)),
+            _ => None,
+        });
+        let render = |val: StyledContent<String>| match gradient {
+            Some((start, end)) => apply_gradient(&val, start, end),
+            None => val.to_string(),
+        };
+
+        let indent = applicable
+            .iter()
+            .filter_map(|attribute| match attribute.kind {
+                AttributeKind::Indent { spaces } => Some(spaces),
+                _ => None,
+            })
+            .sum::<usize>();
+
+        if indent == 0 {
+            return render(apply_phase(apply_phase(val, Phase::Width), Phase::Style));
+        }
+
+        // Each line is aligned and styled on its own so that every line carries its own
+        // ANSI-escape sequences and the indentation isn't styled along with the text.
+        let style = *val.style();
+        let lines = val.content().split('\n').map(|line| {
+            if line.is_empty() {
+                return String::new();
+            }
+            let line = StyledContent::new(style, line.to_string());
+            let line = render(apply_phase(apply_phase(line, Phase::Width), Phase::Style));
+            format!("{:indent$}{line}", "")
+        });
+        lines.collect::<Vec<_>>().join("\n")
+    }
+
+    /// Whether each of `attributes` applies to `txt` in the order they are written. An attribute
+    /// chained with `??` doesn't apply if a conditional attribute earlier in its chain already did.
+    fn applicable(txt: &str, attributes: &[Self]) -> Vec<bool> {
+        // Whether a conditional attribute in the current chain has already been applied.
+        let mut chain_matched = false;
+
+        attributes
+            .iter()
+            .map(|attribute| {
+                if attribute.chained && chain_matched {
+                    return false;
                 }
+                let applicable = attribute.is_applicable(txt);
+
+                if attribute.must_match.is_some() || attribute.must_match_env.is_some() {
+                    chain_matched = applicable;
+                }
+                applicable
+            })
+            .collect()
+    }
+
+    /// Applies this attribute alone to `val`.
+    fn apply_one(&self, val: StyledContent<String>) -> StyledContent<String> {
+        match &self.kind {
+            AttributeKind::Black => val.black(),
+            AttributeKind::Red => val.red(),
+            AttributeKind::Green => val.green(),
+            AttributeKind::Yellow => val.yellow(),
+            AttributeKind::Blue => val.blue(),
+            AttributeKind::Magenta => val.magenta(),
+            AttributeKind::Cyan => val.cyan(),
+            AttributeKind::White => val.white(),
+            AttributeKind::Bold => val.bold(),
+            AttributeKind::Underlined => val.underlined(),
+            AttributeKind::Reverse => val.reverse(),
+            AttributeKind::CrossedOut => val.crossed_out(),
+            AttributeKind::Color {
+                color,
+                background: false,
+            } => val.with(*color),
+            AttributeKind::Color {
+                color,
+                background: true,
+            } => val.on(*color),
+            AttributeKind::BgBlack => val.on_black(),
+            AttributeKind::BgRed => val.on_red(),
+            AttributeKind::BgGreen => val.on_green(),
+            AttributeKind::BgYellow => val.on_yellow(),
+            AttributeKind::BgBlue => val.on_blue(),
+            AttributeKind::BgMagenta => val.on_magenta(),
+            AttributeKind::BgCyan => val.on_cyan(),
+            AttributeKind::BgWhite => val.on_white(),
+            AttributeKind::Date { input_fmt, output_fmt } => map_content(val, |content| {
+                let datetime = NaiveDateTime::parse_from_str(content, input_fmt).ok()?;
+                let mut out = String::new();
+                write!(out, "{}", datetime.format(output_fmt)).ok()?;
+                Some(out)
+            }),
+            AttributeKind::Num { group, precision } => {
+                map_content(val, |content| format_num(content, *group, *precision))
             }
-            val = match attribute.kind {
-                AttributeKind::Black => val.black(),
-                AttributeKind::Red => val.red(),
-                AttributeKind::Green => val.green(),
-                AttributeKind::Yellow => val.yellow(),
-                AttributeKind::Blue => val.blue(),
-                AttributeKind::Magenta => val.magenta(),
-                AttributeKind::Cyan => val.cyan(),
-                AttributeKind::White => val.white(),
-                AttributeKind::Bold => val.bold(),
-                AttributeKind::Underlined => val.underlined(),
-                AttributeKind::Reverse => val.reverse(),
-                AttributeKind::CrossedOut => val.crossed_out(),
-                AttributeKind::BgBlack => val.on_black(),
-                AttributeKind::BgRed => val.on_red(),
-                AttributeKind::BgGreen => val.on_green(),
-                AttributeKind::BgYellow => val.on_yellow(),
-                AttributeKind::BgBlue => val.on_blue(),
-                AttributeKind::BgMagenta => val.on_magenta(),
-                AttributeKind::BgCyan => val.on_cyan(),
-                AttributeKind::BgWhite => val.on_white(),
-                AttributeKind::Align { direction, width } => match direction {
-                    Alignment::Left => {
-                        let val = val.to_string();
-                        format!("{val:<width$}", width = width).stylize()
-                    }
-                    Alignment::Right => {
-                        let val = val.to_string();
-                        format!("{val:>width$}", width = width).stylize()
-                    }
-                    Alignment::Center => {
-                        let val = val.to_string();
-                        format!("{val:^width$}", width = width).stylize()
-                    }
-                },
-            };
+            AttributeKind::ByteSize { binary } => map_content(val, |content| format_bytesize(content, *binary)),
+            AttributeKind::Signed => map_content(val, format_signed),
+            AttributeKind::Repeat { count } => map_content(val, |content| Some(content.repeat(*count))),
+            AttributeKind::Wrap { width } => map_content(val, |content| Some(wrap(content, *width))),
+            // Applied line by line in [Attribute::apply_with_color].
+            AttributeKind::Indent { .. } => val,
+            // Applied character by character in [Attribute::apply_with_color].
+            AttributeKind::Gradient { .. } => val,
+            AttributeKind::Bar { width, max } => map_content(val, |content| Some(bar(content, *width, *max))),
+            AttributeKind::Hash { len } => map_content(val, |content| Some(hash(content, *len))),
+            AttributeKind::Mask {
+                keep_start,
+                keep_end,
+                ch,
+            } => map_content(val, |content| Some(mask(content, *keep_start, *keep_end, *ch))),
+            AttributeKind::Fit { width, align, ellipsis } => {
+                map_content(val, |content| Some(fit(content, *width, *align, *ellipsis)))
+            }
+            AttributeKind::Align { direction, width } => match direction {
+                Alignment::Left => {
+                    let val = val.to_string();
+                    format!("{val:<width$}", width = *width).stylize()
+                }
+                Alignment::Right => {
+                    let val = val.to_string();
+                    format!("{val:>width$}", width = *width).stylize()
+                }
+                Alignment::Center => {
+                    let val = val.to_string();
+                    format!("{val:^width$}", width = *width).stylize()
+                }
+            },
+        }
+    }
+}
+
+/// Replaces the content of `val` with the output of `f` while retaining its style. If `f` returns
+/// `None` then `val` is returned unchanged.
+fn map_content<F>(val: StyledContent<String>, f: F) -> StyledContent<String>
+where
+    F: FnOnce(&str) -> Option<String>,
+{
+    match f(val.content()) {
+        Some(content) => StyledContent::new(*val.style(), content),
+        None => val,
+    }
+}
+
+/// Parses a hex color of the form `#rrggbb` or the shorthand `#rgb` where the `#` is optional.
+fn parse_hex(hex: &str) -> Option<Color> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |i: usize, len: usize| u8::from_str_radix(&digits[i * len..(i + 1) * len], 16).ok();
+
+    let (r, g, b) = match digits.len() {
+        6 => (channel(0, 2)?, channel(1, 2)?, channel(2, 2)?),
+        // Each digit of the shorthand is repeated, e.g. 'f' is 'ff'.
+        3 => (channel(0, 1)? * 17, channel(1, 1)? * 17, channel(2, 1)? * 17),
+        _ => return None,
+    };
+    Some(Color::Rgb { r, g, b })
+}
+
+/// Renders each character of `val` with its style and a foreground color that is interpolated
+/// linearly from `start` at the first character to `end` at the last.
+fn apply_gradient(val: &StyledContent<String>, start: (u8, u8, u8), end: (u8, u8, u8)) -> String {
+    let content = val.content();
+    let steps = content.chars().count().saturating_sub(1).max(1) as f64;
+    let channel = |from: u8, to: u8, t: f64| (f64::from(from) + (f64::from(to) - f64::from(from)) * t).round() as u8;
+
+    let mut out = String::with_capacity(content.len() * 20);
+
+    for (i, c) in content.chars().enumerate() {
+        let t = i as f64 / steps;
+        let mut style = *val.style();
+        style.foreground_color = Some(Color::Rgb {
+            r: channel(start.0, end.0, t),
+            g: channel(start.1, end.1, t),
+            b: channel(start.2, end.2, t),
+        });
+        out.push_str(&StyledContent::new(style, c).to_string());
+    }
+    out
+}
+
+/// The character that ends text truncated by `fit` when its ellipsis is enabled.
+const ELLIPSIS: char = '…';
+
+/// Pads or truncates `content` to exactly `width` columns, aligned according to `align`. If
+/// `ellipsis` then truncated text ends with [ELLIPSIS].
+fn fit(content: &str, width: usize, align: Alignment, ellipsis: bool) -> String {
+    let content_width = str_width(content);
+
+    let (mut out, out_width) = if content_width <= width {
+        (content.to_string(), content_width)
+    } else {
+        let ellipsis = ellipsis && width > 0;
+        let budget = if ellipsis { width - 1 } else { width };
+
+        let mut out = String::with_capacity(content.len());
+        let mut out_width = 0;
+
+        for c in content.chars() {
+            let w = char_width(c);
+            if out_width + w > budget {
+                break;
+            }
+            out.push(c);
+            out_width += w;
+        }
+        if ellipsis {
+            out.push(ELLIPSIS);
+            out_width += 1;
+        }
+        (out, out_width)
+    };
+
+    // A wide character that doesn't fit may leave the truncated text short of the width.
+    let padding = width.saturating_sub(out_width);
+    let (left, right) = match align {
+        Alignment::Left => (0, padding),
+        Alignment::Right => (padding, 0),
+        Alignment::Center => (padding / 2, padding - padding / 2),
+    };
+    out.insert_str(0, &" ".repeat(left));
+    out.push_str(&" ".repeat(right));
+    out
+}
+
+/// Wraps each line of `content` at `width` columns, breaking at spaces where possible and within
+/// words that don't fit on a line of their own otherwise. See [AttributeKind::Wrap].
+fn wrap(content: &str, width: usize) -> String {
+    let mut out = String::with_capacity(content.len());
+
+    for (i, line) in content.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        // The width of the line being written.
+        let mut col = 0;
+
+        for (j, word) in line.split(' ').enumerate() {
+            if j > 0 {
+                if col > 0 && col + 1 + str_width(word) > width {
+                    out.push('\n');
+                    col = 0;
+                } else {
+                    out.push(' ');
+                    col += 1;
+                }
+            }
+            for c in word.chars() {
+                let w = char_width(c);
+                if col > 0 && col + w > width {
+                    out.push('\n');
+                    col = 0;
+                }
+                out.push(c);
+                col += w;
+            }
+        }
+    }
+    out
+}
+
+/// The number of terminal columns that `txt` occupies.
+fn str_width(txt: &str) -> usize {
+    txt.chars().map(char_width).sum()
+}
+
+/// Like [str_width] but ANSI-escape sequences, such as those of styles, don't occupy any columns.
+pub fn display_width(txt: &str) -> usize {
+    let mut width = 0;
+    let mut chars = txt.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += char_width(c);
+            continue;
+        }
+        // Control sequences such as '\x1b[1;31m' end with a character in the range '@' to '~'.
+        if chars.next() == Some('[') {
+            chars.by_ref().find(|c| ('@'..='~').contains(c));
+        }
+    }
+    width
+}
+
+/// The number of terminal columns that `c` occupies: zero for combining and zero-width
+/// characters, two for East Asian wide and fullwidth characters as well as most emoji, and one
+/// otherwise.
+fn char_width(c: char) -> usize {
+    match u32::from(c) {
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Formats `content` as a number with `precision` decimal places, or as many as needed if `None`,
+/// and with `group` inserted between every three integer digits. Returns `None` if `content`
+/// isn't a finite number.
+fn format_num(content: &str, group: char, precision: Option<usize>) -> Option<String> {
+    let num = content.parse::<f64>().ok().filter(|n| n.is_finite())?;
+
+    let is_integer = content
+        .strip_prefix(['-', '+'])
+        .unwrap_or(content)
+        .bytes()
+        .all(|b| b.is_ascii_digit());
+
+    // Integers are formatted from their digits directly to avoid losing precision for values
+    // that can't be represented exactly as a float.
+    let formatted = match precision {
+        None if is_integer => content.strip_prefix('+').unwrap_or(content).to_string(),
+        None => format!("{num}"),
+        Some(precision) => format!("{num:.precision$}"),
+    };
+
+    let (sign, unsigned) = match formatted.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", formatted.as_str()),
+    };
+    let (integer, fraction) = unsigned.split_at(unsigned.find('.').unwrap_or(unsigned.len()));
+
+    let mut out = String::with_capacity(formatted.len() + integer.len() / 3);
+    out.push_str(sign);
+
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            out.push(group);
+        }
+        out.push(digit);
+    }
+    out.push_str(fraction);
+
+    Some(out)
+}
+
+/// Formats `content` as a byte count using binary units, i.e. powers of 1024, if `binary` or SI
+/// units otherwise. Returns `None` if `content` isn't a non-negative number.
+fn format_bytesize(content: &str, binary: bool) -> Option<String> {
+    const BINARY_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+    const SI_UNITS: [&str; 7] = ["B", "kB", "MB", "GB", "TB", "PB", "EB"];
+
+    let mut size = content.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0)?;
+
+    let (base, units) = if binary {
+        (1024.0, BINARY_UNITS)
+    } else {
+        (1000.0, SI_UNITS)
+    };
+
+    let mut unit = 0;
+    while size >= base && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        return Some(format!("{size} {}", units[unit]));
+    }
+    Some(format!("{size:.1} {}", units[unit]))
+}
+
+/// Prefixes `content` with `+` unless it's a negative number, in which case it's left as is, so
+/// that zero is `+0`. Digits are kept as written. Returns `None` if `content` isn't a finite number.
+fn format_signed(content: &str) -> Option<String> {
+    let num = content.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    let unsigned = content.strip_prefix(['-', '+']).unwrap_or(content);
+
+    if num < 0.0 {
+        return Some(format!("-{unsigned}"));
+    }
+    Some(format!("+{unsigned}"))
+}
+
+/// The character that fills the portion of a `bar` that corresponds to its value.
+const BAR_FILLED: char = '█';
+
+/// The character that makes up the remainder of a `bar`.
+const BAR_EMPTY: char = '░';
+
+/// Renders `content` as a bar `width` columns wide whose first columns are [BAR_FILLED] in
+/// proportion to `content` relative to `max` and the rest are [BAR_EMPTY]. The bar is empty if
+/// `content` isn't a number.
+fn bar(content: &str, width: usize, max: f64) -> String {
+    let ratio = content
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|n| !n.is_nan())
+        .map_or(0.0, |n| (n / max).clamp(0.0, 1.0));
+
+    let filled = (ratio * width as f64).round() as usize;
+    let mut out = String::with_capacity(width * BAR_FILLED.len_utf8());
+    out.extend(std::iter::repeat_n(BAR_FILLED, filled));
+    out.extend(std::iter::repeat_n(BAR_EMPTY, width - filled));
+    out
+}
+
+/// The number of hex characters of the digest that `hash` keeps by default.
+const HASH_DEFAULT_LEN: usize = 8;
+
+/// The number of hex characters in a SHA-256 digest.
+const HASH_MAX_LEN: usize = 64;
+
+/// The first `len` hex characters of the SHA-256 digest of `content`.
+fn hash(content: &str, len: usize) -> String {
+    let mut out = String::with_capacity(HASH_MAX_LEN);
+    for byte in Sha256::digest(content.as_bytes()) {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out.truncate(len);
+    out
+}
+
+/// The character that `mask` replaces characters with by default.
+const MASK_DEFAULT: char = '*';
+
+/// Replaces every character of `content` with `ch` except for the first `keep_start` and the last
+/// `keep_end`. If `content` has no more characters than those to keep then every character is
+/// replaced as nothing would be masked otherwise.
+fn mask(content: &str, keep_start: usize, keep_end: usize, ch: char) -> String {
+    let len = content.chars().count();
+
+    if len <= keep_start.saturating_add(keep_end) {
+        return std::iter::repeat_n(ch, len).collect();
+    }
+    content
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i < keep_start || i >= len - keep_end { c } else { ch })
+        .collect()
+}
+
+/// Splits the raw arguments of an attribute on commas that aren't quoted. Surrounding whitespace
+/// and quotes are removed from each argument. Characters escaped by `escape` are kept as is,
+/// escape included if it's [ESCAPE] as regular expressions understand it in the same way. Any other
+/// escape is removed so that the argument reads the same as if [ESCAPE] were used.
+fn split_args(raw_args: &str, escape: char) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut open_quote: Option<char> = None;
+    let mut chars = raw_args.chars();
+
+    while let Some(c) = chars.next() {
+        if c == escape {
+            if escape == ESCAPE {
+                current.push(c);
+            }
+            current.extend(chars.next());
+            continue;
+        }
+        match open_quote {
+            Some(quote) if c == quote => open_quote = None,
+            None if c == LITERAL_SINGLE_QUOTE || c == LITERAL_DOUBLE_QUOTE => open_quote = Some(c),
+            None if c == PARAM_DELIMETER => {
+                args.push(unquote(&current));
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    args.push(unquote(&current));
+    args
+}
+
+/// Trims whitespace and a single pair of matching quotes surrounding `raw_arg`.
+fn unquote(raw_arg: &str) -> String {
+    let trimmed = raw_arg.trim();
+
+    for quote in [LITERAL_SINGLE_QUOTE, LITERAL_DOUBLE_QUOTE] {
+        if let Some(unquoted) = trimmed.strip_prefix(quote).and_then(|t| t.strip_suffix(quote)) {
+            return unquoted.to_string();
         }
-        val.to_string()
     }
+    trimmed.to_string()
 }
@@ -1,15 +1,15 @@
 use super::{
+    alias::{Aliases, ALIAS},
     error::ParseError,
     token::{
-        ANCHOR_CLOSE, ANCHOR_OPEN, ATTRIBUTE_CLOSE, ATTRIBUTE_DELIMETER, ATTRIBUTE_END, ATTRIBUTE_OPEN, DEFAULT_PIPE,
-        ESCAPE, INDEX_CLOSE, INDEX_OPEN, LITERAL_DOUBLE_QUOTE, LITERAL_SINGLE_QUOTE, PARAM_CLOSE, PARAM_OPEN, REQUIRED,
+        ANCHOR_CLOSE, ANCHOR_OPEN, ATTRIBUTE_CLOSE, ATTRIBUTE_DELIMETER, ATTRIBUTE_END, ATTRIBUTE_OPEN, COUNT,
+        DEFAULT_BLANK, DEFAULT_PIPE, ENV_VAR, ESCAPE, INDEX_ALL, INDEX_CLOSE, INDEX_OPEN, INDEX_RANGE, INDEX_SEPARATOR,
+        LITERAL_DOUBLE_QUOTE, LITERAL_SINGLE_QUOTE, PARAM_CLOSE, PARAM_OPEN, REQUIRED,
     },
+    OutputTemplate,
 };
 use anyhow::{format_err, Result};
-use std::{
-    cmp::Ordering,
-    fmt::{self, Debug},
-};
+use std::fmt::{self, Debug};
 
 /// Concerned with ANSI-escape sequences that can be applied to anchors.
 pub mod attr;
@@ -18,7 +18,7 @@ pub use attr::{Attribute, AttributeKind};
 /// Concerned with validating certain properties that are computed during parsing
 /// such as anchor name.
 pub mod rules;
-use rules::Rules;
+use rules::{Rules, ANCHOR_CHARSET, SPAN_PREFIX};
 
 #[cfg(test)]
 pub mod test;
@@ -26,11 +26,13 @@ pub mod test;
 /// An interpolation point with additional properties that affect how text is transformed.
 /// The `name` field should be identical with the regular expression capture group whose value
 /// will be used to replace the anchor. The `start` and `end` field mark the range in which the
-/// anchor appears in the original template string. The `index` determines which value amongst the
-/// captures will be used for interpolation (defaults to 0). The `defaults` field contains
+/// anchor appears in the original template string. The `index` determines which value(s) amongst
+/// the captures will be used for interpolation (defaults to the first). The `defaults` field contains
 /// fallbacks in case an anchor doesn't have an associated match. The first non-blank value amongst
 /// the defaults will be used for interpolation. The `attributes` fields applies ANSI-escape
-/// sequences to the interpolated value.
+/// sequences to the interpolated value. If `count_of` is set then the number of matches of the
+/// capture is interpolated rather than any of its values. If `default_on_blank` is set then a
+/// blank value is treated as though there were no match so that the defaults are used instead.
 ///
 /// If `name` is empty then default is expected to contain a single literal value.
 #[derive(Debug, Default, Clone)]
@@ -38,31 +40,91 @@ pub struct Anchor {
     pub name: String,
     pub start: usize,
     pub end: usize,
-    pub index: Option<usize>,
+    pub index: Option<Index>,
     pub defaults: Vec<DefaultValue>,
     pub attributes: Vec<Attribute>,
     pub required: bool,
+    pub count_of: bool,
+    pub default_on_blank: bool,
 }
 
 /// State that is maintained during parsing. The `cursor` is the index of the current token
 /// that we are on amongst `tokens`. The `mode` field determines which phase we are in during
 /// parsing. The `bound_anchor` is the anchor that we are currently working on.
-struct ParseState {
+struct ParseState<'a> {
     cursor: usize,
     tokens: Vec<char>,
     mode: ParseStateMode,
     bound_anchor: Option<Anchor>,
+    /// How many default string literals the template being parsed is nested within.
+    nesting: usize,
+    /// Whether the attributes being parsed belong to a default value rather than the anchor.
+    in_default: bool,
+    /// Attributes of the default value that is being parsed, if it has any of its own.
+    default_attributes: Option<Vec<Attribute>>,
+    /// Named lists of attributes that may be referenced amongst attributes and the escape character.
+    options: ParseOptions<'a>,
     /// For debugging purposes only
     recursion_depth: usize,
 }
 
+/// Options that affect how a template is parsed.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions<'a> {
+    /// Named lists of attributes that may be referenced amongst attributes.
+    pub aliases: &'a Aliases,
+    /// The character that causes the character following it to be treated as a non-special
+    /// character, [ESCAPE] by default.
+    pub escape: char,
+    /// The character that begins an anchor, [ANCHOR_OPEN] by default.
+    pub anchor_open: char,
+    /// The character that ends an anchor, [ANCHOR_CLOSE] by default.
+    pub anchor_close: char,
+    /// The characters allowed in anchor names, [ANCHOR_CHARSET] by default.
+    pub anchor_charset: &'a str,
+}
+
+impl<'a> ParseOptions<'a> {
+    pub fn new(aliases: &'a Aliases) -> Self {
+        Self {
+            aliases,
+            escape: ESCAPE,
+            anchor_open: ANCHOR_OPEN,
+            anchor_close: ANCHOR_CLOSE,
+            anchor_charset: ANCHOR_CHARSET,
+        }
+    }
+
+    /// Use `escape` rather than [ESCAPE] as the escape character.
+    pub fn with_escape(mut self, escape: char) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Use `open` and `close` rather than [ANCHOR_OPEN] and [ANCHOR_CLOSE] to delimit anchors, in
+    /// which case a literal `open` or `close` is written by doubling it. The two may be the same
+    /// character, e.g. `%lvl%`.
+    pub fn with_anchor_delimiters(mut self, open: char, close: char) -> Self {
+        self.anchor_open = open;
+        self.anchor_close = close;
+        self
+    }
+
+    /// Allow the characters of `charset` rather than [ANCHOR_CHARSET] in anchor names. The charset is
+    /// written as the contents of a regular expression bracket expression, e.g. `a-z_/`.
+    pub fn with_anchor_charset(mut self, charset: &'a str) -> Self {
+        self.anchor_charset = charset;
+        self
+    }
+}
+
 /// Determines which mode of parsing we are in.
 #[derive(Debug)]
 enum ParseStateMode {
     /// Walk through regular characters.
     Base,
-    /// Encountered an escape character which will cause the next token to be treated as a
-    /// non-special character.
+    /// Encountered an escape character or a doubled brace which will cause the next token to be
+    /// treated as a non-special character.
     Escaping,
     /// Encountered `{` which begins the anchor.
     AnchorBegin,
@@ -74,7 +136,7 @@ enum ParseStateMode {
     AnchorParseDefaultValue,
     /// Encountered a '"' or a '\'' opening quote which indicates a default literal value.
     AnchorParseDefaultLiteral,
-    /// User is using an anchor as a default value.
+    /// User is using an anchor or an environment variable as a default value.
     AnchorParseDefaultAnchor,
     /// Encountered a '(' while parsing an achor indicating attribute usage
     AttributeParse,
@@ -83,23 +145,153 @@ enum ParseStateMode {
 #[derive(Debug, Clone)]
 pub enum DefaultValue {
     Literal(String),
+    /// A string literal that contains anchors of its own, e.g. `"HTTP {code}"`, which are
+    /// interpolated using the same captures. The original literal is kept in `literal`.
+    Interpolated {
+        literal: String,
+        template: Box<OutputTemplate>,
+    },
     /// Unlike a regular anchor, this one is unconcerned about position
     Anchor {
         name: String,
-        index: Option<usize>,
+        index: Option<Index>,
     },
+    /// The value of an environment variable, e.g. `$HOSTNAME`. Unset or blank variables are
+    /// treated as not having a value.
+    Env(String),
+    /// A default value with attributes of its own, e.g. `(red):"DOWN"`, which are applied in place
+    /// of the attributes of the anchor.
+    Attributed {
+        value: Box<DefaultValue>,
+        attributes: Vec<Attribute>,
+    },
+}
+
+impl DefaultValue {
+    /// Wraps `self` with its own `attributes` if there are any.
+    fn with_attributes(self, attributes: Option<Vec<Attribute>>) -> Self {
+        match attributes {
+            Some(attributes) => Self::Attributed {
+                value: Box::new(self),
+                attributes,
+            },
+            None => self,
+        }
+    }
+}
+
+/// Separator used to join all values of a capture when `[*]` is used without an explicit separator.
+pub const DEFAULT_JOIN_SEPARATOR: &str = ",";
+
+/// Determines which of the values captured for a given name are used for interpolation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Index {
+    /// The value at the given position, e.g. `[1]`. Negative positions count backwards from the
+    /// last value, e.g. `[-1]`.
+    One(isize),
+    /// All values joined by the separator, e.g. `[*]` or `[*:", "]`.
+    All(String),
+    /// Values within the half-open range joined by the separator, e.g. `[1..3]`, `[1..]`,
+    /// `[..3]`, or `[1..3:" "]`. Bounds may be negative and saturate rather than fall out of range.
+    Range {
+        start: Option<isize>,
+        end: Option<isize>,
+        separator: String,
+    },
+}
+
+impl Index {
+    /// Resolves a possibly negative `position` into an offset amongst `len` values. Returns `None`
+    /// if a negative `position` reaches beyond the first value.
+    pub fn resolve(position: isize, len: usize) -> Option<usize> {
+        if position >= 0 {
+            return Some(position.unsigned_abs());
+        }
+        len.checked_sub(position.unsigned_abs())
+    }
+
+    /// Resolves the optional bounds of a range into offsets amongst `len` values, saturating bounds
+    /// that fall out of range.
+    pub fn resolve_range(start: Option<isize>, end: Option<isize>, len: usize) -> (usize, usize) {
+        let saturate = |position: isize| Self::resolve(position, len).unwrap_or_default().min(len);
+        let start = start.map_or(0, saturate);
+        let end = end.map_or(len, saturate);
+        (start, end.max(start))
+    }
+
+    /// Parses the contents found between the `[` and `]` of an index operation.
+    fn parse(raw: &str) -> Option<Self> {
+        let (bounds, separator) = match raw.split_once(INDEX_SEPARATOR) {
+            Some((bounds, separator)) => (bounds, Some(separator)),
+            None => (raw, None),
+        };
+
+        if bounds.strip_prefix(INDEX_ALL).is_some_and(str::is_empty) {
+            return Self::parse_separator(separator).map(Self::All);
+        }
+
+        if let Some((start, end)) = bounds.split_once(INDEX_RANGE) {
+            let start = if start.is_empty() {
+                None
+            } else {
+                Some(start.parse::<isize>().ok()?)
+            };
+            let end = if end.is_empty() {
+                None
+            } else {
+                Some(end.parse::<isize>().ok()?)
+            };
+            let separator = Self::parse_separator(separator)?;
+            return Some(Self::Range { start, end, separator });
+        }
+
+        if separator.is_some() {
+            return None;
+        }
+        bounds.parse::<isize>().ok().map(Self::One)
+    }
+
+    /// Parses the optional quoted separator that follows the `:` of an index operation.
+    fn parse_separator(separator: Option<&str>) -> Option<String> {
+        let Some(separator) = separator else {
+            return Some(DEFAULT_JOIN_SEPARATOR.to_string());
+        };
+        let mut chars = separator.trim().chars();
+        let quote = chars
+            .next()
+            .filter(|c| *c == LITERAL_DOUBLE_QUOTE || *c == LITERAL_SINGLE_QUOTE)?;
+        chars.as_str().strip_suffix(quote).map(String::from)
+    }
 }
 
+/// The maximum number of default string literals that anchors can be nested within, e.g. `{c}` in
+/// `{a || "{b || '{c}'}"}` is nested twice.
+pub const MAX_DEFAULT_NESTING: usize = 2;
+
 /// Parses the user-sourced template string.
 pub(super) fn parse(template: &str) -> Result<Vec<Anchor>> {
+    parse_with_options(template, ParseOptions::new(&Aliases::default()))
+}
+
+/// Parses the user-sourced template string according to `options`.
+pub(super) fn parse_with_options(template: &str, options: ParseOptions<'_>) -> Result<Vec<Anchor>> {
+    parse_nested(template, 0, options)
+}
+
+/// Parses a template string that is nested within `nesting` default string literals.
+pub(super) fn parse_nested(template: &str, nesting: usize, options: ParseOptions<'_>) -> Result<Vec<Anchor>> {
     let mut mode = ParseState {
         cursor: 0,
         tokens: template.chars().collect(),
         mode: ParseStateMode::Base,
         bound_anchor: None,
+        nesting,
+        in_default: false,
+        default_attributes: None,
+        options,
         recursion_depth: 0,
     };
-    let rules = Rules::new();
+    let rules = Rules::new(options.anchor_charset)?;
     let mut anchors = Vec::new();
     parse_impl(&mut mode, &mut anchors, &rules)?;
 
@@ -107,7 +299,7 @@ pub(super) fn parse(template: &str) -> Result<Vec<Anchor>> {
 }
 
 /// Finite mode machine
-fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -> Result<()> {
+fn parse_impl(mode: &mut ParseState<'_>, anchors: &mut Vec<Anchor>, rules: &Rules) -> Result<()> {
     mode.recursion_depth += 1;
     log::debug!("{mode:?}");
 
@@ -119,10 +311,14 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 let Some(token) = mode.tokens.get(i).copied() else {
                     return Ok(());
                 };
-                if token == ESCAPE {
+                let next_token = mode.tokens.get(i + 1).copied();
+
+                let open = mode.options.anchor_open;
+
+                if token == mode.options.escape || (token == open && next_token == Some(open)) {
                     mode.mode = ParseStateMode::Escaping;
                     return parse_impl(mode, anchors, rules);
-                } else if token == ANCHOR_OPEN {
+                } else if token == open {
                     mode.mode = ParseStateMode::AnchorBegin;
                     mode.bound_anchor = Some(Anchor {
                         start: mode.cursor,
@@ -137,7 +333,7 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
         ParseStateMode::Escaping => {
             mode.cursor += 1;
             if mode.tokens.get(mode.cursor).is_none() {
-                return Err(ParseError::missing_escapee(mode.cursor - 1, &mode.tokens).into());
+                return Err(ParseError::missing_escapee(mode.cursor - 1, &mode.tokens, mode.options.escape).into());
             }
             mode.cursor += 1;
             mode.mode = ParseStateMode::Base;
@@ -180,9 +376,6 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
         }
 
         ParseStateMode::AnchorParseBase => {
-            let begin = mode.cursor;
-            let mut end = mode.cursor;
-
             let Some(anchor) = mode.bound_anchor.as_mut() else {
                 log::error!("expected mode.bound_anchor to be `Some` while in `AnchorParseBase`");
                 return Err(format_err!(
@@ -190,12 +383,28 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 ));
             };
 
+            // A '#' preceding the name counts the matches of the capture rather than using them.
+            if anchor.name.is_empty() && anchor.defaults.is_empty() && !anchor.count_of {
+                let first = (mode.cursor..mode.tokens.len()).find(|i| !mode.tokens[*i].is_ascii_whitespace());
+
+                if let Some(i) = first.filter(|i| mode.tokens[*i] == COUNT) {
+                    anchor.count_of = true;
+                    mode.cursor = i + 1;
+                }
+            }
+            let begin = mode.cursor;
+            let mut end = mode.cursor;
+
             for i in mode.cursor..mode.tokens.len() {
                 mode.cursor = i;
                 let Some(token) = mode.tokens.get(mode.cursor).copied() else {
-                    return Err(ParseError::unclosed_anchor(mode.cursor - 1, &mode.tokens).into());
+                    let close = mode.options.anchor_close;
+                    return Err(ParseError::unclosed_anchor(mode.cursor - 1, &mode.tokens, close).into());
                 };
                 if token == INDEX_OPEN {
+                    if anchor.count_of {
+                        return Err(ParseError::count_with_index_or_default(mode.cursor, &mode.tokens).into());
+                    }
                     for token in &mode.tokens[begin..end] {
                         if token.is_ascii_whitespace() {
                             continue;
@@ -209,15 +418,22 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                     }
                     mode.mode = ParseStateMode::AnchorParseIndex;
                     break;
-                } else if token == ANCHOR_CLOSE {
+                } else if token == mode.options.anchor_close {
                     for token in &mode.tokens[begin..end] {
                         if token.is_ascii_whitespace() {
                             continue;
                         }
                         anchor.name.push(*token)
                     }
-                    if (anchor.name.is_empty() || !rules.name_is_valid(&anchor.name)) && anchor.defaults.is_empty() {
-                        return Err(ParseError::invalid_anchor_name(mode.cursor - 1, &mode.tokens).into());
+                    let name_is_valid = rules.name_is_valid(&anchor.name)
+                        || rules.positional_is_valid(&anchor.name)
+                        || rules.namespaced_is_valid(&anchor.name)
+                        || rules.span_is_valid(&anchor.name);
+
+                    if (anchor.name.is_empty() || !name_is_valid) && anchor.defaults.is_empty() {
+                        return Err(
+                            ParseError::invalid_anchor_name(mode.cursor - 1, &mode.tokens, rules.charset()).into(),
+                        );
                     }
                     mode.mode = ParseStateMode::Base;
 
@@ -229,8 +445,10 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                     };
                     anchor.end = mode.cursor + 1;
                     anchors.push(anchor);
+                    // Resume past the closing delimiter which may also be the opening delimiter.
+                    mode.cursor += 1;
                     break;
-                } else if token == DEFAULT_PIPE {
+                } else if token == DEFAULT_PIPE || token == DEFAULT_BLANK {
                     for token in &mode.tokens[begin..end] {
                         if token.is_ascii_whitespace() {
                             continue;
@@ -238,26 +456,35 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                         anchor.name.push(*token)
                     }
                     mode.cursor += 1;
-                    let next_token_is_pipe = mode.tokens.get(mode.cursor).is_some_and(|token| *token == DEFAULT_PIPE);
+                    let next_token_matches = mode.tokens.get(mode.cursor).is_some_and(|next| *next == token);
 
-                    if anchor.name.is_empty() || !next_token_is_pipe {
+                    if anchor.name.is_empty() || !next_token_matches {
                         return Err(ParseError::invalid_default_value_operation(mode.cursor - 1, &mode.tokens).into());
                     }
+                    // '??' rather than '||' also falls back to the defaults on blank values.
+                    anchor.default_on_blank |= token == DEFAULT_BLANK;
                     mode.mode = ParseStateMode::AnchorParseDefaultValue;
                     break;
                 } else if token == LITERAL_DOUBLE_QUOTE || token == LITERAL_SINGLE_QUOTE {
+                    if anchor.count_of {
+                        return Err(ParseError::count_with_index_or_default(mode.cursor, &mode.tokens).into());
+                    }
                     mode.mode = ParseStateMode::AnchorParseDefaultLiteral;
                     break;
                 }
                 end += 1;
             }
+            // Ran out of tokens without encountering anything that ends the anchor name.
+            if matches!(mode.mode, ParseStateMode::AnchorParseBase) {
+                let close = mode.options.anchor_close;
+                return Err(ParseError::unclosed_anchor(mode.tokens.len() - 1, &mode.tokens, close).into());
+            }
             parse_impl(mode, anchors, rules)
         }
 
         ParseStateMode::AnchorParseIndex => {
             mode.cursor += 1;
             let begin = mode.cursor;
-            let mut end = begin;
 
             let Some(anchor) = mode.bound_anchor.as_mut() else {
                 log::error!("expected mode.bound_anchor to be `Some` during `AnchorParseIndex`");
@@ -265,19 +492,13 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                     "An unexpected error occurred while parsing template string."
                 ));
             };
-            for i in mode.cursor..mode.tokens.len() {
-                mode.cursor = i;
-                if mode.tokens.get(i).is_some_and(|token| *token == INDEX_CLOSE) {
-                    mode.cursor += 1;
-                    break;
-                }
-                end += 1;
-            }
-            let index = mode.tokens[begin..end]
-                .iter()
-                .collect::<String>()
-                .parse::<usize>()
-                .map_err(|_| ParseError::invalid_index(mode.cursor - 1, &mode.tokens))?;
+            let Some(end) = scan_index(&mode.tokens, begin) else {
+                return Err(ParseError::index_parsing_eol(mode.tokens.len() - 1, &mode.tokens).into());
+            };
+            mode.cursor = end + 1;
+
+            let index = Index::parse(&mode.tokens[begin..end].iter().collect::<String>())
+                .ok_or_else(|| ParseError::invalid_index(end, &mode.tokens))?;
             anchor.index = Some(index);
 
             mode.mode = ParseStateMode::AnchorParseBase;
@@ -288,6 +509,9 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
             if mode.bound_anchor.as_ref().is_some_and(|a| a.required) {
                 return Err(ParseError::default_disallowed_with_required(mode.cursor - 1, &mode.tokens).into());
             }
+            if mode.bound_anchor.as_ref().is_some_and(|a| a.count_of) {
+                return Err(ParseError::count_with_index_or_default(mode.cursor - 1, &mode.tokens).into());
+            }
 
             mode.cursor += 1;
             for i in mode.cursor..mode.tokens.len() {
@@ -298,11 +522,14 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 };
                 if token.is_ascii_whitespace() {
                     continue;
+                } else if token == ATTRIBUTE_OPEN && mode.default_attributes.is_none() {
+                    mode.in_default = true;
+                    mode.mode = ParseStateMode::AttributeParse;
+                    return parse_impl(mode, anchors, rules);
                 } else if token == LITERAL_SINGLE_QUOTE || token == LITERAL_DOUBLE_QUOTE {
-                    mode.cursor -= 1;
                     mode.mode = ParseStateMode::AnchorParseDefaultLiteral;
                     return parse_impl(mode, anchors, rules);
-                } else if rules.name_is_valid(&token.to_string()) {
+                } else if token == ENV_VAR || token == SPAN_PREFIX || rules.name_is_valid(&token.to_string()) {
                     mode.cursor -= 1;
                     mode.mode = ParseStateMode::AnchorParseDefaultAnchor;
                     return parse_impl(mode, anchors, rules);
@@ -335,33 +562,50 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
             mode.cursor += 1;
 
             let begin = mode.cursor;
-            let mut end = mode.cursor + 1;
-
-            for _ in mode.cursor..mode.tokens.len() {
-                mode.cursor += 1;
+            let mut closed = false;
 
-                let Some(token) = mode.tokens.get(mode.cursor).copied() else {
-                    break;
-                };
-                if token == ESCAPE {
-                    mode.cursor += 1;
+            while let Some(token) = mode.tokens.get(mode.cursor).copied() {
+                if token == mode.options.escape {
+                    mode.cursor += 2;
                     continue;
                 } else if token == opening_quote {
-                    let Some(bound_anchor) = mode.bound_anchor.as_mut() else {
-                        log::error!("expected mode.bound_anchor to be `Some` during `AnchorParseDefaultLiteral`");
-                        return Err(format_err!(
-                            "An unexpected error occurred while parsing template string."
-                        ));
-                    };
-                    let literal_value = mode.tokens[begin..end].iter().collect::<String>();
-                    bound_anchor.defaults.push(DefaultValue::Literal(literal_value));
-                    mode.mode = ParseStateMode::AnchorParseBase;
-                    mode.cursor += 1;
-                    return parse_impl(mode, anchors, rules);
+                    closed = true;
+                    break;
                 }
-                end += 1;
+                mode.cursor += 1;
             }
-            Err(ParseError::default_str_literal_missing_closing_quote(mode.cursor - 1, &mode.tokens).into())
+            if !closed {
+                return Err(
+                    ParseError::default_str_literal_missing_closing_quote(mode.tokens.len() - 1, &mode.tokens).into(),
+                );
+            }
+            let Some(bound_anchor) = mode.bound_anchor.as_mut() else {
+                log::error!("expected mode.bound_anchor to be `Some` during `AnchorParseDefaultLiteral`");
+                return Err(format_err!(
+                    "An unexpected error occurred while parsing template string."
+                ));
+            };
+            let raw = &mode.tokens[begin..mode.cursor];
+            let escape = mode.options.escape;
+
+            let default_val = if !raw.contains(&mode.options.anchor_open) {
+                DefaultValue::Literal(unescape_literal(raw, escape, &[opening_quote, escape]))
+            } else if mode.nesting < MAX_DEFAULT_NESTING {
+                // Only the quote is unescaped as the nested template handles any other escapes.
+                let literal_value = unescape_literal(raw, escape, &[opening_quote]);
+                let template = OutputTemplate::parse_nested(&literal_value, mode.nesting + 1, mode.options)?;
+                DefaultValue::Interpolated {
+                    literal: literal_value,
+                    template: Box::new(template),
+                }
+            } else {
+                return Err(ParseError::default_literal_too_nested(begin, &mode.tokens).into());
+            };
+            let attributes = mode.default_attributes.take();
+            bound_anchor.defaults.push(default_val.with_attributes(attributes));
+            mode.mode = ParseStateMode::AnchorParseBase;
+            mode.cursor += 1;
+            parse_impl(mode, anchors, rules)
         }
 
         ParseStateMode::AnchorParseDefaultAnchor => {
@@ -378,6 +622,10 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 }
                 break;
             }
+            let is_env_var = mode.tokens.get(mode.cursor).is_some_and(|token| *token == ENV_VAR);
+            if is_env_var {
+                mode.cursor += 1;
+            }
             let begin = mode.cursor;
             let mut end = begin + 1;
             let mut index = None;
@@ -388,10 +636,22 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 let Some(token) = mode.tokens.get(mode.cursor).copied() else {
                     break;
                 };
-                if index.is_some() || token.is_ascii_whitespace() || token == DEFAULT_PIPE || token == ANCHOR_CLOSE {
+                if index.is_some()
+                    || token.is_ascii_whitespace()
+                    || token == DEFAULT_PIPE
+                    || token == DEFAULT_BLANK
+                    || token == mode.options.anchor_close
+                {
                     let name: String = mode.tokens[begin..end].iter().collect();
-                    if !rules.name_is_valid(&name) {
-                        return Err(ParseError::invalid_anchor_name(mode.cursor - 1, &mode.tokens).into());
+                    let is_valid = if is_env_var {
+                        rules.env_var_is_valid(&name)
+                    } else {
+                        rules.name_is_valid(&name) || rules.namespaced_is_valid(&name) || rules.span_is_valid(&name)
+                    };
+                    if !is_valid {
+                        return Err(
+                            ParseError::invalid_anchor_name(mode.cursor - 1, &mode.tokens, rules.charset()).into(),
+                        );
                     }
                     let Some(anchor) = mode.bound_anchor.as_mut() else {
                         log::error!("expected mode.bound_anchor to be `Some` while in `AnchorParseDefaultAnchor`");
@@ -399,29 +659,34 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                             "An unexpected error occurred while parsing template string."
                         ));
                     };
-                    anchor.defaults.push(DefaultValue::Anchor { name, index });
+                    // Environment variable names can't begin with a digit, so a `$` followed by
+                    // digits refers to a capture group by its index instead.
+                    let default_val = if is_env_var && name.bytes().all(|b| b.is_ascii_digit()) {
+                        let name = format!("{ENV_VAR}{name}");
+                        DefaultValue::Anchor { name, index }
+                    } else if is_env_var {
+                        DefaultValue::Env(name)
+                    } else {
+                        DefaultValue::Anchor { name, index }
+                    };
+                    let attributes = mode.default_attributes.take();
+                    anchor.defaults.push(default_val.with_attributes(attributes));
                     mode.mode = ParseStateMode::AnchorParseBase;
                     return parse_impl(mode, anchors, rules);
                 } else if token == INDEX_OPEN {
-                    mode.cursor += 1;
-                    let index_begin = mode.cursor;
-                    let mut index_end = index_begin + 1;
+                    let is_positional = mode.tokens[begin..end].iter().all(char::is_ascii_digit);
 
-                    for _ in mode.cursor..mode.tokens.len() {
-                        mode.cursor += 1;
-                        let Some(token) = mode.tokens.get(mode.cursor).copied() else {
-                            return Err(ParseError::index_parsing_eol(mode.cursor - 1, &mode.tokens).into());
-                        };
-                        if token == INDEX_CLOSE {
-                            break;
-                        }
-                        index_end += 1;
+                    if is_env_var && !is_positional {
+                        return Err(ParseError::invalid_indexing_operation(mode.cursor, &mode.tokens).into());
                     }
-                    let parsed_index = mode.tokens[index_begin..index_end]
-                        .iter()
-                        .collect::<String>()
-                        .parse::<usize>()
-                        .map_err(|_| ParseError::invalid_index(mode.cursor - 1, &mode.tokens))?;
+                    let index_begin = mode.cursor + 1;
+                    let Some(index_end) = scan_index(&mode.tokens, index_begin) else {
+                        return Err(ParseError::index_parsing_eol(mode.tokens.len() - 1, &mode.tokens).into());
+                    };
+                    mode.cursor = index_end;
+
+                    let parsed_index = Index::parse(&mode.tokens[index_begin..index_end].iter().collect::<String>())
+                        .ok_or_else(|| ParseError::invalid_index(index_end, &mode.tokens))?;
                     index = Some(parsed_index);
                     continue;
                 }
@@ -435,7 +700,8 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
             let mut start = mode.cursor;
             let mut closed = false;
 
-            let mut raw_attrs: Vec<(String, Option<String>)> = Vec::new();
+            // Each attribute along with its parameters and where its name begins.
+            let mut raw_attrs: Vec<(String, Option<String>, usize)> = Vec::new();
 
             while mode.cursor < mode.tokens.len() {
                 mode.cursor += 1;
@@ -444,8 +710,10 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                 };
                 if token == ATTRIBUTE_CLOSE {
                     if mode.cursor - start > 1 {
-                        let attr = mode.tokens[start..mode.cursor].iter().collect::<String>();
-                        raw_attrs.push((attr, None));
+                        let (attr, attr_start) = attribute_name(&mode.tokens[start..mode.cursor], start);
+                        if !attr.is_empty() {
+                            raw_attrs.push((attr, None, attr_start));
+                        }
                     }
                     closed = true;
                     break;
@@ -454,10 +722,14 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                         start = mode.cursor + 1;
                         continue;
                     }
-                    let attr = mode.tokens[start..mode.cursor].iter().collect::<String>();
+                    let (attr, attr_start) = attribute_name(&mode.tokens[start..mode.cursor], start);
                     let mut args = None;
                     start = mode.cursor + 1;
 
+                    if attr.is_empty() {
+                        continue;
+                    }
+
                     if token == PARAM_OPEN {
                         mode.cursor += 1;
 
@@ -470,7 +742,7 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                             if token == PARAM_CLOSE && open_quote.is_none() {
                                 start = mode.cursor;
                                 break;
-                            } else if token == ESCAPE {
+                            } else if token == mode.options.escape {
                                 mode.cursor += 1
                             } else if token == LITERAL_SINGLE_QUOTE || token == LITERAL_DOUBLE_QUOTE {
                                 if let Some(quote) = open_quote {
@@ -487,12 +759,29 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
                                 }
                             }
                             mode.cursor += 1;
-                            params_end += 1;
+                            params_end = mode.cursor.min(mode.tokens.len());
                             current_token = mode.tokens.get(mode.cursor).copied();
                         }
                         args = Some(mode.tokens[params_start..params_end].iter().collect::<String>());
+
+                        // Only whitespace may separate the closing parenthesis of the parameters
+                        // from the next attribute or the end of the attributes.
+                        let next = mode
+                            .tokens
+                            .get(mode.cursor + 1..)
+                            .unwrap_or_default()
+                            .iter()
+                            .position(|token| !token.is_whitespace())
+                            .map(|offset| mode.cursor + 1 + offset);
+
+                        if let Some(next) = next {
+                            if mode.tokens[next] != ATTRIBUTE_DELIMETER && mode.tokens[next] != ATTRIBUTE_CLOSE {
+                                return Err(ParseError::attribute_after_parameters(next, &mode.tokens).into());
+                            }
+                            start = next;
+                        }
                     }
-                    raw_attrs.push((attr, args));
+                    raw_attrs.push((attr, args, attr_start));
                 }
             }
             if !closed {
@@ -505,21 +794,29 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
             }
 
             let mut attrs = Vec::with_capacity(raw_attrs.len());
-            for (name, args) in raw_attrs {
-                let attr = Attribute::parse(name, args)?;
-                attrs.push(attr);
+            for (name, args, attr_start) in raw_attrs {
+                let Some(alias) = name.strip_prefix(ALIAS) else {
+                    attrs.push(Attribute::parse(name, args, mode.options.escape)?);
+                    continue;
+                };
+                if args.is_some() {
+                    return Err(ParseError::alias_with_arguments(attr_start, &mode.tokens, &name).into());
+                }
+                let aliased = mode
+                    .options
+                    .aliases
+                    .get(alias)
+                    .ok_or_else(|| ParseError::undefined_alias(attr_start, &mode.tokens, &name))?;
+                attrs.extend_from_slice(aliased);
             }
 
-            // Alignment always comes first due to ANSI-escape sequences messing with string length
-            attrs.sort_by(|a, b| {
-                if let AttributeKind::Align { .. } = a.kind {
-                    Ordering::Less
-                } else if let AttributeKind::Align { .. } = b.kind {
-                    Ordering::Greater
-                } else {
-                    Ordering::Equal
-                }
-            });
+            // The default value that the attributes belong to follows the ':'.
+            if mode.in_default {
+                mode.in_default = false;
+                mode.default_attributes = Some(attrs);
+                mode.mode = ParseStateMode::AnchorParseDefaultValue;
+                return parse_impl(mode, anchors, rules);
+            }
 
             if let Some(anchor) = mode.bound_anchor.as_mut() {
                 anchor.attributes = attrs;
@@ -536,7 +833,46 @@ fn parse_impl(mode: &mut ParseState, anchors: &mut Vec<Anchor>, rules: &Rules) -
     }
 }
 
-impl Debug for ParseState {
+/// Collects the `raw` chars of a default string literal, removing the `escape` from before any of
+/// the `unescaped` chars, e.g. the quote that encloses the literal. The escape is kept before any
+/// other char, like a double-quoted string in a shell.
+fn unescape_literal(raw: &[char], escape: char, unescaped: &[char]) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.iter().copied().peekable();
+
+    while let Some(c) = chars.next() {
+        if c == escape && chars.peek().is_some_and(|next| unescaped.contains(next)) {
+            out.extend(chars.next());
+            continue;
+        }
+        out.push(c);
+
+        if c == escape {
+            out.extend(chars.next());
+        }
+    }
+    out
+}
+
+/// Scans the contents of an index operation starting from `begin`, the position immediately
+/// following the opening `[`, and returns the position of the closing `]`. Quoted separators may
+/// contain a `]`.
+fn scan_index(tokens: &[char], begin: usize) -> Option<usize> {
+    let mut open_quote = None;
+
+    for (i, token) in tokens.iter().copied().enumerate().skip(begin) {
+        match open_quote {
+            Some(quote) if token == quote => open_quote = None,
+            Some(_) => (),
+            None if token == LITERAL_DOUBLE_QUOTE || token == LITERAL_SINGLE_QUOTE => open_quote = Some(token),
+            None if token == INDEX_CLOSE => return Some(i),
+            None => (),
+        }
+    }
+    None
+}
+
+impl Debug for ParseState<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let ParseState {
             recursion_depth,
@@ -551,3 +887,9 @@ impl Debug for ParseState {
         )
     }
 }
+
+/// Collects the `tokens` of an attribute's name ignoring surrounding whitespace.
+fn attribute_name(tokens: &[char], start: usize) -> (String, usize) {
+    let leading = tokens.iter().take_while(|token| token.is_whitespace()).count();
+    (tokens.iter().collect::<String>().trim().to_string(), start + leading)
+}
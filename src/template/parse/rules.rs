@@ -1,23 +1,116 @@
 use regex::Regex;
 
-/// Defines a valid anchor name
-pub const VALID_ANCHOR_CHARSET: &str = r#"^[a-zA-Z0-9_]+$"#;
+/// The characters allowed in anchor names by default, written as the contents of a bracket
+/// expression. Names may not begin with any of the [NAME_SEPARATORS].
+pub const ANCHOR_CHARSET: &str = r#"a-zA-Z0-9_.\-"#;
+
+/// Defines a valid anchor name that refers to a capture group by its index, e.g. `$1`
+pub const VALID_POSITIONAL_ANCHOR: &str = r#"^\$[0-9]+$"#;
+
+/// Defines the namespace of an anchor name that refers to a capture group of the patterns of a
+/// particular name, e.g. the `req` of `req:status` or `req:$1`
+pub const VALID_NAMESPACE: &str = r#"[a-zA-Z0-9_]+"#;
+
+/// Defines the prefix of an anchor name that refers to where the matches of a capture group begin
+/// or end within the line, e.g. the `@start` of `@start:ip` or the `@end` of `@end:ip`
+pub const VALID_SPAN_PREFIX: &str = r#"@(start|end)"#;
+
+/// Separates the namespace or span prefix of an anchor name from the name of the capture group.
+pub const NAME_QUALIFIER: char = ':';
+
+/// The character that begins anchor names that refer to where the matches of a capture group begin
+/// or end.
+pub const SPAN_PREFIX: char = '@';
+
+/// Defines a valid environment variable name used as a default value
+pub const VALID_ENV_VAR_CHARSET: &str = r#"^[a-zA-Z0-9_]+$"#;
+
+/// Characters allowed in anchor names that may not begin them. If no capture group has the name of an
+/// anchor as written then they are translated to [NAME_SEPARATOR_REPLACEMENT] so that
+/// `{http.status}` may also refer to `http_status`.
+pub const NAME_SEPARATORS: [char; 2] = ['.', '-'];
+
+/// What each of the [NAME_SEPARATORS] in an anchor name is translated to.
+pub const NAME_SEPARATOR_REPLACEMENT: char = '_';
 
 /// Concerned with enforcing validations for various properties
 /// computed during parsing such as anchor name.
 pub struct Rules {
+    charset: String,
     valid_anchor_name: Regex,
+    valid_positional_anchor: Regex,
+    valid_namespaced_anchor: Regex,
+    valid_span_anchor: Regex,
+    valid_env_var_name: Regex,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self::new(ANCHOR_CHARSET).unwrap()
+    }
 }
 
 impl Rules {
-    pub fn new() -> Self {
-        Self {
-            valid_anchor_name: Regex::new(VALID_ANCHOR_CHARSET).unwrap(),
-        }
+    /// Enforces that anchor names consist of the characters of `charset`, which is written as the
+    /// contents of a bracket expression, e.g. [ANCHOR_CHARSET]. Fails if `charset` isn't valid.
+    pub fn new(charset: &str) -> Result<Self, regex::Error> {
+        let name = format!(r#"[{charset}--[.\-]][{charset}]*"#);
+
+        Ok(Self {
+            charset: charset.to_string(),
+            valid_anchor_name: Regex::new(&format!("^(?:{name})$"))?,
+            valid_positional_anchor: Regex::new(VALID_POSITIONAL_ANCHOR)?,
+            valid_namespaced_anchor: Regex::new(&format!(r#"^{VALID_NAMESPACE}{NAME_QUALIFIER}(?:{name}|\$[0-9]+)$"#))?,
+            valid_span_anchor: Regex::new(&format!("^{VALID_SPAN_PREFIX}{NAME_QUALIFIER}(?:{name})$"))?,
+            valid_env_var_name: Regex::new(VALID_ENV_VAR_CHARSET)?,
+        })
     }
 
     /// Is the anchor name valid
     pub fn name_is_valid(&self, name: &str) -> bool {
         self.valid_anchor_name.is_match(name)
     }
+
+    /// The characters allowed in anchor names
+    pub fn charset(&self) -> &str {
+        &self.charset
+    }
+
+    /// Is the anchor name a valid reference to a capture group by its index
+    pub fn positional_is_valid(&self, name: &str) -> bool {
+        self.valid_positional_anchor.is_match(name)
+    }
+
+    /// Is the anchor name a valid reference to a capture group of the patterns of a particular name
+    pub fn namespaced_is_valid(&self, name: &str) -> bool {
+        self.valid_namespaced_anchor.is_match(name)
+    }
+
+    /// Is the anchor name a valid reference to where the matches of a capture group begin or end
+    pub fn span_is_valid(&self, name: &str) -> bool {
+        self.valid_span_anchor.is_match(name)
+    }
+
+    /// Is the environment variable name valid
+    pub fn env_var_is_valid(&self, name: &str) -> bool {
+        self.valid_env_var_name.is_match(name)
+    }
+
+    /// Translates the [NAME_SEPARATORS] of a valid anchor name into [NAME_SEPARATOR_REPLACEMENT] if
+    /// none of `group_names` is the capture group it refers to as written but one is once translated.
+    /// Only the part of the name that follows its namespace or span prefix is a group name.
+    pub fn capture_name(name: &str, group_names: &[&str]) -> Option<String> {
+        let (qualifier, group) = match name.rsplit_once(NAME_QUALIFIER) {
+            Some((qualifier, group)) => (&name[..=qualifier.len()], group),
+            None => ("", name),
+        };
+        if !group.contains(NAME_SEPARATORS) || group_names.contains(&group) {
+            return None;
+        }
+        let translated = group.replace(NAME_SEPARATORS, &NAME_SEPARATOR_REPLACEMENT.to_string());
+
+        group_names
+            .contains(&translated.as_str())
+            .then(|| format!("{qualifier}{translated}"))
+    }
 }
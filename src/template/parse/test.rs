@@ -1,7 +1,9 @@
 use super::{
-    attr::{Alignment, AttributeKind},
-    parse, DefaultValue,
+    attr::{Alignment, AttributeKind, Phase},
+    parse, parse_nested, parse_with_options, DefaultValue, Index, ParseOptions, MAX_DEFAULT_NESTING,
 };
+use crate::template::Aliases;
+use crossterm::style::Color;
 
 #[test]
 fn test_parse_plain() {
@@ -48,6 +50,58 @@ fn test_parse_escape() {
     assert_eq!(anchor.index, None);
 }
 
+#[test]
+fn test_parse_custom_escape() {
+    let aliases = Aliases::default();
+    let options = ParseOptions::new(&aliases).with_escape('~');
+
+    let template_string = r"~{skipped} \{log} ~~{level}";
+    let anchors = parse_with_options(template_string, options).unwrap();
+    let names = anchors.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["log", "level"]);
+
+    // The escape is removed from attribute parameters so that quotes can be escaped without it
+    // ending up in the pattern.
+    let anchors = parse_with_options(r"{(?red('O~'Conner')):foo}", options).unwrap();
+    let attr = &anchors[0].attributes[0];
+    assert!(attr.must_match.as_ref().unwrap().is_match("O'Conner"));
+
+    let anchors = parse_with_options(r"{(?red('\d+')):foo}", options).unwrap();
+    let attr = &anchors[0].attributes[0];
+    assert!(attr.must_match.as_ref().unwrap().is_match("42"));
+    assert!(!attr.must_match.as_ref().unwrap().is_match("d"));
+
+    let err = parse_with_options("output~", options).unwrap_err().to_string();
+    assert!(err.contains("'~' escape"), "{err}");
+}
+
+#[test]
+fn test_parse_custom_anchor_delimiters() {
+    let aliases = Aliases::default();
+    let options = ParseOptions::new(&aliases).with_anchor_delimiters('<', '>');
+
+    let anchors = parse_with_options(
+        r#"{"lvl": "<(red):lvl>", "msg": "<msg || 'x<code>'>"} <<skipped>>"#,
+        options,
+    )
+    .unwrap();
+    let names = anchors.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["lvl", "msg"]);
+    assert!(matches!(&anchors[1].defaults[0], DefaultValue::Interpolated { .. }));
+
+    // Braces are ordinary characters so they needn't be doubled.
+    assert!(parse_with_options("{ <a> }", options).is_ok());
+
+    let err = parse_with_options("<a", options).unwrap_err().to_string();
+    assert!(err.contains("Expected a '>'"), "{err}");
+
+    // The same character may both open and close anchors.
+    let options = ParseOptions::new(&aliases).with_anchor_delimiters('%', '%');
+    let anchors = parse_with_options("%a%%b% 100%% %c || 'x%d%'%", options).unwrap();
+    let names = anchors.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "c"]);
+}
+
 #[test]
 fn test_parse_index() {
     let template_string = "primary={log[0]} secondary={log[102]}";
@@ -57,12 +111,101 @@ fn test_parse_index() {
     let anchor = &anchors[0];
     assert_eq!(&anchor.name, "log");
     assert_eq!("{log[0]}", &template_string[anchor.start..anchor.end]);
-    assert_eq!(anchor.index, Some(0));
+    assert_eq!(anchor.index, Some(Index::One(0)));
 
     let anchor = &anchors[1];
     assert_eq!(&anchor.name, "log");
     assert_eq!("{log[102]}", &template_string[anchor.start..anchor.end]);
-    assert_eq!(anchor.index, Some(102));
+    assert_eq!(anchor.index, Some(Index::One(102)));
+
+    let template_string = "last={log[-1]} penultimate={foo || log[-2]}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 2);
+
+    let anchor = &anchors[0];
+    assert_eq!("{log[-1]}", &template_string[anchor.start..anchor.end]);
+    assert_eq!(anchor.index, Some(Index::One(-1)));
+
+    let DefaultValue::Anchor { name, index } = &anchors[1].defaults[0] else {
+        panic!("expected default value to be anchor");
+    };
+    assert_eq!(name, "log");
+    assert_eq!(*index, Some(Index::One(-2)));
+}
+
+#[test]
+fn test_parse_index_all() {
+    let template_string = r#"all={ip[*]} custom={ip[*:", "]} bracket={ip[*:']']}"#;
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 3);
+
+    let anchor = &anchors[0];
+    assert_eq!(&anchor.name, "ip");
+    assert_eq!("{ip[*]}", &template_string[anchor.start..anchor.end]);
+    assert_eq!(anchor.index, Some(Index::All(",".to_string())));
+
+    let anchor = &anchors[1];
+    assert_eq!(&anchor.name, "ip");
+    assert_eq!(r#"{ip[*:", "]}"#, &template_string[anchor.start..anchor.end]);
+    assert_eq!(anchor.index, Some(Index::All(", ".to_string())));
+
+    let anchor = &anchors[2];
+    assert_eq!("{ip[*:']']}", &template_string[anchor.start..anchor.end]);
+    assert_eq!(anchor.index, Some(Index::All("]".to_string())));
+
+    let template_string = "primary={foo || ip[*:'-']}";
+    let anchors = parse(template_string).unwrap();
+    let DefaultValue::Anchor { name, index } = &anchors[0].defaults[0] else {
+        panic!("expected default value to be anchor");
+    };
+    assert_eq!(name, "ip");
+    assert_eq!(*index, Some(Index::All("-".to_string())));
+}
+
+#[test]
+fn test_parse_index_range() {
+    let template_string = r#"{w[1..3]} {w[1..]} {w[..-1]} {w[..]} {w[0..2:" "]}"#;
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 5);
+
+    let expected = [
+        (Some(1), Some(3), ","),
+        (Some(1), None, ","),
+        (None, Some(-1), ","),
+        (None, None, ","),
+        (Some(0), Some(2), " "),
+    ];
+    for (anchor, (start, end, separator)) in anchors.iter().zip(expected) {
+        assert_eq!(&anchor.name, "w");
+        assert_eq!(
+            anchor.index,
+            Some(Index::Range {
+                start,
+                end,
+                separator: separator.to_string()
+            })
+        );
+    }
+
+    let template_string = "primary={foo[1...3]}";
+    assert!(parse(template_string).is_err());
+
+    let template_string = "primary={foo[a..3]}";
+    assert!(parse(template_string).is_err());
+
+    let template_string = "primary={foo[1:\" \"]}";
+    assert!(parse(template_string).is_err());
+}
+
+#[test]
+fn test_resolve_index_range() {
+    assert_eq!(Index::resolve_range(Some(1), Some(3), 5), (1, 3));
+    assert_eq!(Index::resolve_range(None, None, 5), (0, 5));
+    assert_eq!(Index::resolve_range(Some(3), Some(100), 5), (3, 5));
+    assert_eq!(Index::resolve_range(Some(-2), None, 5), (3, 5));
+    assert_eq!(Index::resolve_range(Some(-100), Some(-4), 5), (0, 1));
+    assert_eq!(Index::resolve_range(Some(4), Some(2), 5), (4, 4));
+    assert_eq!(Index::resolve_range(Some(10), None, 0), (0, 0));
 }
 
 #[test]
@@ -78,6 +221,18 @@ fn test_parse_index_errors() {
     let template_string = "primary={foobar [0]}";
     let anchors = parse(template_string);
     assert!(anchors.is_err_and(|e| e.to_string().contains("Invalid index operation")));
+
+    let template_string = "primary={foobar[*,]}";
+    let anchors = parse(template_string);
+    assert!(anchors.is_err_and(|e| e.to_string().contains("Expected index")));
+
+    let template_string = "primary={foobar[*:,]}";
+    let anchors = parse(template_string);
+    assert!(anchors.is_err_and(|e| e.to_string().contains("Expected index")));
+
+    let template_string = "primary={foobar[0";
+    let anchors = parse(template_string);
+    assert!(anchors.is_err());
 }
 
 #[test]
@@ -114,6 +269,44 @@ fn test_default_literal_parse_multi_value() {
     }
 }
 
+#[test]
+fn test_default_literal_special_chars() {
+    let cases = [
+        (r#"{foo || "N/A (unknown)"}"#, "N/A (unknown)"),
+        (r#"{foo || "a|b"}"#, "a|b"),
+        (r#"{foo || "a || b"}"#, "a || b"),
+        (r#"{foo || "}"}"#, "}"),
+        (r#"{foo || "(red):foo"}"#, "(red):foo"),
+        (r#"{foo || ""}"#, ""),
+        (r#"{foo || "say \"hi\""}"#, r#"say "hi""#),
+        (r#"{foo || 'it\'s'}"#, "it's"),
+        (r#"{foo || "it's"}"#, "it's"),
+        (r#"{foo || "C:\logs\\"}"#, r"C:\logs\"),
+        (r#"{foo || "\\"}"#, r"\"),
+    ];
+    for (template_string, expected) in cases {
+        let anchors = parse(template_string).unwrap();
+        assert_eq!(anchors.len(), 1, "{template_string}");
+        assert_eq!(anchors[0].end, template_string.chars().count(), "{template_string}");
+
+        let DefaultValue::Literal(val) = &anchors[0].defaults[0] else {
+            panic!("expected default of {template_string} to be a literal");
+        };
+        assert_eq!(val, expected, "{template_string}");
+    }
+
+    // The escape is removed from before the quote prior to parsing a nested template.
+    let anchors = parse(r#"{foo || "\"{bar}\""}"#).unwrap();
+    let DefaultValue::Interpolated { literal, .. } = &anchors[0].defaults[0] else {
+        panic!("expected default to be interpolated");
+    };
+    assert_eq!(literal, r#""{bar}""#);
+
+    for invalid in [r#"{foo || "{"}"#, r#"{foo || "abc\"}"#, r#"{foo || "abc}"#] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
 #[test]
 fn test_default_anchor() {
     let template_string = "primary={foo||bar}";
@@ -130,6 +323,24 @@ fn test_default_anchor() {
     assert!(index.is_none());
 }
 
+#[test]
+fn test_default_on_blank() {
+    let anchors = parse(r#"{foo ?? bar ?? "baz"} {foo || bar}"#).unwrap();
+    assert_eq!(anchors.len(), 2);
+    assert!(anchors[0].default_on_blank);
+    assert_eq!(anchors[0].name, "foo");
+    assert_eq!(anchors[0].defaults.len(), 2);
+    assert!(!anchors[1].default_on_blank);
+
+    let anchors = parse("{foo || bar ?? baz}").unwrap();
+    assert!(anchors[0].default_on_blank);
+    assert_eq!(anchors[0].defaults.len(), 2);
+
+    for invalid in ["{foo ? bar}", "{?? bar}", "{foo ?| bar}", "{!foo ?? bar}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
 #[test]
 fn test_default_anchor_whitespace() {
     let template_string = "primary={foo || bar}";
@@ -161,7 +372,7 @@ fn test_default_anchor_indexes() {
         panic!("expected default value to be anchor");
     };
     assert_eq!(name, "bar");
-    assert!(index.is_some_and(|i| i == 0));
+    assert_eq!(*index, Some(Index::One(0)));
 }
 
 #[test]
@@ -195,7 +406,7 @@ fn test_default_values_multi_value_with_indexes() {
         &template_string[anchor.start..anchor.end]
     );
 
-    assert!(anchor.index.is_some_and(|i| i == 3));
+    assert_eq!(anchor.index, Some(Index::One(3)));
     let default_values = &anchor.defaults;
     assert_eq!(default_values.len(), 2);
 
@@ -203,15 +414,57 @@ fn test_default_values_multi_value_with_indexes() {
         match default_value {
             DefaultValue::Anchor { name, index } => {
                 assert!(name == "bar");
-                assert!(index.is_some_and(|i| i == 0));
+                assert_eq!(*index, Some(Index::One(0)));
             }
             DefaultValue::Literal(val) => {
                 assert!(val == "baz");
             }
+            DefaultValue::Env(_) => panic!("unexpected environment variable default"),
+            DefaultValue::Interpolated { .. } => panic!("unexpected interpolated default"),
+            DefaultValue::Attributed { .. } => panic!("unexpected attributed default"),
         }
     }
 }
 
+#[test]
+fn test_default_env_var() {
+    let template_string = r#"primary={host || $HOSTNAME || "unknown"}"#;
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 1);
+    let anchor = &anchors[0];
+    assert_eq!(
+        r#"{host || $HOSTNAME || "unknown"}"#,
+        &template_string[anchor.start..anchor.end]
+    );
+
+    let default_values = &anchor.defaults;
+    assert_eq!(default_values.len(), 2);
+    let DefaultValue::Env(var) = &default_values[0] else {
+        panic!("expected default value to be an environment variable");
+    };
+    assert_eq!(var, "HOSTNAME");
+    let DefaultValue::Literal(val) = &default_values[1] else {
+        panic!("expected default value to be a literal");
+    };
+    assert_eq!(val, "unknown");
+
+    let template_string = "primary={host||$HOSTNAME}";
+    let anchors = parse(template_string).unwrap();
+    let DefaultValue::Env(var) = &anchors[0].defaults[0] else {
+        panic!("expected default value to be an environment variable");
+    };
+    assert_eq!(var, "HOSTNAME");
+}
+
+#[test]
+fn test_default_env_var_errors() {
+    let template_string = "primary={host || $HOSTNAME[0]}";
+    assert!(parse(template_string).is_err());
+
+    let template_string = "primary={host || $HOST-NAME}";
+    assert!(parse(template_string).is_err());
+}
+
 #[test]
 fn test_attribute() {
     let template_string = "output={(red|bold):foo}";
@@ -244,6 +497,7 @@ fn test_literal_anchor() {
 }
 
 #[test]
+#[allow(clippy::len_zero)]
 fn test_literal_anchor_with_attributes() {
     let template_string = r#"output={(red|bold):"foo"}"#;
     let anchors = parse(template_string).unwrap();
@@ -282,6 +536,38 @@ fn test_required_anchor() {
     assert!(anchor.required);
 }
 
+#[test]
+fn test_count_anchor() {
+    for (template_string, name, required) in [
+        ("matches={#ip}", "ip", false),
+        ("matches={ #ip }", "ip", false),
+        ("matches={!#ip}", "ip", true),
+        ("matches={(green):#ip}", "ip", false),
+        ("matches={#$2}", "$2", false),
+    ] {
+        let anchors = parse(template_string).unwrap();
+        assert_eq!(anchors.len(), 1, "{template_string}");
+
+        let anchor = &anchors[0];
+        assert_eq!(anchor.name, name, "{template_string}");
+        assert_eq!(&template_string[8..], &template_string[anchor.start..anchor.end]);
+        assert!(anchor.count_of, "{template_string}");
+        assert_eq!(anchor.required, required, "{template_string}");
+    }
+    assert!(!parse("{ip}").unwrap()[0].count_of);
+
+    for invalid in [
+        "{#}",
+        "{#ip[0]}",
+        "{#ip || \"none\"}",
+        "{#ip || host}",
+        "{#\"none\"}",
+        "{i#p}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
 #[test]
 fn test_required_anchor_with_attrs() {
     let template_string = "output={!(red|bold):log}";
@@ -342,6 +628,101 @@ fn test_attr_conditional_regex() {
     assert!(attr.must_match.as_ref().unwrap().is_match("info"));
 }
 
+#[test]
+fn test_attr_conditional_env() {
+    let template_string = "output={(?red($GRITS_TEST_UNSET_PATTERN)|?blue('$NOT_ENV')):foo}";
+    let anchors = parse(template_string).unwrap();
+    let attrs = &anchors[0].attributes;
+    assert_eq!(attrs.len(), 2);
+    assert_eq!(attrs[0].kind, AttributeKind::Red);
+    assert!(attrs[0].must_match.is_none());
+    let pattern = attrs[0].must_match_env.as_ref().unwrap();
+    assert_eq!(pattern.var, "GRITS_TEST_UNSET_PATTERN");
+    assert!(pattern.regex().is_none());
+
+    assert!(attrs[1].must_match_env.is_none());
+    assert!(attrs[1].must_match.is_some());
+}
+
+#[test]
+fn test_attr_conditional_chained() {
+    let anchors = parse(r"{(?green('^\d{1,2}ms$')|??yellow('^\d{3}ms$')|??red('.')):latency}").unwrap();
+    let attrs = &anchors[0].attributes;
+    assert_eq!(attrs.len(), 3);
+    assert_eq!(attrs[0].kind, AttributeKind::Green);
+    assert!(!attrs[0].chained);
+    assert_eq!(attrs[1].kind, AttributeKind::Yellow);
+    assert!(attrs[1].chained);
+    assert!(attrs[1].must_match.as_ref().unwrap().is_match("250ms"));
+    assert_eq!(attrs[2].kind, AttributeKind::Red);
+    assert!(attrs[2].chained);
+
+    assert!(parse("{(??red):latency}").is_err());
+}
+
+#[test]
+fn test_attr_conditional_quoted_commas() {
+    let anchors = parse(r#"{(?red('(?:a,b)')):foo} {(?red( "x, y" ) | bold):foo}"#).unwrap();
+
+    let attr = &anchors[0].attributes[0];
+    let re = attr.must_match.as_ref().unwrap();
+    assert_eq!(re.as_str(), "(?:a,b)");
+    assert!(re.is_match("a,b"));
+    assert!(!re.is_match("a"));
+
+    let attrs = &anchors[1].attributes;
+    assert_eq!(attrs.len(), 2);
+    assert_eq!(attrs[0].must_match.as_ref().unwrap().as_str(), "x, y");
+    assert_eq!(attrs[1].kind, AttributeKind::Bold);
+}
+
+#[test]
+fn test_attr_whitespace() {
+    let anchors = parse("{( red | bold ):a} {(lalign(3) |?cyan('x') ):a}").unwrap();
+    let kinds = anchors
+        .iter()
+        .flat_map(|a| a.attributes.iter().map(|attr| attr.kind.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::Red,
+            AttributeKind::Bold,
+            AttributeKind::Align {
+                direction: Alignment::Left,
+                width: 3
+            },
+            AttributeKind::Cyan,
+        ]
+    );
+
+    assert!(parse("{(lalign(3) bold):a}").is_err());
+    assert!(parse("{(red b old):a}").is_err());
+}
+
+#[test]
+fn test_attr_conditional_quantifier_braces() {
+    let template_string = r"{(?red('\d{3,5}')|?bold('a{2}')):foo} {(?cyan(a{2})|bold):foo}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 2);
+    assert_eq!(
+        r"{(?red('\d{3,5}')|?bold('a{2}')):foo}",
+        &template_string[anchors[0].start..anchors[0].end]
+    );
+
+    let attrs = &anchors[0].attributes;
+    let digits = attrs[0].must_match.as_ref().unwrap();
+    assert_eq!(digits.as_str(), r"\d{3,5}");
+    assert!(digits.is_match("1234"));
+    assert!(!digits.is_match("12"));
+    assert_eq!(attrs[1].must_match.as_ref().unwrap().as_str(), "a{2}");
+
+    let attrs = &anchors[1].attributes;
+    assert_eq!(attrs.len(), 2);
+    assert_eq!(attrs[0].must_match.as_ref().unwrap().as_str(), "a{2}");
+    assert_eq!(attrs[1].kind, AttributeKind::Bold);
+}
+
 #[test]
 fn test_attr_conditional_escape() {
     let template_string = r"output={(?red('(?i)O\'Conner')):foo}";
@@ -422,3 +803,493 @@ fn test_attr_alignment() {
     assert_eq!(width, 9);
     assert_eq!(direction, Alignment::Left)
 }
+
+#[test]
+fn test_attr_fit() {
+    let anchors = parse("{(fit(10)):a} {(rfit(4, 'ellipsis')):a} {(cfit(7)):a}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::Fit {
+                width: 10,
+                align: Alignment::Left,
+                ellipsis: false
+            },
+            AttributeKind::Fit {
+                width: 4,
+                align: Alignment::Right,
+                ellipsis: true
+            },
+            AttributeKind::Fit {
+                width: 7,
+                align: Alignment::Center,
+                ellipsis: false
+            },
+        ]
+    );
+
+    for invalid in ["{(fit):a}", "{(fit(ten)):a}", "{(lfit(10, 'dots')):a}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_repeat() {
+    let anchors = parse(r#"{(repeat(20)):"="} {(repeat(0)):a}"#).unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [AttributeKind::Repeat { count: 20 }, AttributeKind::Repeat { count: 0 }]
+    );
+
+    for invalid in ["{(repeat):a}", "{(repeat(-1)):a}", "{(repeat(many)):a}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_bar() {
+    let anchors = parse("{(bar(20, 100)):pct} {(bar(10,0.5)):ratio}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::Bar { width: 20, max: 100.0 },
+            AttributeKind::Bar { width: 10, max: 0.5 },
+        ]
+    );
+
+    for invalid in [
+        "{(bar):a}",
+        "{(bar(20)):a}",
+        "{(bar(20, 0)):a}",
+        "{(bar(20, -5)):a}",
+        "{(bar(wide, 100)):a}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_hash() {
+    let anchors = parse("{(hash):user} {(hash(12)):ip} {(HASH(64)):ip}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::Hash { len: 8 },
+            AttributeKind::Hash { len: 12 },
+            AttributeKind::Hash { len: 64 },
+        ]
+    );
+
+    for invalid in ["{(hash(0)):a}", "{(hash(65)):a}", "{(hash(short)):a}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_mask() {
+    let anchors = parse("{(mask(4, 4)):card} {(mask(0,2,'#')):phone}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::Mask {
+                keep_start: 4,
+                keep_end: 4,
+                ch: '*'
+            },
+            AttributeKind::Mask {
+                keep_start: 0,
+                keep_end: 2,
+                ch: '#'
+            },
+        ]
+    );
+
+    for invalid in [
+        "{(mask):a}",
+        "{(mask(4)):a}",
+        "{(mask(-1, 4)):a}",
+        "{(mask(4, four)):a}",
+        "{(mask(4, 4, '##')):a}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_colors() {
+    let anchors = parse(
+        r##"{(white|bg_rgb(40, 40, 40)):key} {(hex("#ff8000")|bg_hex('0af')):a} {(color256(208)|bg-color256(235)):a}"##,
+    )
+    .unwrap();
+    let kinds = anchors
+        .iter()
+        .flat_map(|a| a.attributes.iter().map(|attr| attr.kind.clone()))
+        .collect::<Vec<_>>();
+    let color = |color, background| AttributeKind::Color { color, background };
+
+    assert_eq!(
+        kinds,
+        [
+            AttributeKind::White,
+            color(Color::Rgb { r: 40, g: 40, b: 40 }, true),
+            color(Color::Rgb { r: 255, g: 128, b: 0 }, false),
+            color(Color::Rgb { r: 0, g: 170, b: 255 }, true),
+            color(Color::AnsiValue(208), false),
+            color(Color::AnsiValue(235), true),
+        ]
+    );
+
+    for invalid in [
+        "{(rgb(1, 2)):a}",
+        "{(rgb(1, 2, 256)):a}",
+        "{(bg_hex('#12345')):a}",
+        "{(hex('#gggggg')):a}",
+        "{(color256(-1)):a}",
+        "{(bg_color256):a}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_gradient() {
+    let anchors = parse(r##"{(gradient("#ff8000", '0af')):a}"##).unwrap();
+    assert_eq!(
+        anchors[0].attributes[0].kind,
+        AttributeKind::Gradient {
+            start: (255, 128, 0),
+            end: (0, 170, 255)
+        }
+    );
+    assert_eq!(anchors[0].attributes[0].kind.phase(), Phase::Style);
+
+    for invalid in [
+        "{(gradient):a}",
+        "{(gradient('#ff0000')):a}",
+        "{(gradient('#ff0000', red)):a}",
+        "{(gradient('#ff0000', '#gggggg')):a}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_date() {
+    let template_string = r#"{(red|date("%Y-%m-%d, %H:%M:%S", %H:%M)):ts}"#;
+    let anchors = parse(template_string).unwrap();
+    let anchor = &anchors[0];
+    assert_eq!(anchor.attributes.len(), 2);
+
+    // Attributes are kept in the order they are written.
+    assert_eq!(anchor.attributes[0].kind, AttributeKind::Red);
+    let AttributeKind::Date { input_fmt, output_fmt } = &anchor.attributes[1].kind else {
+        panic!("expected date attribute");
+    };
+    assert_eq!(input_fmt, "%Y-%m-%d, %H:%M:%S");
+    assert_eq!(output_fmt, "%H:%M");
+
+    assert!(parse(r#"{(date("%Y-%m-%d")):ts}"#).is_err());
+}
+
+#[test]
+fn test_attr_num() {
+    let anchors = parse("{(num):bytes} {(num(' ', 2)):bytes} {(num(\"_\")):bytes}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            AttributeKind::Num {
+                group: ',',
+                precision: None
+            },
+            AttributeKind::Num {
+                group: ' ',
+                precision: Some(2)
+            },
+            AttributeKind::Num {
+                group: '_',
+                precision: None
+            },
+        ]
+    );
+
+    assert!(parse("{(num(',,')):bytes}").is_err());
+    assert!(parse("{(num(',', two)):bytes}").is_err());
+}
+
+#[test]
+fn test_attr_bytesize() {
+    let anchors = parse("{(bytesize):size} {(bytesize('si')):size} {(bytesize(binary)):size}").unwrap();
+    let kinds = anchors.iter().map(|a| a.attributes[0].kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            AttributeKind::ByteSize { binary: true },
+            AttributeKind::ByteSize { binary: false },
+            AttributeKind::ByteSize { binary: true },
+        ]
+    );
+
+    assert!(parse("{(bytesize('kb')):size}").is_err());
+}
+
+#[test]
+fn test_attr_signed() {
+    let anchors = parse("{(signed|ralign(5)):delta}").unwrap();
+    let kinds = anchors[0].attributes.iter().map(|a| a.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            AttributeKind::Signed,
+            AttributeKind::Align {
+                direction: Alignment::Right,
+                width: 5
+            },
+        ]
+    );
+    assert_eq!(AttributeKind::Signed.phase(), Phase::Transform);
+}
+
+#[test]
+fn test_attr_indent() {
+    let anchors = parse("{(indent(4)|red):trace}").unwrap();
+    let kinds = anchors[0].attributes.iter().map(|a| a.kind.clone()).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![AttributeKind::Indent { spaces: 4 }, AttributeKind::Red]);
+    assert_eq!(kinds[0].phase(), Phase::Indent);
+
+    for invalid in ["{(indent):a}", "{(indent(-1)):a}", "{(indent(four)):a}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_attr_wrap() {
+    let anchors = parse("{(wrap(40)):msg}").unwrap();
+    assert_eq!(anchors[0].attributes[0].kind, AttributeKind::Wrap { width: 40 });
+    assert_eq!(anchors[0].attributes[0].kind.phase(), Phase::Transform);
+
+    for invalid in ["{(wrap):a}", "{(wrap(0)):a}", "{(wrap(-1)):a}", "{(wrap(wide)):a}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_doubled_braces() {
+    let template_string = "{{not an anchor}}";
+    let anchors = parse(template_string).unwrap();
+    assert!(anchors.is_empty());
+
+    let template_string = "{{{log}}} \\{{lvl}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 2);
+    assert_eq!(&anchors[0].name, "log");
+    assert_eq!("{log}", &template_string[anchors[0].start..anchors[0].end]);
+    assert_eq!(&anchors[1].name, "lvl");
+    assert_eq!("{lvl}", &template_string[anchors[1].start..anchors[1].end]);
+}
+
+#[test]
+fn test_parse_namespaced_anchor_names() {
+    let template_string = "{http.status} {(red):user-agent[0]} {req.id || http.request-id || $HOME}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 3);
+    assert_eq!(&anchors[0].name, "http.status");
+    assert_eq!(&anchors[1].name, "user-agent");
+    assert_eq!(anchors[1].index, Some(Index::One(0)));
+    assert_eq!(&anchors[2].name, "req.id");
+
+    let DefaultValue::Anchor { name, .. } = &anchors[2].defaults[0] else {
+        panic!("expected anchor default value");
+    };
+    assert_eq!(name, "http.request-id");
+
+    for invalid in ["{.status}", "{-status}", "{http/status}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_anchor_charset() {
+    let aliases = Aliases::default();
+    let options = ParseOptions::new(&aliases).with_anchor_charset(r"a-z_/\-");
+
+    let anchors = parse_with_options("{http/status} {@start:http/status || user-agent}", options).unwrap();
+    assert_eq!(&anchors[0].name, "http/status");
+    assert_eq!(&anchors[1].name, "@start:http/status");
+
+    let DefaultValue::Anchor { name, .. } = &anchors[1].defaults[0] else {
+        panic!("expected anchor default value");
+    };
+    assert_eq!(name, "user-agent");
+
+    for invalid in ["{http.status}", "{Status}", "{-status}", "{req:status1}"] {
+        assert!(parse_with_options(invalid, options).is_err(), "{invalid}");
+    }
+    assert!(parse_with_options("{a}", ParseOptions::new(&aliases).with_anchor_charset("z-a")).is_err());
+}
+
+#[test]
+fn test_parse_pattern_namespaced_anchors() {
+    let template_string = "{req:status} {(red):req:user-agent[0]} {#up:status} {req:$1} {foo || up:status || $HOME}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 5);
+    assert_eq!(&anchors[0].name, "req:status");
+    assert_eq!(&anchors[1].name, "req:user-agent");
+    assert_eq!(anchors[1].index, Some(Index::One(0)));
+    assert_eq!(anchors[1].attributes.len(), 1);
+    assert_eq!(&anchors[2].name, "up:status");
+    assert!(anchors[2].count_of);
+    assert_eq!(&anchors[3].name, "req:$1");
+
+    let DefaultValue::Anchor { name, .. } = &anchors[4].defaults[0] else {
+        panic!("expected anchor default value");
+    };
+    assert_eq!(name, "up:status");
+
+    for invalid in [
+        "{:status}",
+        "{req:}",
+        "{req.id:status}",
+        "{req:status:code}",
+        "{req:$1a}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_span_anchors() {
+    let template_string = "{@start:ip} {(red):@end:user-agent[0]} {#@start:ip} {foo || @end:ip}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 4);
+    assert_eq!(&anchors[0].name, "@start:ip");
+    assert_eq!(&anchors[1].name, "@end:user-agent");
+    assert_eq!(anchors[1].index, Some(Index::One(0)));
+    assert!(anchors[2].count_of);
+
+    let DefaultValue::Anchor { name, .. } = &anchors[3].defaults[0] else {
+        panic!("expected anchor default value");
+    };
+    assert_eq!(name, "@end:ip");
+
+    for invalid in [
+        "{@start}",
+        "{@start:}",
+        "{@middle:ip}",
+        "{@start:$1}",
+        "{@start:req:ip}",
+        "{@ip}",
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_positional_anchors() {
+    let template_string = "{$1} {(red):$2[-1]} {foo || $3 || $HOME}";
+    let anchors = parse(template_string).unwrap();
+    assert_eq!(anchors.len(), 3);
+    assert_eq!(&anchors[0].name, "$1");
+    assert_eq!(&anchors[1].name, "$2");
+    assert_eq!(anchors[1].index, Some(Index::One(-1)));
+
+    let DefaultValue::Anchor { name, .. } = &anchors[2].defaults[0] else {
+        panic!("expected anchor default value");
+    };
+    assert_eq!(name, "$3");
+    let DefaultValue::Env(var) = &anchors[2].defaults[1] else {
+        panic!("expected environment variable default value");
+    };
+    assert_eq!(var, "HOME");
+
+    for invalid in ["{$}", "{$1a}", "{$a}", "{1$}"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_interpolated_default_literal() {
+    let anchors = parse(r#"{status || "HTTP {code}"} {a || "plain"}"#).unwrap();
+    assert_eq!(anchors.len(), 2);
+
+    let DefaultValue::Interpolated { literal, template } = &anchors[0].defaults[0] else {
+        panic!("expected interpolated default value");
+    };
+    assert_eq!(literal, "HTTP {code}");
+    assert_eq!(template.anchor_names().collect::<Vec<_>>(), vec!["code"]);
+
+    let DefaultValue::Literal(val) = &anchors[1].defaults[0] else {
+        panic!("expected literal default value");
+    };
+    assert_eq!(val, "plain");
+
+    let anchors = parse(r#"{a || "{b || '{c}'}"}"#).unwrap();
+    let DefaultValue::Interpolated { template, .. } = &anchors[0].defaults[0] else {
+        panic!("expected interpolated default value");
+    };
+    assert_eq!(template.anchor_names().collect::<Vec<_>>(), vec!["b", "c"]);
+    assert!(parse_nested(
+        r#"{a || "{b}"}"#,
+        MAX_DEFAULT_NESTING - 1,
+        ParseOptions::new(&Aliases::default())
+    )
+    .is_ok());
+    assert!(parse_nested(
+        r#"{a || "{b}"}"#,
+        MAX_DEFAULT_NESTING,
+        ParseOptions::new(&Aliases::default())
+    )
+    .is_err());
+
+    for invalid in [r#"{a || "{"}"#, r#"{a || "{b"}"#, r#"{a || "{.b}"}"#] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_unclosed_anchor() {
+    for invalid in ["{", "{a", "foo {a b", "{!a"] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_parse_default_attributes() {
+    let anchors = parse(r#"{(green):status || (red|bold):"DOWN"} {a || b || (blue): $HOME}"#).unwrap();
+    assert_eq!(anchors.len(), 2);
+    assert_eq!(anchors[0].attributes.len(), 1);
+
+    let DefaultValue::Attributed { value, attributes } = &anchors[0].defaults[0] else {
+        panic!("expected attributed default value");
+    };
+    assert!(matches!(value.as_ref(), DefaultValue::Literal(val) if val == "DOWN"));
+    assert_eq!(attributes.len(), 2);
+    assert_eq!(attributes[0].kind, AttributeKind::Red);
+    assert_eq!(attributes[1].kind, AttributeKind::Bold);
+
+    assert!(anchors[1].attributes.is_empty());
+    assert!(matches!(&anchors[1].defaults[0], DefaultValue::Anchor { name, .. } if name == "b"));
+    let DefaultValue::Attributed { value, attributes } = &anchors[1].defaults[1] else {
+        panic!("expected attributed default value");
+    };
+    assert!(matches!(value.as_ref(), DefaultValue::Env(var) if var == "HOME"));
+    assert_eq!(attributes[0].kind, AttributeKind::Blue);
+
+    let anchors = parse(r#"{a ||"b"} {a||(red):'c'}"#).unwrap();
+    assert!(matches!(&anchors[0].defaults[0], DefaultValue::Literal(val) if val == "b"));
+    assert!(matches!(&anchors[1].defaults[0], DefaultValue::Attributed { .. }));
+
+    for invalid in [
+        r#"{a || (red):}"#,
+        r#"{a || (red)"b"}"#,
+        r#"{a || (red):(blue):"b"}"#,
+        r#"{a || (red:"b"}"#,
+    ] {
+        assert!(parse(invalid).is_err(), "{invalid}");
+    }
+}
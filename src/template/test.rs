@@ -1,8 +1,9 @@
 use super::{
+    error::{ParseError, ParseErrorKind},
     parse::attr::{Attribute, AttributeKind},
-    OutputTemplate,
+    Aliases, OutputTemplate, ParseOptions, UnmatchedAnchor,
 };
-use crossterm::style::Stylize;
+use crossterm::style::{Color, Stylize};
 use std::collections::HashMap;
 
 #[test]
@@ -19,6 +20,30 @@ fn test_output_template_basic() {
     assert_eq!(resultant, "log=foo_value out=bar_value baz")
 }
 
+#[test]
+fn test_output_template_slots() {
+    // Templates of different sizes and nested templates share the slots of the thread.
+    let names = (0..20).map(|i| format!("c{i}")).collect::<Vec<_>>();
+    let template = names
+        .iter()
+        .map(|name| format!("{{{name}}}{{{name}}}"))
+        .collect::<Vec<_>>();
+    let large = OutputTemplate::parse(&template.join(" ")).unwrap();
+    let small = OutputTemplate::parse(r#"{c0} {nope || "{c1}-{c1}"}"#).unwrap();
+    assert_eq!(large.slots, 20);
+
+    let values = (0..names.len()).map(|i| vec![i.to_string()]).collect::<Vec<_>>();
+    let mut interpolation_map = HashMap::new();
+    for (name, vals) in names.iter().zip(&values) {
+        interpolation_map.insert(name.as_str(), vals.iter().map(String::as_str).collect::<Vec<_>>());
+    }
+
+    let expected = (0..names.len()).map(|i| format!("{i}{i}")).collect::<Vec<_>>();
+    assert_eq!(large.transform(&interpolation_map), expected.join(" "));
+    assert_eq!(small.transform(&interpolation_map), "0 1-1");
+    assert_eq!(large.transform(&interpolation_map), expected.join(" "));
+}
+
 #[test]
 fn test_output_template_no_match() {
     let template = "log={foo} out={bar} baz";
@@ -45,6 +70,43 @@ fn test_output_template_default() {
     assert_eq!(resultant, "log=foo_value out=foobaz baz")
 }
 
+#[test]
+fn test_output_template_default_on_blank() {
+    let out =
+        OutputTemplate::parse(r#"[{foo || "none"}] [{foo ?? "none"}] [{foo ?? bar ?? baz}] [{foo ?? ""}]"#).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("foo", vec![""]);
+    interpolation_map.insert("bar", vec![""]);
+    interpolation_map.insert("baz", vec!["baz_value"]);
+    assert_eq!(out.transform(&interpolation_map), "[] [none] [baz_value] []");
+    assert!(out.transform_strict(&interpolation_map).is_ok());
+
+    interpolation_map.insert("foo", vec!["foo_value"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        "[foo_value] [foo_value] [foo_value] [foo_value]"
+    );
+}
+
+#[test]
+fn test_output_template_default_env_var() {
+    std::env::set_var("GRITS_TEST_DEFAULT_ENV_VAR", "from_env");
+    std::env::remove_var("GRITS_TEST_UNSET_ENV_VAR");
+
+    let template = r#"out={bar || $GRITS_TEST_UNSET_ENV_VAR || $GRITS_TEST_DEFAULT_ENV_VAR || "foobaz"}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    let interpolation_map = HashMap::new();
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, "out=from_env");
+
+    let template = r#"out={bar || $GRITS_TEST_UNSET_ENV_VAR || "foobaz"}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, "out=foobaz");
+}
+
 #[test]
 fn test_output_template_indexes() {
     let template = r#"log={foo} out={bar[1]}"#;
@@ -58,6 +120,50 @@ fn test_output_template_indexes() {
     let resultant = out.transform(&interpolation_map);
     assert_eq!(resultant, "log=foo_value out=bar_value_2")
 }
+#[test]
+fn test_output_template_negative_indexes() {
+    let template = r#"last={bar[-1]} first={bar[-2]} out_of_range={bar[-3] || "NONE"}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("bar", vec!["bar_value_1", "bar_value_2"]);
+
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, "last=bar_value_2 first=bar_value_1 out_of_range=NONE");
+
+    interpolation_map.insert("bar", vec![]);
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, "last= first= out_of_range=NONE");
+}
+
+#[test]
+fn test_output_template_index_range() {
+    let template = r#"{w[1..3:" "]}|{w[2..]}|{w[..-2]}|{w[5..10] || "NONE"}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("w", vec!["a", "b", "c", "d"]);
+
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, "b c|c,d|a,b|NONE");
+}
+
+#[test]
+fn test_output_template_index_all() {
+    let template = r#"all={ip[*]} custom={ip[*:" | "]} none={foo[*] || "NONE"}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("ip", vec!["127.0.0.1", "10.0.0.1"]);
+    interpolation_map.insert("foo", vec![]);
+
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(
+        resultant,
+        "all=127.0.0.1,10.0.0.1 custom=127.0.0.1 | 10.0.0.1 none=NONE"
+    );
+}
+
 #[test]
 fn test_output_template_indexes_and_defaults() {
     let template = r#"{foo[1]||bar[1]}"#;
@@ -91,10 +197,14 @@ fn test_output_template_attributes() {
             Attribute {
                 kind: AttributeKind::Red,
                 must_match: None,
+                must_match_env: None,
+                chained: false,
             },
             Attribute {
                 kind: AttributeKind::Bold,
                 must_match: None,
+                must_match_env: None,
+                chained: false,
             },
         ],
     );
@@ -118,6 +228,28 @@ fn test_output_template_required() {
     );
 }
 
+#[test]
+fn test_output_template_count() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("ip", vec!["10.0.0.1", "10.0.0.2"]);
+    interpolation_map.insert("host", vec![]);
+
+    let out = OutputTemplate::parse("ips={#ip} hosts={#host} users={#user}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "ips=2 hosts=0 users=0");
+
+    let (_, unmatched) = out.transform_with_report(&interpolation_map);
+    assert!(unmatched.is_empty());
+
+    let out = OutputTemplate::parse("ips={!#ip}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "ips=2");
+
+    let out = OutputTemplate::parse("ips={#ip} hosts={!#host}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "");
+
+    let out = OutputTemplate::parse("{(lalign(3)):#ip}|").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "2  |");
+}
+
 #[test]
 fn test_output_template_required_with_attributes() {
     let template = "log={!(red):foo} out={bar} baz";
@@ -198,3 +330,677 @@ fn test_output_template_alignment() {
     let resultant = out.transform(&interpolation_map);
     assert_eq!(resultant, "bar      ".red().to_string());
 }
+
+#[test]
+fn test_output_template_anchor_names() {
+    let template = r#"{1} {(red):lvl[0] || 2 || $HOME || "none"} {msg}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+    let names = out.anchor_names().collect::<Vec<_>>();
+    assert_eq!(names, vec!["1", "lvl", "2", "msg"]);
+}
+
+#[test]
+fn test_output_template_date() {
+    let template = r#"{(date("%Y-%m-%dT%H:%M:%S", "%d/%m %H:%M")):ts}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("ts", vec!["2025-01-02T13:45:00"]);
+    assert_eq!(out.transform(&interpolation_map), "02/01 13:45");
+
+    interpolation_map.insert("ts", vec!["yesterday"]);
+    assert_eq!(out.transform(&interpolation_map), "yesterday");
+
+    let template = r#"{(red|lalign(7)|date("%Y-%m-%dT%H:%M:%S", "%H:%M")):ts}"#;
+    let out = OutputTemplate::parse(template).unwrap();
+
+    interpolation_map.insert("ts", vec!["2025-01-02T13:45:00"]);
+    assert_eq!(out.transform(&interpolation_map), "13:45  ".red().to_string());
+}
+
+#[test]
+fn test_output_template_num() {
+    let out = OutputTemplate::parse("{(num(',', 0)):n}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected) in [
+        ("1048576", "1,048,576"),
+        ("-1234.6", "-1,235"),
+        ("999", "999"),
+        ("n/a", "n/a"),
+    ] {
+        interpolation_map.insert("n", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected);
+    }
+
+    let out = OutputTemplate::parse("{(num):n}").unwrap();
+    for (input, expected) in [
+        ("12345678901234567890", "12,345,678,901,234,567,890"),
+        ("+1000", "1,000"),
+        ("1234.5678", "1,234.5678"),
+        ("inf", "inf"),
+    ] {
+        interpolation_map.insert("n", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected);
+    }
+
+    let out = OutputTemplate::parse("{(ralign(10)|num('.', 2)):n}").unwrap();
+    interpolation_map.insert("n", vec!["1234.5"]);
+    assert_eq!(out.transform(&interpolation_map), "  1.234.50");
+}
+
+#[test]
+fn test_output_template_bytesize() {
+    let binary = OutputTemplate::parse("{(bytesize):size}").unwrap();
+    let si = OutputTemplate::parse("{(bytesize('si')):size}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected_binary, expected_si) in [
+        ("512", "512 B", "512 B"),
+        ("1048576", "1.0 MiB", "1.0 MB"),
+        ("3650722201", "3.4 GiB", "3.7 GB"),
+        ("-1", "-1", "-1"),
+        ("lots", "lots", "lots"),
+    ] {
+        interpolation_map.insert("size", vec![input]);
+        assert_eq!(binary.transform(&interpolation_map), expected_binary);
+        assert_eq!(si.transform(&interpolation_map), expected_si);
+    }
+
+    let out = OutputTemplate::parse("{(ralign(9)|bytesize):size}").unwrap();
+    interpolation_map.insert("size", vec!["2048"]);
+    assert_eq!(out.transform(&interpolation_map), "  2.0 KiB");
+}
+
+#[test]
+fn test_output_template_signed() {
+    let out = OutputTemplate::parse("{(signed):delta}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected) in [
+        ("42", "+42"),
+        ("+42", "+42"),
+        ("-7", "-7"),
+        ("0", "+0"),
+        ("-0", "+0"),
+        ("3.50", "+3.50"),
+        ("-0.25", "-0.25"),
+        ("n/a", "n/a"),
+        ("inf", "inf"),
+    ] {
+        interpolation_map.insert("delta", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected, "{input}");
+    }
+
+    let out = OutputTemplate::parse("[{(ralign(5)|signed):delta}]").unwrap();
+    for (input, expected) in [("42", "[  +42]"), ("-7", "[   -7]"), ("0", "[   +0]")] {
+        interpolation_map.insert("delta", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected, "{input}");
+    }
+}
+
+#[test]
+fn test_output_template_indent() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("trace", vec!["at foo\n\nat bar"]);
+
+    let out = OutputTemplate::parse("error:\n{(indent(2)):trace}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "error:\n  at foo\n\n  at bar");
+
+    // Indentation accumulates and width adjustments apply to each line.
+    let out = OutputTemplate::parse("{(indent(1)|ralign(7)|indent(1)):trace}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "   at foo\n\n   at bar");
+
+    // Each line is styled on its own whereas the indentation isn't styled.
+    let out = OutputTemplate::parse("{(red|indent(2)):trace}").unwrap();
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!("  {}\n\n  {}", "at foo".red(), "at bar".red())
+    );
+    let out = out.with_color(false);
+    assert_eq!(out.transform(&interpolation_map), "  at foo\n\n  at bar");
+}
+
+#[test]
+fn test_output_template_wrap() {
+    let out = OutputTemplate::parse("{(wrap(10)):msg}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected) in [
+        ("short", "short"),
+        ("exactly 10", "exactly 10"),
+        ("the quick brown fox jumps", "the quick\nbrown fox\njumps"),
+        ("a supercalifragilistic word", "a\nsupercalif\nragilistic\nword"),
+        ("first line\nsecond line", "first line\nsecond\nline"),
+        ("日本語の文章です", "日本語の文\n章です"),
+        ("", ""),
+    ] {
+        interpolation_map.insert("msg", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected, "{input}");
+    }
+
+    // Wrapped lines are indented individually.
+    let out = OutputTemplate::parse("{(indent(2)|wrap(10)):msg}").unwrap();
+    interpolation_map.insert("msg", vec!["the quick brown fox jumps"]);
+    assert_eq!(out.transform(&interpolation_map), "  the quick\n  brown fox\n  jumps");
+}
+
+#[test]
+fn test_output_template_gradient() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("msg", vec!["héllo"]);
+    let rgb = |r, g, b| Color::Rgb { r, g, b };
+
+    // Unicode scalar values are counted so that `é` takes up a single step.
+    let out = OutputTemplate::parse("{(bold|gradient('#ff0000', '#0000ff')):msg}").unwrap();
+    let expected = [
+        ('h', rgb(255, 0, 0)),
+        ('é', rgb(191, 0, 64)),
+        ('l', rgb(128, 0, 128)),
+        ('l', rgb(64, 0, 191)),
+        ('o', rgb(0, 0, 255)),
+    ]
+    .iter()
+    .map(|(c, color)| c.with(*color).bold().to_string())
+    .collect::<String>();
+    assert_eq!(out.transform(&interpolation_map), expected);
+
+    // A single character is rendered with the start color and the last gradient applies.
+    interpolation_map.insert("msg", vec!["x"]);
+    let out = OutputTemplate::parse("{(gradient('#000', '#fff')|gradient('#00ff00', '#0000ff')):msg}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), 'x'.with(rgb(0, 255, 0)).to_string());
+
+    interpolation_map.insert("msg", vec!["héllo"]);
+    let out = OutputTemplate::parse("{(gradient('#ff0000', '#0000ff')|ralign(7)):msg}")
+        .unwrap()
+        .with_color(false);
+    assert_eq!(out.transform(&interpolation_map), "  héllo");
+}
+
+#[test]
+fn test_output_template_repeat() {
+    let rule = OutputTemplate::parse(r#"{(repeat(5)):"="}"#).unwrap();
+    let mut interpolation_map = HashMap::new();
+    assert_eq!(rule.transform(&interpolation_map), "=====");
+
+    let out = OutputTemplate::parse("[{(repeat(3)):unit}]").unwrap();
+    interpolation_map.insert("unit", vec!["ab"]);
+    assert_eq!(out.transform(&interpolation_map), "[ababab]");
+
+    // Repetition precedes alignment and styling regardless of the order of the attributes.
+    let out = OutputTemplate::parse("{(red|lalign(8)|repeat(3)):unit}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "ababab  ".red().to_string());
+
+    let out = OutputTemplate::parse("[{(repeat(0)):unit}]").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "[]");
+}
+
+#[test]
+fn test_output_template_hash() {
+    let out = OutputTemplate::parse("{(hash):user} {(hash(64)):user}").unwrap();
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("user", vec!["alice"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        "2bd806c9 2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90"
+    );
+
+    // Identical values hash identically regardless of which capture they come from.
+    let out = OutputTemplate::parse("{(hash(6)):src}-{(hash(6)):dst}").unwrap();
+    interpolation_map.insert("src", vec!["10.0.0.1"]);
+    interpolation_map.insert("dst", vec!["10.0.0.1"]);
+    let hashed = out.transform(&interpolation_map);
+    let (src, dst) = hashed.split_once('-').unwrap();
+    assert_eq!(src, dst);
+    assert_eq!(src.len(), 6);
+
+    // Hashing is a transformation so the digest is styled rather than the styled value hashed.
+    let out = OutputTemplate::parse("{(red|hash(4)):user}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "2bd8".red().to_string());
+}
+
+#[test]
+fn test_output_template_mask() {
+    let out = OutputTemplate::parse("{(mask(4, 4)):v}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected) in [
+        ("4111111111111111", "4111********1111"),
+        ("123456789", "1234*6789"),
+        // Values that aren't longer than the characters to keep are masked entirely.
+        ("12345678", "********"),
+        ("abc", "***"),
+        ("", ""),
+        ("ÄÖÜßäöüß日本", "ÄÖÜß**üß日本"),
+    ] {
+        interpolation_map.insert("v", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected, "{input}");
+    }
+
+    let out = OutputTemplate::parse("{(mask(0, 2, '#')|red):v}").unwrap();
+    interpolation_map.insert("v", vec!["5551234"]);
+    assert_eq!(out.transform(&interpolation_map), "#####34".red().to_string());
+}
+
+#[test]
+fn test_output_template_bar() {
+    let out = OutputTemplate::parse("[{(bar(10, 200)):n}]").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected) in [
+        ("0", "[░░░░░░░░░░]"),
+        ("50", "[███░░░░░░░]"),
+        ("100", "[█████░░░░░]"),
+        ("200", "[██████████]"),
+        ("500", "[██████████]"),
+        ("-3", "[░░░░░░░░░░]"),
+        ("n/a", "[░░░░░░░░░░]"),
+    ] {
+        interpolation_map.insert("n", vec![input]);
+        assert_eq!(out.transform(&interpolation_map), expected, "{input}");
+    }
+
+    let out = OutputTemplate::parse("{(green|bar(4, 1)):n}").unwrap();
+    interpolation_map.insert("n", vec!["0.5"]);
+    assert_eq!(out.transform(&interpolation_map), "██░░".green().to_string());
+}
+
+#[test]
+fn test_output_template_attribute_phases() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("n", vec!["1234567"]);
+    let expected = "1,234".red().bold().to_string();
+
+    // Transforms precede width adjustments which precede styles regardless of the written order.
+    for template in [
+        "{(num(',', 0)|lfit(5)|red|bold):n}",
+        "{(red|bold|lfit(5)|num(',', 0)):n}",
+        "{(lfit(5)|red|num(',', 0)|bold):n}",
+    ] {
+        let out = OutputTemplate::parse(template).unwrap();
+        assert_eq!(out.transform(&interpolation_map), expected, "{template}");
+    }
+
+    // Within a phase, attributes apply in the order they are written.
+    let out = OutputTemplate::parse("[{(repeat(2)|bar(4, 100)):n}]").unwrap();
+    interpolation_map.insert("n", vec!["5"]);
+    assert_eq!(out.transform(&interpolation_map), "[██░░]");
+    let out = OutputTemplate::parse("[{(bar(4, 100)|repeat(2)):n}]").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "[░░░░░░░░]");
+}
+
+#[test]
+fn test_output_template_chain_across_phases() {
+    // Chains are decided in the written order even though 'num' is applied before 'red'.
+    let out = OutputTemplate::parse("{(?red('^1')|??num('.', ',', 0)):n}").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    interpolation_map.insert("n", vec!["1234"]);
+    assert_eq!(out.transform(&interpolation_map), "1234".red().to_string());
+
+    interpolation_map.insert("n", vec!["2345"]);
+    assert_eq!(out.transform(&interpolation_map), "2,345");
+}
+
+#[test]
+fn test_output_template_fit() {
+    let left = OutputTemplate::parse("[{(fit(6)):name}]").unwrap();
+    let right = OutputTemplate::parse("[{(rfit(6, 'ellipsis')):name}]").unwrap();
+    let center = OutputTemplate::parse("[{(cfit(6, ellipsis)):name}]").unwrap();
+    let mut interpolation_map = HashMap::new();
+
+    for (input, expected_left, expected_right, expected_center) in [
+        ("abc", "[abc   ]", "[   abc]", "[ abc  ]"),
+        ("abcdef", "[abcdef]", "[abcdef]", "[abcdef]"),
+        ("abcdefgh", "[abcdef]", "[abcde…]", "[abcde…]"),
+        ("日本語", "[日本語]", "[日本語]", "[日本語]"),
+        ("日本語です", "[日本語]", "[ 日本…]", "[日本… ]"),
+        ("e\u{301}tude", "[e\u{301}tude ]", "[ e\u{301}tude]", "[e\u{301}tude ]"),
+    ] {
+        interpolation_map.insert("name", vec![input]);
+        assert_eq!(left.transform(&interpolation_map), expected_left);
+        assert_eq!(right.transform(&interpolation_map), expected_right);
+        assert_eq!(center.transform(&interpolation_map), expected_center);
+    }
+
+    let out = OutputTemplate::parse("{(red|lfit(3)):name}").unwrap();
+    interpolation_map.insert("name", vec!["abcdef"]);
+    assert_eq!(out.transform(&interpolation_map), "abc".red().to_string());
+}
+
+#[test]
+fn test_output_template_doubled_braces() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("log", vec!["foo"]);
+
+    let out = OutputTemplate::parse("{{not an anchor}}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "{not an anchor}");
+
+    let out = OutputTemplate::parse("{{{log}}} {log}}}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), "{foo} foo}");
+
+    let out = OutputTemplate::parse(r"\{log\} {{log}}").unwrap();
+    assert_eq!(out.transform(&interpolation_map), r"\{log\} {log}");
+}
+
+#[test]
+fn test_output_template_namespaced_anchor_names() {
+    let mut out = OutputTemplate::parse("{http.status} {http-method} {@end:http.status || req:http-id}").unwrap();
+    out.resolve_capture_names(&["http_status", "http_method", "http_id"]);
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("http_status", vec!["200"]);
+    interpolation_map.insert("http_method", vec!["GET"]);
+    interpolation_map.insert("req:http_id", vec!["7"]);
+
+    assert_eq!(out.transform(&interpolation_map), "200 GET 7");
+    assert_eq!(
+        out.anchor_names().collect::<Vec<_>>(),
+        vec!["http_status", "http_method", "@end:http_status", "req:http_id"]
+    );
+
+    // Capture groups may be named with dots, in which case the name as written takes precedence.
+    let mut out = OutputTemplate::parse("{http.status} {http.method}").unwrap();
+    out.resolve_capture_names(&["http.status", "http_status", "http_method"]);
+    assert_eq!(
+        out.anchor_names().collect::<Vec<_>>(),
+        vec!["http.status", "http_method"]
+    );
+
+    let mut out = OutputTemplate::parse("{http.status}").unwrap();
+    out.resolve_capture_names(&["status"]);
+    assert_eq!(out.anchor_names().collect::<Vec<_>>(), vec!["http.status"]);
+}
+
+#[test]
+fn test_output_template_multibyte() {
+    let out = OutputTemplate::parse("λ→{foo} {(red):bar}…").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("foo", vec!["ƒ"]);
+    interpolation_map.insert("bar", vec!["b"]);
+
+    let resultant = out.transform(&interpolation_map);
+    assert_eq!(resultant, format!("λ→ƒ {}…", "b".red()));
+}
+
+#[test]
+fn test_output_template_transform_strict() {
+    let out = OutputTemplate::parse("{lvl} {msg || src || \"none\"} {ip || src}").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["info"]);
+    interpolation_map.insert("src", vec!["main"]);
+    assert_eq!(out.transform_strict(&interpolation_map).unwrap(), "info main main");
+
+    interpolation_map.insert("src", vec![]);
+    let err = out.transform_strict(&interpolation_map).unwrap_err();
+    assert_eq!(err.to_string(), "no match for anchor 'ip'");
+    assert_eq!(out.transform(&interpolation_map), "info none ");
+
+    let out = OutputTemplate::parse("{msg} {!lvl}").unwrap();
+    interpolation_map.insert("lvl", vec![]);
+    assert_eq!(out.transform_strict(&interpolation_map).unwrap(), "");
+}
+
+#[test]
+fn test_output_template_transform_with_report() {
+    let out = OutputTemplate::parse("{lvl} {msg || \"none\"} {ip}").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["info"]);
+
+    let (resultant, unmatched) = out.transform_with_report(&interpolation_map);
+    assert_eq!(resultant, "info none ");
+    assert_eq!(
+        unmatched,
+        vec![
+            UnmatchedAnchor {
+                name: "msg",
+                defaulted: true,
+                required: false
+            },
+            UnmatchedAnchor {
+                name: "ip",
+                defaulted: false,
+                required: false
+            },
+        ]
+    );
+
+    let out = OutputTemplate::parse("{ip} {!msg} {lvl}").unwrap();
+    let (resultant, unmatched) = out.transform_with_report(&interpolation_map);
+    assert_eq!(resultant, "");
+    assert_eq!(unmatched.len(), 2);
+    assert!(unmatched[1].required);
+}
+
+#[test]
+fn test_output_template_default_literal_special_chars() {
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("bar", vec!["b"]);
+
+    let out =
+        OutputTemplate::parse(r#"[{foo || "N/A (unknown)"}] [{foo || "}|{{"}] [{foo || ""}] [{foo || "\"{bar}\""}]"#)
+            .unwrap();
+    assert_eq!(out.transform(&interpolation_map), r#"[N/A (unknown)] [}|{] [] ["b"]"#);
+}
+
+#[test]
+fn test_output_template_interpolated_default() {
+    let out =
+        OutputTemplate::parse(r#"{status || "HTTP {code || 'unknown'}"} {{{(lalign(11)):msg || "{lvl}: none"}}}"#)
+            .unwrap();
+    assert_eq!(
+        out.anchor_names().collect::<Vec<_>>(),
+        vec!["status", "code", "msg", "lvl"]
+    );
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("code", vec!["404"]);
+    interpolation_map.insert("lvl", vec!["warn"]);
+    assert_eq!(out.transform(&interpolation_map), "HTTP 404 {warn: none }");
+
+    interpolation_map.insert("status", vec!["Not Found"]);
+    interpolation_map.insert("msg", vec!["gone"]);
+    assert_eq!(out.transform(&interpolation_map), "Not Found {gone       }");
+
+    let (resultant, unmatched) = out.transform_with_report(&HashMap::new());
+    assert_eq!(resultant, "HTTP unknown {: none     }");
+    assert!(unmatched.iter().all(|anchor| anchor.defaulted));
+}
+
+#[test]
+fn test_output_template_default_attributes() {
+    let out = OutputTemplate::parse(r#"{(green):status || (red):"DOWN"} {(bold):a || b || "none"}"#).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("status", vec!["UP"]);
+    interpolation_map.insert("b", vec!["bee"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!("{} {}", "UP".green(), "bee".bold())
+    );
+
+    let interpolation_map = HashMap::new();
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!("{} {}", "DOWN".red(), "none".bold())
+    );
+}
+
+#[test]
+fn test_output_template_conditional_env() {
+    std::env::set_var("GRITS_TEST_HIGHLIGHT", "(?i)^error$");
+    let out = OutputTemplate::parse("{(?red($GRITS_TEST_HIGHLIGHT)):lvl} {(?red($GRITS_TEST_UNSET)):lvl}").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["ERROR"]);
+    assert_eq!(out.transform(&interpolation_map), format!("{} ERROR", "ERROR".red()));
+
+    interpolation_map.insert("lvl", vec!["INFO"]);
+    assert_eq!(out.transform(&interpolation_map), "INFO INFO");
+}
+
+#[test]
+fn test_output_template_conditional_scale() {
+    let out =
+        OutputTemplate::parse(r"{(?green('^\d{1,2}ms$')|??yellow('^\d{3}ms$')|??red('.')|bold):latency}").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    for (latency, expected) in [
+        ("42ms", "42ms".green().bold()),
+        ("250ms", "250ms".yellow().bold()),
+        ("1200ms", "1200ms".red().bold()),
+    ] {
+        interpolation_map.insert("latency", vec![latency]);
+        assert_eq!(out.transform(&interpolation_map), format!("{expected}"));
+    }
+
+    // Without chaining, every matching conditional applies.
+    let out = OutputTemplate::parse(r"{(?green('ms$')|?underlined('^\d{3}ms$')):latency}").unwrap();
+    interpolation_map.insert("latency", vec!["250ms"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!("{}", "250ms".green().underlined())
+    );
+}
+
+#[test]
+fn test_output_template_without_color() {
+    let out = OutputTemplate::parse(r#"{(red|bold|ralign(6)):lvl} {(?green('^\d+$')|num(',')):count || (cyan):"?"}"#)
+        .unwrap()
+        .with_color(false);
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["WARN"]);
+    interpolation_map.insert("count", vec!["1234"]);
+    assert_eq!(out.transform(&interpolation_map), "  WARN 1,234");
+
+    interpolation_map.remove("count");
+    assert_eq!(out.transform(&interpolation_map), "  WARN ?");
+}
+
+#[test]
+fn test_output_template_without_color_nested_default() {
+    let out = OutputTemplate::parse(r#"{a || "<{(red):b}>"}"#)
+        .unwrap()
+        .with_color(false);
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("b", vec!["bee"]);
+    assert_eq!(out.transform(&interpolation_map), "<bee>");
+}
+
+#[test]
+fn test_output_template_shared_slots() {
+    let out = OutputTemplate::parse("{a} {(red):a} {b[-1]} {a[1] || b} {c}").unwrap();
+    assert_eq!(out.slots, 3);
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("a", vec!["a0"]);
+    interpolation_map.insert("b", vec!["b0", "b1"]);
+    assert_eq!(out.transform(&interpolation_map), format!("a0 {} b1 b0 ", "a0".red()));
+}
+
+#[test]
+fn test_output_template_colors() {
+    let out = OutputTemplate::parse("{(white|bg_rgb(40, 40, 40)):key} {(color256(208)):key}").unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("key", vec!["k"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!(
+            "{} {}",
+            "k".white().on(Color::Rgb { r: 40, g: 40, b: 40 }),
+            "k".with(Color::AnsiValue(208))
+        )
+    );
+}
+
+#[test]
+fn test_output_template_aliases() {
+    let aliases = Aliases::parse(&[
+        "error=red|bold",
+        "alert = @error|underlined",
+        "cell=?cyan('a|b')|lalign(4)",
+    ])
+    .unwrap();
+    let out =
+        OutputTemplate::parse_with_aliases("{(@error):lvl} {(@alert|bg_white):lvl} {(@cell):x}", &aliases).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["ERR"]);
+    interpolation_map.insert("x", vec!["a|b"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!(
+            "{} {} {}",
+            "ERR".red().bold(),
+            "ERR".red().bold().underlined().on_white(),
+            "a|b ".cyan()
+        )
+    );
+
+    let err = OutputTemplate::parse_with_aliases("{(red| @warn):lvl}", &aliases).unwrap_err();
+    let err = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(err.kind(), ParseErrorKind::UndefinedAlias);
+    assert_eq!(err.char_index(), 7);
+    assert_eq!(err.message(), "Undefined attribute alias '@warn'.");
+
+    let err = OutputTemplate::parse_with_aliases("{(@error(1)):lvl}", &aliases).unwrap_err();
+    let err = err.downcast_ref::<ParseError>().unwrap();
+    assert_eq!(err.kind(), ParseErrorKind::AliasWithArguments);
+    assert_eq!(err.char_index(), 2);
+    assert_eq!(err.message(), "Attribute alias '@error' doesn't take any arguments.");
+    assert!(OutputTemplate::parse("{(@error):lvl}").is_err());
+}
+
+#[test]
+fn test_aliases_errors() {
+    let err = |definitions: &[&str]| Aliases::parse(definitions).unwrap_err().to_string();
+
+    assert_eq!(
+        err(&["a=@b|bold", "b=red|@c", "c=@b"]),
+        "alias is defined recursively: @b -> @c -> @b"
+    );
+    assert_eq!(err(&["a=@a"]), "alias is defined recursively: @a -> @a");
+    assert_eq!(
+        err(&["a=red|@missing"]),
+        "alias '@a' references an undefined alias '@missing'"
+    );
+    assert_eq!(err(&["a=red", "a=blue"]), "alias '@a' is defined more than once");
+    assert!(Aliases::parse(&["a"]).is_err());
+    assert!(Aliases::parse(&["a b=red"]).is_err());
+    assert!(Aliases::parse(&["a=purple"]).is_err());
+}
+
+#[test]
+fn test_output_template_custom_anchor_delimiters() {
+    let aliases = Aliases::default();
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("lvl", vec!["info"]);
+
+    let options = ParseOptions::new(&aliases).with_anchor_delimiters('<', '>');
+    let out = OutputTemplate::parse_with_options(r#"{"lvl": "<lvl>", "op": "<<=>>"}"#, options).unwrap();
+    assert_eq!(out.transform(&interpolation_map), r#"{"lvl": "info", "op": "<=>"}"#);
+
+    let options = ParseOptions::new(&aliases).with_anchor_delimiters('%', '%');
+    let out = OutputTemplate::parse_with_options("%lvl% at 100%% {lvl}", options).unwrap();
+    assert_eq!(out.transform(&interpolation_map), "info at 100% {lvl}");
+}
+
+#[test]
+fn test_output_template_custom_escape() {
+    let aliases = Aliases::parse_with_escape(&["quoted=?red('it#'s')"], '#').unwrap();
+    let options = ParseOptions::new(&aliases).with_escape('#');
+    let out = OutputTemplate::parse_with_options(r"C:\{word} #{word} {(@quoted):word}", options).unwrap();
+
+    let mut interpolation_map = HashMap::new();
+    interpolation_map.insert("word", vec!["it's"]);
+    assert_eq!(
+        out.transform(&interpolation_map),
+        format!(r"C:\it's #{{word}} {}", "it's".red())
+    );
+}
@@ -3,7 +3,11 @@ pub const ANCHOR_OPEN: char = '{';
 pub const ANCHOR_CLOSE: char = '}';
 pub const INDEX_OPEN: char = '[';
 pub const INDEX_CLOSE: char = ']';
+pub const INDEX_ALL: char = '*';
+pub const INDEX_SEPARATOR: char = ':';
+pub const INDEX_RANGE: &str = "..";
 pub const DEFAULT_PIPE: char = '|';
+pub const DEFAULT_BLANK: char = '?';
 pub const LITERAL_DOUBLE_QUOTE: char = '"';
 pub const LITERAL_SINGLE_QUOTE: char = '\'';
 pub const ATTRIBUTE_OPEN: char = '(';
@@ -14,3 +18,26 @@ pub const PARAM_DELIMETER: char = ',';
 pub const ATTRIBUTE_DELIMETER: char = '|';
 pub const ATTRIBUTE_END: char = ':';
 pub const REQUIRED: char = '!';
+pub const ENV_VAR: char = '$';
+pub const COUNT: char = '#';
+
+/// Characters with special meaning within templates, none of which can serve as the escape.
+pub const RESERVED: [char; 17] = [
+    ANCHOR_OPEN,
+    ANCHOR_CLOSE,
+    INDEX_OPEN,
+    INDEX_CLOSE,
+    INDEX_ALL,
+    INDEX_SEPARATOR,
+    '.',
+    DEFAULT_PIPE,
+    DEFAULT_BLANK,
+    LITERAL_DOUBLE_QUOTE,
+    LITERAL_SINGLE_QUOTE,
+    ATTRIBUTE_OPEN,
+    ATTRIBUTE_CLOSE,
+    PARAM_DELIMETER,
+    REQUIRED,
+    ENV_VAR,
+    COUNT,
+];
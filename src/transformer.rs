@@ -0,0 +1,225 @@
+use crate::{
+    captures::Captures,
+    cli::RequireMode,
+    line::{passes_require, render_templates},
+    pattern::{CapturesBuffer, Flags, Patterns},
+    template::OutputTemplate,
+};
+use anyhow::{format_err, Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Applies regular expressions with named captures to individual lines and transforms them using
+/// templates, exactly as the command-line application does but without any IO. A [Transformer]
+/// is constructed once and can then be used to transform any number of lines.
+///
+/// ```
+/// use grits::{RequireMode, Transformer};
+///
+/// let transformer = Transformer::new(
+///     &[r"level=(?<lvl>\w+)", r"msg=(?<msg>\w+)"],
+///     &["[{lvl}]", "{msg}"],
+/// )
+/// .unwrap()
+/// .with_separator(" ")
+/// .with_require(&["msg"], RequireMode::All);
+///
+/// assert_eq!(
+///     transformer.transform_line("level=info msg=hello"),
+///     Some(String::from("[info] hello"))
+/// );
+/// assert_eq!(transformer.transform_line("level=info"), None);
+/// ```
+#[derive(Debug)]
+pub struct Transformer {
+    patterns: Patterns,
+    templates: Vec<OutputTemplate>,
+    separators: Vec<String>,
+    require: Vec<String>,
+    require_mode: RequireMode,
+}
+
+impl Transformer {
+    /// Compiles the regular expressions in `patterns` and parses the `templates`. Fails if any
+    /// pattern or template is invalid or if none of the patterns contain named capture groups and
+    /// none of the templates reference capture groups by index, e.g. `{$1}`.
+    pub fn new<P: AsRef<str>, T: AsRef<str>>(patterns: &[P], templates: &[T]) -> Result<Self> {
+        let mut regexes = Vec::with_capacity(patterns.len());
+
+        for pat in patterns {
+            let pat = pat.as_ref();
+            let regex = Regex::new(pat).with_context(|| format!("encountered invalid regular expression: {pat}"))?;
+            regexes.push(regex);
+        }
+
+        let group_names = regexes
+            .iter()
+            .flat_map(|re| re.capture_names().flatten())
+            .collect::<Vec<_>>();
+
+        let mut output_templates = Vec::with_capacity(templates.len());
+        for templ in templates {
+            let mut output_template = OutputTemplate::parse(templ.as_ref())?;
+            output_template.resolve_capture_names(&group_names);
+            output_templates.push(output_template);
+        }
+
+        let anchor_names = output_templates
+            .iter()
+            .flat_map(OutputTemplate::anchor_names)
+            .collect::<Vec<_>>();
+        let patterns = Patterns::new(regexes, Flags::default())
+            .with_positional_groups(anchor_names.iter().copied())
+            .with_line_capture(anchor_names.iter().copied());
+
+        if patterns.capture_names().is_empty() {
+            return Err(format_err!(
+                "none of the provided patterns contained named capture groups"
+            ));
+        }
+
+        Ok(Self::from_parts(patterns, output_templates))
+    }
+
+    /// Assembles a transformer from `patterns` and `templates` that have already been compiled and
+    /// parsed. Unlike [new](Transformer::new), the patterns aren't checked for named capture groups.
+    pub(crate) fn from_parts(patterns: Patterns, templates: Vec<OutputTemplate>) -> Self {
+        Self {
+            patterns,
+            templates,
+            separators: Vec::new(),
+            require: Vec::new(),
+            require_mode: RequireMode::default(),
+        }
+    }
+
+    /// Sets the separator used to join the results of each template. Defaults to an empty string.
+    pub fn with_separator(mut self, separator: &str) -> Self {
+        self.separators = vec![separator.to_string()];
+        self
+    }
+
+    /// Sets the separators used to join the results of each template where the Nth separator is
+    /// placed between the results of the Nth and following template. The last separator is used
+    /// for any remaining templates.
+    ///
+    /// ```
+    /// use grits::Transformer;
+    ///
+    /// let transformer = Transformer::new(&[r"(?<a>a)(?<b>b)(?<c>c)(?<d>d)"], &["{a}", "{b}", "{c}", "{d}"])
+    ///     .unwrap()
+    ///     .with_separators(&[" ", "\t"]);
+    ///
+    /// assert_eq!(transformer.transform_line("abcd"), Some(String::from("a b\tc\td")));
+    /// ```
+    pub fn with_separators<S: AsRef<str>>(mut self, separators: &[S]) -> Self {
+        self.separators = separators.iter().map(|s| s.as_ref().to_string()).collect();
+        self
+    }
+
+    /// Sets the capture names that must have a match for a line to be transformed according to
+    /// `require_mode`.
+    pub fn with_require<S: AsRef<str>>(mut self, require: &[S], require_mode: RequireMode) -> Self {
+        self.require = require.iter().map(|r| r.as_ref().to_string()).collect();
+        self.require_mode = require_mode;
+        self
+    }
+
+    /// Whether or not to use a [RegexSet](regex::RegexSet) to determine which patterns match a
+    /// line before extracting captures. Enabled by default as it significantly speeds up
+    /// processing when there are many patterns that mostly don't match.
+    pub fn with_prefilter(mut self, prefilter: bool) -> Self {
+        self.patterns = self.patterns.with_prefilter(prefilter);
+        self
+    }
+
+    /// Transforms a single `line`, returning `None` if the line doesn't satisfy the required
+    /// captures or if the transformation produces no output.
+    pub fn transform_line(&self, line: &str) -> Option<String> {
+        let mut buffer = CapturesBuffer::new(&self.patterns);
+        self.patterns.populate(line, &mut buffer);
+        self.render(&buffer.captures(line))
+    }
+
+    /// Transforms each of the `lines` lazily, yielding the same results as calling
+    /// [transform_line](Transformer::transform_line) on each of them. Prefer this when
+    /// transforming many lines as the storage for captures is reused from one line to the next.
+    ///
+    /// ```
+    /// use grits::Transformer;
+    ///
+    /// let transformer = Transformer::new(&[r"level=(?<lvl>\w+)"], &["[{lvl}]"]).unwrap();
+    /// let out = transformer.transform_lines(["level=info", "level=warn"]).collect::<Vec<_>>();
+    /// assert_eq!(out, vec![Some(String::from("[info]")), Some(String::from("[warn]"))]);
+    /// ```
+    pub fn transform_lines<'a, I>(&'a self, lines: I) -> impl Iterator<Item = Option<String>> + 'a
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+        I::IntoIter: 'a,
+    {
+        let mut buffer = CapturesBuffer::new(&self.patterns);
+
+        lines.into_iter().map(move |line| {
+            let line = line.as_ref();
+
+            self.patterns.populate(line, &mut buffer);
+            self.render(&buffer.captures(line))
+        })
+    }
+
+    /// Applies the patterns to a single `line` and returns the matches of every named capture
+    /// group, in the order they were matched, without rendering any templates. Capture names
+    /// without a match map to an empty vector. Required captures are not considered.
+    ///
+    /// ```
+    /// use grits::Transformer;
+    ///
+    /// let transformer = Transformer::new(&[r"ip=(?<ip>[^ ]+)"], &[] as &[&str]).unwrap();
+    /// let captures = transformer.capture_line("ip=127.0.0.1 ip=10.0.0.1");
+    /// assert_eq!(captures["ip"], vec!["127.0.0.1", "10.0.0.1"]);
+    /// ```
+    pub fn capture_line(&self, line: &str) -> HashMap<String, Vec<String>> {
+        let mut buffer = CapturesBuffer::new(&self.patterns);
+        self.patterns.populate(line, &mut buffer);
+
+        buffer.captures(line).to_map()
+    }
+
+    /// The compiled patterns that are applied to each line.
+    pub(crate) fn patterns(&self) -> &Patterns {
+        &self.patterns
+    }
+
+    /// The parsed templates used to render each line.
+    pub(crate) fn templates(&self) -> &[OutputTemplate] {
+        &self.templates
+    }
+
+    /// Whether the `captures_map` of a single line satisfies the required captures.
+    pub(crate) fn passes_require(&self, captures_map: &dyn Captures) -> bool {
+        passes_require(captures_map, &self.require, self.require_mode)
+    }
+
+    /// Renders the templates using the `captures_map` of a single line and joins the results with
+    /// the separators, regardless of the required captures. See
+    /// [OutputTemplate::transform_strict] for how `strict` affects rendering.
+    pub(crate) fn render_templates(&self, captures_map: &dyn Captures, strict: bool) -> Result<String> {
+        render_templates(&self.templates, &self.separators, captures_map, strict)
+    }
+
+    /// Renders the templates using the `captures_map` of a single line.
+    fn render(&self, captures_map: &dyn Captures) -> Option<String> {
+        if !self.passes_require(captures_map) {
+            return None;
+        }
+
+        // Rendering can only fail when strict.
+        let out = self.render_templates(captures_map, false).ok()?;
+
+        if out.is_empty() {
+            return None;
+        }
+        Some(out)
+    }
+}
@@ -0,0 +1,763 @@
+use crate::{
+    cli::{ColorChoice, Theme},
+    template::{error::ParseError, parse::attr::display_width},
+};
+use anyhow::{Context, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, stderr, stdout, IsTerminal, LineWriter, Stderr, Stdout, Write},
+    ops::Drop,
+    time::{Duration, Instant},
+};
+
+#[cfg(test)]
+mod test;
+
+/// The output file path that stands for stdout.
+pub const STDOUT_PATH: &str = "-";
+
+/// Traditional block size in bytes, used as the default buffer size of [BlockBufferedOutputWriter].
+pub const BLOCK_SIZE: usize = 512;
+
+/// Delimits the columns of output written to an [AlignedOutputWriter].
+pub const COLUMN_DELIMITER: char = '\x1f';
+
+/// ANSI-escape sequences for the bold red and yellow used to style errors and warnings
+/// respectively on dark backgrounds.
+const DARK_ERROR_STYLE: &str = "\x1b[1;31m";
+const DARK_WARNING_STYLE: &str = "\x1b[1;33m";
+
+/// ANSI-escape sequences for the darker bold red and orange used to style errors and warnings
+/// respectively on light backgrounds, against which yellow in particular is hard to read.
+const LIGHT_ERROR_STYLE: &str = "\x1b[1;38;5;124m";
+const LIGHT_WARNING_STYLE: &str = "\x1b[1;38;5;130m";
+
+/// ANSI-escape sequence that resets all styles.
+const RESET_STYLE: &str = "\x1b[0m";
+
+/// Entry-point for the rest of the application to access handlers
+/// to stdout and stderr. Also enables/disable colorization for the
+/// application based on '--color', whether stdout is a tty, and the
+/// `NO_COLOR`, `CLICOLOR_FORCE`, and `CLICOLOR` environment variables.
+/// See [color_enabled_for] for their precedence. Errors are colorized
+/// independently based on whether stderr is a tty and styled according to
+/// '--theme'.
+#[derive(Debug)]
+pub struct TtyContext {
+    pub stdout: Stdout,
+    pub stderr: Stderr,
+    stdout_color: bool,
+    stderr_color: bool,
+    theme: Theme,
+}
+
+/// Contains behavior to write to output.
+pub trait OutputWriter {
+    /// Writes `txt` followed by the record terminator which is a newline by default.
+    fn writeln(&mut self, txt: &str) -> Result<()>;
+
+    /// Like [writeln](OutputWriter::writeln) but `txt` is ordered by `key` rather than by itself
+    /// for writers that order their output. Other writers ignore `key`.
+    fn writeln_keyed(&mut self, key: &str, txt: &str) -> Result<()> {
+        let _ = key;
+        self.writeln(txt)
+    }
+
+    /// Writes output that is merely buffered rather than held back on purpose, e.g. for sorting.
+    fn flush_buffered(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// When buffered output is due to be flushed according to a flush interval, if there is any.
+    fn flush_deadline(&self) -> Option<Instant> {
+        None
+    }
+}
+
+impl<W: OutputWriter + ?Sized> OutputWriter for Box<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        (**self).writeln(txt)
+    }
+
+    fn writeln_keyed(&mut self, key: &str, txt: &str) -> Result<()> {
+        (**self).writeln_keyed(key, txt)
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        (**self).flush_buffered()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        (**self).flush_deadline()
+    }
+}
+
+/// Writes directly to `inner`, typically stdout, in a line-buffered manner. If `flush_each` is
+/// enabled then `inner` is flushed after every record rather than relying on it to flush on its own.
+/// If `trailing_terminator` is disabled then the terminator is written between records rather than
+/// after each one so that the final record isn't terminated.
+pub struct LineBufferedOutputWriter<W: Write> {
+    inner: W,
+    terminator: u8,
+    flush_each: bool,
+    trailing_terminator: bool,
+    written: bool,
+}
+
+/// Writes to `inner`, typically stdout, in a block-buffered manner. Any contents that remain in
+/// the buffer that weren't manually flushed will be flushed when dropped.
+pub struct BlockBufferedOutputWriter<W: Write> {
+    inner: W,
+    buffer: Vec<u8>,
+    buffer_size: usize,
+    terminator: u8,
+    trailing_terminator: bool,
+    written: bool,
+    /// If set, buffered output is flushed once it has been buffered this long.
+    flush_interval: Option<Duration>,
+    /// When the oldest output record that is still buffered was written.
+    buffered_since: Option<Instant>,
+}
+
+/// How output is buffered: whether it is line-buffered, flushed after every record, the number of
+/// bytes to buffer when block-buffered, and how long block-buffered output may be held back.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Buffering {
+    pub line_buffered: bool,
+    pub unbuffered: bool,
+    pub buffer_size: usize,
+    pub flush_interval: Option<Duration>,
+}
+
+/// Wraps another [OutputWriter] and suppresses output that is identical to the output written
+/// immediately before it, akin to `uniq`. Only the previous output is retained. If `count` is
+/// enabled then each output is held back until a different output arrives so that the number of
+/// times it was repeated can be prefixed to it. Held back output is written when dropped.
+pub struct UniqueOutputWriter<W: OutputWriter> {
+    inner: W,
+    previous: Option<String>,
+    repeats: usize,
+    count: bool,
+}
+
+/// Wraps another [OutputWriter] and holds back all output until dropped at which point it is
+/// written in sorted order, akin to `sort`. Output is ordered by its key if it was written with
+/// one, otherwise by the output itself. Output that compares equal retains the order it was
+/// written in.
+pub struct SortedOutputWriter<W: OutputWriter> {
+    inner: W,
+    records: Vec<(Option<String>, String)>,
+    reverse: bool,
+}
+
+/// Wraps another [OutputWriter] and holds back all output until dropped at which point it is
+/// written with its columns aligned, akin to `column -t`. The columns of each output are delimited
+/// by [COLUMN_DELIMITER] and every column but the last is padded to the display width of the
+/// widest value in that column amongst all output. The Nth separator is placed after the Nth
+/// column, with the last separator used for any remaining columns.
+pub struct AlignedOutputWriter<W: OutputWriter> {
+    inner: W,
+    rows: Vec<String>,
+    separators: Vec<String>,
+}
+
+impl Buffering {
+    /// Whether output is block-buffered when written to a file if `to_file` and to stdout
+    /// otherwise, see [init_output_writer].
+    pub fn is_block_buffered(&self, tty: &TtyContext, to_file: bool) -> bool {
+        !(self.line_buffered || self.unbuffered || !to_file && tty.stdout.is_terminal())
+    }
+}
+
+impl Default for TtyContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a [LineBufferedOutputWriter] if stdout is a terminal or if `buffering` is line-buffered
+/// or unbuffered, otherwise returns a [BlockBufferedOutputWriter] that buffers up to the buffer size
+/// of `buffering`. Output is written to `output_file` if provided rather than stdout. If unbuffered
+/// then output is flushed after every output record. Each output record is terminated by
+/// `terminator`, except for the final one if `trailing_terminator` is `false`.
+pub fn init_output_writer(
+    tty: &TtyContext,
+    output_file: Option<File>,
+    buffering: Buffering,
+    terminator: u8,
+    trailing_terminator: bool,
+) -> Box<dyn OutputWriter> {
+    let Some(file) = output_file else {
+        let buffering = Buffering {
+            line_buffered: tty.stdout.is_terminal() || buffering.line_buffered,
+            ..buffering
+        };
+        return buffered_writer(tty.stdout.lock(), buffering, terminator, trailing_terminator);
+    };
+    // Unlike stdout, files aren't line-buffered on their own.
+    if buffering.line_buffered || buffering.unbuffered {
+        let buffering = Buffering {
+            line_buffered: true,
+            ..buffering
+        };
+        return buffered_writer(LineWriter::new(file), buffering, terminator, trailing_terminator);
+    }
+    buffered_writer(file, buffering, terminator, trailing_terminator)
+}
+
+/// Wraps `inner` in a [LineBufferedOutputWriter] if `buffering` is line-buffered or unbuffered and
+/// a [BlockBufferedOutputWriter] otherwise. See [init_output_writer].
+fn buffered_writer<W: Write + 'static>(
+    inner: W,
+    buffering: Buffering,
+    terminator: u8,
+    trailing_terminator: bool,
+) -> Box<dyn OutputWriter> {
+    let Buffering {
+        line_buffered,
+        unbuffered,
+        buffer_size,
+        flush_interval,
+    } = buffering;
+
+    if line_buffered || unbuffered {
+        log::debug!("line buffered, flushing each record: {unbuffered}");
+        return Box::new(
+            LineBufferedOutputWriter::new(inner)
+                .with_terminator(terminator)
+                .with_trailing_terminator(trailing_terminator)
+                .with_flush_each(unbuffered),
+        );
+    }
+    log::debug!("block buffered with a buffer size of {buffer_size} and a flush interval of {flush_interval:?}");
+    Box::new(
+        BlockBufferedOutputWriter::new(inner)
+            .with_buffer_size(buffer_size)
+            .with_flush_interval(flush_interval)
+            .with_terminator(terminator)
+            .with_trailing_terminator(trailing_terminator),
+    )
+}
+
+/// Opens the file at `path` that output is written to, creating it if it doesn't exist. The file is
+/// appended to if `append` and truncated otherwise. Returns `None` if `path` is `-` which stands
+/// for stdout.
+pub fn open_output_file(path: &str, append: bool) -> Result<Option<File>> {
+    if path == STDOUT_PATH {
+        return Ok(None);
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .with_context(|| format!("failed to open output file: {path}"))?;
+    Ok(Some(file))
+}
+
+impl TtyContext {
+    pub fn new() -> Self {
+        let mut tty = Self {
+            stdout: stdout(),
+            stderr: stderr(),
+            stdout_color: false,
+            stderr_color: false,
+            theme: Theme::default(),
+        };
+        tty.set_color_choice(ColorChoice::Auto);
+        tty
+    }
+
+    /// Decides whether stdout and stderr are colorized based on `choice` along with whether each
+    /// is a tty and the environment. See [color_enabled_for].
+    pub fn set_color_choice(&mut self, choice: ColorChoice) {
+        let var = |name: &str| std::env::var(name).ok();
+
+        let stdout_is_terminal = self.stdout.is_terminal();
+        log::debug!("stdout is terminal: {stdout_is_terminal}");
+
+        self.stdout_color = color_enabled_for(choice, stdout_is_terminal, var);
+        log::debug!("color enabled: {}", self.stdout_color);
+        crossterm::style::force_color_output(self.stdout_color);
+
+        self.stderr_color = color_enabled_for(choice, self.stderr.is_terminal(), var);
+        log::debug!("stderr color enabled: {}", self.stderr_color);
+    }
+
+    /// Sets the theme that errors and warnings written to stderr are styled with.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// The theme that errors and warnings are styled with if stderr is colorized.
+    fn stderr_theme(&self) -> Option<Theme> {
+        self.stderr_color.then_some(self.theme)
+    }
+
+    /// Whether output written to stdout is colorized. See [color_enabled_for].
+    pub fn color_enabled(&self) -> bool {
+        self.stdout_color
+    }
+
+    /// An [OutputWriter] backed by stderr for output that is routed there rather than stdout. Like
+    /// stderr itself, output isn't buffered beyond a single record so that it interleaves with
+    /// warnings and errors. Each output record is terminated by `terminator`, except for the final
+    /// one if `trailing_terminator` is `false`.
+    pub fn stderr_writer(&self, terminator: u8, trailing_terminator: bool) -> Box<dyn OutputWriter> {
+        Box::new(
+            LineBufferedOutputWriter::new(self.stderr.lock())
+                .with_terminator(terminator)
+                .with_trailing_terminator(trailing_terminator),
+        )
+    }
+
+    /// Writes a warning to stderr that doesn't interrupt processing. Only the label is colorized,
+    /// and only if stderr is colorized.
+    pub fn write_warning(&mut self, warning: &str) -> Result<()> {
+        let label = warning_style("warning:", self.stderr_theme());
+        writeln!(self.stderr, "{label} {warning}")?;
+        Ok(())
+    }
+
+    /// Writes `err` to stderr. Template parse errors have the position of the error and their
+    /// message colorized whereas other errors are colorized in their entirety, but only if stderr
+    /// is colorized. See [color_enabled_for].
+    pub fn write_err(&mut self, err: &anyhow::Error) -> Result<()> {
+        let theme = self.stderr_theme();
+        let out = match err.downcast_ref::<ParseError>() {
+            Some(parse_err) => parse_err.render(theme),
+            None => error_style(&format!("{err:?}"), theme),
+        };
+        writeln!(self.stderr, "{}", out)?;
+        Ok(())
+    }
+}
+
+/// Whether a stream is colorized given `choice`, whether the stream `is_terminal`, and environment
+/// variables as looked up by `var`. In order of precedence:
+///
+/// 1. '--color always' or '--color never'.
+/// 2. A non-blank `NO_COLOR` disables colorization.
+/// 3. A `CLICOLOR_FORCE` that is neither blank nor `0` enables colorization.
+/// 4. A `CLICOLOR` of `0` disables colorization.
+/// 5. Otherwise the stream is colorized if it is a terminal.
+pub fn color_enabled_for<F>(choice: ColorChoice, is_terminal: bool, var: F) -> bool
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match choice {
+        ColorChoice::Always => return true,
+        ColorChoice::Never => return false,
+        ColorChoice::Auto => (),
+    }
+    let set = |name: &str| var(name).filter(|val| !val.is_empty());
+
+    if set("NO_COLOR").is_some() {
+        return false;
+    }
+    if set("CLICOLOR_FORCE").is_some_and(|val| val != "0") {
+        return true;
+    }
+    if set("CLICOLOR").is_some_and(|val| val == "0") {
+        return false;
+    }
+    is_terminal
+}
+
+/// Whether or not `err` was caused by writing to a pipe whose reading end was closed, such as when
+/// output is piped into `head`. This is how well-behaved Unix tools learn that they should stop
+/// rather than a failure.
+pub fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|io_err| io_err.kind() == io::ErrorKind::BrokenPipe)
+}
+
+/// Styles `txt` as an error, in bold red, for `theme` if there is one. This doesn't rely on
+/// crossterm as its colorization is toggled globally based on stdout which may not be a tty when
+/// stderr is.
+pub fn error_style(txt: &str, theme: Option<Theme>) -> String {
+    let style = match theme {
+        Some(Theme::Dark) => DARK_ERROR_STYLE,
+        Some(Theme::Light) => LIGHT_ERROR_STYLE,
+        None => return txt.to_string(),
+    };
+    styled(txt, style)
+}
+
+/// Styles `txt` as a warning, in bold yellow or orange, for `theme` if there is one. See
+/// [error_style].
+pub fn warning_style(txt: &str, theme: Option<Theme>) -> String {
+    let style = match theme {
+        Some(Theme::Dark) => DARK_WARNING_STYLE,
+        Some(Theme::Light) => LIGHT_WARNING_STYLE,
+        None => return txt.to_string(),
+    };
+    styled(txt, style)
+}
+
+fn styled(txt: &str, style: &str) -> String {
+    if txt.is_empty() {
+        return txt.to_string();
+    }
+    format!("{style}{txt}{RESET_STYLE}")
+}
+
+impl<W: Write> LineBufferedOutputWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            terminator: b'\n',
+            flush_each: false,
+            trailing_terminator: true,
+            written: false,
+        }
+    }
+
+    /// Terminate each output record with `terminator` rather than a newline.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Omit the terminator after the final output record if `trailing_terminator` is `false`.
+    pub fn with_trailing_terminator(mut self, trailing_terminator: bool) -> Self {
+        self.trailing_terminator = trailing_terminator;
+        self
+    }
+
+    /// Flush `inner` after every output record if `flush_each` is `true`.
+    pub fn with_flush_each(mut self, flush_each: bool) -> Self {
+        self.flush_each = flush_each;
+        self
+    }
+}
+
+impl<W: Write> BlockBufferedOutputWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buffer: Vec::with_capacity(BLOCK_SIZE),
+            buffer_size: BLOCK_SIZE,
+            terminator: b'\n',
+            trailing_terminator: true,
+            written: false,
+            flush_interval: None,
+            buffered_since: None,
+        }
+    }
+
+    /// Buffer up to `buffer_size` bytes rather than [BLOCK_SIZE] before writing. Output records
+    /// larger than the buffer are written in their entirety.
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer = Vec::with_capacity(buffer_size);
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Terminate each output record with `terminator` rather than a newline.
+    pub fn with_terminator(mut self, terminator: u8) -> Self {
+        self.terminator = terminator;
+        self
+    }
+
+    /// Omit the terminator after the final output record if `trailing_terminator` is `false`.
+    pub fn with_trailing_terminator(mut self, trailing_terminator: bool) -> Self {
+        self.trailing_terminator = trailing_terminator;
+        self
+    }
+
+    /// Also flush the buffer, along with `inner`, once output has been buffered for
+    /// `flush_interval` so that sparse output isn't held back for long. The interval is checked as
+    /// output records are written; see [OutputWriter::flush_deadline] for flushing in between.
+    pub fn with_flush_interval(mut self, flush_interval: Option<Duration>) -> Self {
+        self.flush_interval = flush_interval;
+        self
+    }
+
+    /// Flushes and clears the buffer.
+    fn flush_buffer(&mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            self.inner
+                .write_all(&self.buffer)
+                .context("failed to write buffered output")?;
+            self.buffer.clear();
+        }
+        self.buffered_since = None;
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputWriter for LineBufferedOutputWriter<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        let mut write = || -> std::io::Result<()> {
+            if self.trailing_terminator {
+                self.inner.write_all(txt.as_bytes())?;
+                self.inner.write_all(&[self.terminator])?;
+            } else {
+                if self.written {
+                    self.inner.write_all(&[self.terminator])?;
+                }
+                self.inner.write_all(txt.as_bytes())?;
+            }
+
+            // Stdout, like any line-buffered writer, only flushes on its own when it encounters a
+            // newline, which without a trailing terminator precedes rather than follows the record.
+            if self.flush_each || self.terminator != b'\n' || !self.trailing_terminator {
+                self.inner.flush()?;
+            }
+            Ok(())
+        };
+        write().context("something went wrong while trying to write output")?;
+        self.written = true;
+        Ok(())
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.inner.flush().context("failed to flush output")
+    }
+}
+
+impl<W: Write> OutputWriter for BlockBufferedOutputWriter<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        let txt_bytes = txt.as_bytes();
+
+        if self.buffer.len() + txt_bytes.len() + 1 > self.buffer_size {
+            self.flush_buffer()?;
+        }
+        // A record that is larger than the buffer is appended to the now empty buffer as a whole
+        // and flushed immediately below.
+        if self.trailing_terminator {
+            self.buffer.extend_from_slice(txt_bytes);
+            self.buffer.push(self.terminator);
+        } else {
+            if self.written {
+                self.buffer.push(self.terminator);
+            }
+            self.buffer.extend_from_slice(txt_bytes);
+        }
+        self.written = true;
+
+        if self.buffer.len() >= self.buffer_size {
+            self.flush_buffer()?;
+        } else if self.flush_interval.is_some() {
+            self.buffered_since.get_or_insert_with(Instant::now);
+
+            if self.flush_deadline().is_some_and(|deadline| deadline <= Instant::now()) {
+                self.flush_buffered()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.flush_buffer()?;
+        // Stdout would otherwise hold back whatever follows the last newline.
+        self.inner.flush().context("failed to flush buffered output")
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        Some(self.buffered_since? + self.flush_interval?)
+    }
+}
+
+impl<W: OutputWriter> UniqueOutputWriter<W> {
+    pub fn new(inner: W, count: bool) -> Self {
+        Self {
+            inner,
+            previous: None,
+            repeats: 0,
+            count,
+        }
+    }
+
+    /// Writes the held back output, if any, prefixed by the number of times it was repeated.
+    fn flush_previous(&mut self) -> Result<()> {
+        if !self.count {
+            return Ok(());
+        }
+        match self.previous.as_deref() {
+            Some(previous) if self.repeats > 1 => self.inner.writeln(&format!("{}× {previous}", self.repeats)),
+            Some(previous) => self.inner.writeln(previous),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: OutputWriter> OutputWriter for UniqueOutputWriter<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        if self.previous.as_deref() == Some(txt) {
+            self.repeats += 1;
+            return Ok(());
+        }
+        self.flush_previous()?;
+
+        if !self.count {
+            self.inner.writeln(txt)?;
+        }
+        match self.previous.as_mut() {
+            Some(previous) => {
+                previous.clear();
+                previous.push_str(txt);
+            }
+            None => self.previous = Some(txt.to_string()),
+        }
+        self.repeats = 1;
+        Ok(())
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.inner.flush_buffered()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.inner.flush_deadline()
+    }
+}
+
+impl<W: OutputWriter> SortedOutputWriter<W> {
+    pub fn new(inner: W, reverse: bool) -> Self {
+        Self {
+            inner,
+            records: Vec::new(),
+            reverse,
+        }
+    }
+
+    /// Sorts and writes all of the held back output.
+    fn flush_sorted(&mut self) -> Result<()> {
+        let mut records = std::mem::take(&mut self.records);
+
+        records.sort_by(|(a_key, a), (b_key, b)| {
+            let a = a_key.as_deref().unwrap_or(a);
+            let b = b_key.as_deref().unwrap_or(b);
+            if self.reverse {
+                b.cmp(a)
+            } else {
+                a.cmp(b)
+            }
+        });
+
+        for (_, txt) in records {
+            self.inner.writeln(&txt)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: OutputWriter> OutputWriter for SortedOutputWriter<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        self.records.push((None, txt.to_string()));
+        Ok(())
+    }
+
+    fn writeln_keyed(&mut self, key: &str, txt: &str) -> Result<()> {
+        self.records.push((Some(key.to_string()), txt.to_string()));
+        Ok(())
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.inner.flush_buffered()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.inner.flush_deadline()
+    }
+}
+
+impl<W: OutputWriter> AlignedOutputWriter<W> {
+    pub fn new<S: AsRef<str>>(inner: W, separators: &[S]) -> Self {
+        Self {
+            inner,
+            rows: Vec::new(),
+            separators: separators.iter().map(|s| s.as_ref().to_string()).collect(),
+        }
+    }
+
+    /// Pads the columns of all of the held back output and writes it.
+    fn flush_aligned(&mut self) -> Result<()> {
+        let rows = std::mem::take(&mut self.rows);
+        let mut widths: Vec<usize> = Vec::new();
+
+        for row in &rows {
+            for (i, column) in row.split(COLUMN_DELIMITER).enumerate() {
+                let width = display_width(column);
+                match widths.get_mut(i) {
+                    Some(max) => *max = (*max).max(width),
+                    None => widths.push(width),
+                }
+            }
+        }
+
+        let mut out = String::new();
+        for row in &rows {
+            out.clear();
+            let columns = row.split(COLUMN_DELIMITER).collect::<Vec<_>>();
+
+            for (i, column) in columns.iter().enumerate() {
+                out.push_str(column);
+                if i + 1 == columns.len() {
+                    break;
+                }
+                let padding = widths[i] - display_width(column);
+                out.extend(std::iter::repeat_n(' ', padding));
+
+                let separator = self.separators.get(i).or(self.separators.last());
+                out.push_str(separator.map_or("", String::as_str));
+            }
+            self.inner.writeln(&out)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: OutputWriter> OutputWriter for AlignedOutputWriter<W> {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        self.rows.push(txt.to_string());
+        Ok(())
+    }
+
+    fn flush_buffered(&mut self) -> Result<()> {
+        self.inner.flush_buffered()
+    }
+
+    fn flush_deadline(&self) -> Option<Instant> {
+        self.inner.flush_deadline()
+    }
+}
+
+impl<W: OutputWriter> Drop for AlignedOutputWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_aligned() {
+            log::warn!("failed to write output of aligned output writer before dropping: {e}");
+        }
+    }
+}
+
+impl<W: OutputWriter> Drop for SortedOutputWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_sorted() {
+            log::warn!("failed to write output of sorted output writer before dropping: {e}");
+        }
+    }
+}
+
+impl<W: OutputWriter> Drop for UniqueOutputWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_previous() {
+            log::warn!("failed to write final output of unique output writer before dropping: {e}");
+        }
+    }
+}
+
+impl<W: Write> Drop for BlockBufferedOutputWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_buffer() {
+            log::warn!(
+                "failed to flush buffer of block buffered output writer before dropping: {}",
+                e
+            );
+        }
+    }
+}
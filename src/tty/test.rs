@@ -0,0 +1,401 @@
+use super::{
+    color_enabled_for, is_broken_pipe, open_output_file, AlignedOutputWriter, BlockBufferedOutputWriter,
+    LineBufferedOutputWriter, OutputWriter, SortedOutputWriter, UniqueOutputWriter, COLUMN_DELIMITER,
+};
+use crate::cli::ColorChoice;
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Collects output in memory.
+#[derive(Default, Clone)]
+struct MemoryWriter(Rc<RefCell<Vec<String>>>);
+
+impl OutputWriter for MemoryWriter {
+    fn writeln(&mut self, txt: &str) -> Result<()> {
+        self.0.borrow_mut().push(txt.to_string());
+        Ok(())
+    }
+}
+
+fn write_all(writer: &mut impl OutputWriter, lines: &[&str]) {
+    for line in lines {
+        writer.writeln(line).unwrap();
+    }
+}
+
+#[test]
+fn test_unique_output_writer() {
+    let output = MemoryWriter::default();
+    let mut writer = UniqueOutputWriter::new(output.clone(), false);
+
+    write_all(&mut writer, &["a", "a", "b", "a", "a", "a", "c"]);
+    assert_eq!(*output.0.borrow(), vec!["a", "b", "a", "c"]);
+
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["a", "b", "a", "c"]);
+}
+
+#[test]
+fn test_unique_output_writer_count() {
+    let output = MemoryWriter::default();
+    let mut writer = UniqueOutputWriter::new(output.clone(), true);
+
+    write_all(&mut writer, &["a", "a", "b", "a", "a", "a", "c", "c"]);
+    assert_eq!(*output.0.borrow(), vec!["2× a", "b", "3× a"]);
+
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["2× a", "b", "3× a", "2× c"]);
+}
+
+#[test]
+fn test_sorted_output_writer() {
+    let output = MemoryWriter::default();
+    let mut writer = SortedOutputWriter::new(output.clone(), false);
+
+    write_all(&mut writer, &["b", "c", "a", "b"]);
+    assert!(output.0.borrow().is_empty());
+
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["a", "b", "b", "c"]);
+}
+
+#[test]
+fn test_aligned_output_writer() {
+    let output = MemoryWriter::default();
+    let mut writer = AlignedOutputWriter::new(output.clone(), &["  "]);
+
+    let rows = [
+        vec!["[info]", "started", "alice"],
+        vec!["[warning]", "日本語", "bob"],
+        vec!["\x1b[31m[error]\x1b[0m", "slow"],
+        vec!["--"],
+    ];
+    for row in &rows {
+        writer.writeln(&row.join(&COLUMN_DELIMITER.to_string())).unwrap();
+    }
+    assert!(output.0.borrow().is_empty());
+
+    drop(writer);
+    assert_eq!(
+        *output.0.borrow(),
+        vec![
+            "[info]     started  alice",
+            "[warning]  日本語   bob",
+            "\x1b[31m[error]\x1b[0m    slow",
+            "--",
+        ]
+    );
+
+    let output = MemoryWriter::default();
+    let mut writer = AlignedOutputWriter::new(output.clone(), &[" | ", ","]);
+    write_all(&mut writer, &["a\x1fbb\x1fc\x1fd", "aaa\x1fb\x1fc\x1fd"]);
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["a   | bb,c,d", "aaa | b ,c,d"]);
+}
+
+#[test]
+fn test_sorted_output_writer_keyed() {
+    let output = MemoryWriter::default();
+    let mut writer = SortedOutputWriter::new(output.clone(), true);
+
+    writer.writeln_keyed("2", "x").unwrap();
+    writer.writeln_keyed("3", "y").unwrap();
+    writer.writeln_keyed("2", "z").unwrap();
+    writer.writeln_keyed("1", "w").unwrap();
+
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["y", "x", "z", "w"]);
+}
+
+#[test]
+fn test_sorted_unique_output_writer() {
+    let output = MemoryWriter::default();
+    let mut writer = SortedOutputWriter::new(UniqueOutputWriter::new(output.clone(), true), false);
+
+    write_all(&mut writer, &["b", "a", "b", "a", "c"]);
+    drop(writer);
+    assert_eq!(*output.0.borrow(), vec!["2× a", "2× b", "c"]);
+}
+
+#[test]
+fn test_block_buffered_output_writer_buffer_size() {
+    let mut output = Vec::new();
+    let mut writer = BlockBufferedOutputWriter::new(&mut output).with_buffer_size(8);
+
+    write_all(&mut writer, &["abc", "de"]);
+    drop(writer);
+    assert_eq!(output, b"abc\nde\n");
+
+    // Records larger than the buffer are written in their entirety.
+    let long = "x".repeat(20);
+    let mut output = Vec::new();
+    let mut writer = BlockBufferedOutputWriter::new(&mut output).with_buffer_size(8);
+
+    write_all(&mut writer, &["abc", &long, "de"]);
+    drop(writer);
+    assert_eq!(output, format!("abc\n{long}\nde\n").into_bytes());
+}
+
+#[test]
+fn test_line_buffered_output_writer() {
+    let mut output = Vec::new();
+    let mut writer = LineBufferedOutputWriter::new(&mut output).with_trailing_terminator(false);
+    write_all(&mut writer, &["abc", "de"]);
+    assert_eq!(output, b"abc\nde");
+}
+
+/// Records each call to `write` and `flush` so that tests can assert on when output reaches the
+/// underlying writer rather than only on what it ends up containing.
+#[derive(Default, Clone)]
+struct RecordingWriter(Rc<RefCell<Vec<Call>>>);
+
+#[derive(Debug, PartialEq)]
+enum Call {
+    Write(Vec<u8>),
+    Flush,
+}
+
+impl Write for RecordingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().push(Call::Write(buf.to_vec()));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.borrow_mut().push(Call::Flush);
+        Ok(())
+    }
+}
+
+fn write(bytes: &[u8]) -> Call {
+    Call::Write(bytes.to_vec())
+}
+
+#[test]
+fn test_block_buffered_output_writer_block_boundaries() {
+    let output = RecordingWriter::default();
+    let mut writer = BlockBufferedOutputWriter::new(output.clone()).with_buffer_size(8);
+
+    // Records accumulate until the next one would overflow the buffer.
+    write_all(&mut writer, &["abc", "de"]);
+    assert!(output.0.borrow().is_empty());
+
+    write_all(&mut writer, &["f"]);
+    assert_eq!(*output.0.borrow(), vec![write(b"abc\nde\n")]);
+
+    // A buffer that is filled exactly is written straight away.
+    write_all(&mut writer, &["ghijk"]);
+    assert_eq!(*output.0.borrow(), vec![write(b"abc\nde\n"), write(b"f\nghijk\n")]);
+
+    // Whatever remains is written when dropped.
+    write_all(&mut writer, &["l"]);
+    drop(writer);
+    assert_eq!(
+        *output.0.borrow(),
+        vec![write(b"abc\nde\n"), write(b"f\nghijk\n"), write(b"l\n")]
+    );
+}
+
+#[test]
+fn test_block_buffered_output_writer_flush_interval() {
+    let output = RecordingWriter::default();
+    let mut writer = BlockBufferedOutputWriter::new(output.clone())
+        .with_buffer_size(64)
+        .with_flush_interval(Some(Duration::from_millis(20)));
+
+    // Nothing is due while nothing is buffered.
+    assert_eq!(writer.flush_deadline(), None);
+
+    // Records written within the interval are held back as usual.
+    let buffered_at = Instant::now();
+    write_all(&mut writer, &["abc"]);
+    assert!(output.0.borrow().is_empty());
+
+    // The deadline is the interval after the oldest buffered record was written.
+    let deadline = writer.flush_deadline().unwrap();
+    assert!(deadline >= buffered_at + Duration::from_millis(20));
+    write_all(&mut writer, &["de"]);
+    assert_eq!(writer.flush_deadline(), Some(deadline));
+
+    // The first record written after the deadline flushes everything buffered so far.
+    thread::sleep(Duration::from_millis(30));
+    write_all(&mut writer, &["f"]);
+    assert_eq!(*output.0.borrow(), vec![write(b"abc\nde\nf\n"), Call::Flush]);
+    assert_eq!(writer.flush_deadline(), None);
+
+    // Buffered output can also be flushed before it's due, e.g. while waiting for input.
+    let mut writer = UniqueOutputWriter::new(writer, false);
+    write_all(&mut writer, &["g"]);
+    assert!(writer.flush_deadline().is_some());
+    writer.flush_buffered().unwrap();
+    assert_eq!(output.0.borrow()[2..], [write(b"g\n"), Call::Flush]);
+    assert_eq!(writer.flush_deadline(), None);
+}
+
+#[test]
+fn test_block_buffered_output_writer_terminators() {
+    let mut output = Vec::new();
+    let mut writer = BlockBufferedOutputWriter::new(&mut output)
+        .with_terminator(b'\0')
+        .with_trailing_terminator(false)
+        .with_buffer_size(4);
+
+    write_all(&mut writer, &["abc", "de", "f"]);
+    drop(writer);
+    assert_eq!(output, b"abc\0de\0f");
+}
+
+#[test]
+fn test_line_buffered_output_writer_flushes() {
+    // Newline-terminated records are left to the underlying writer to flush.
+    let output = RecordingWriter::default();
+    let mut writer = LineBufferedOutputWriter::new(output.clone());
+    write_all(&mut writer, &["a", "b"]);
+    assert_eq!(
+        *output.0.borrow(),
+        vec![write(b"a"), write(b"\n"), write(b"b"), write(b"\n")]
+    );
+
+    // Otherwise every record is flushed explicitly.
+    let output = RecordingWriter::default();
+    let mut writer = LineBufferedOutputWriter::new(output.clone()).with_flush_each(true);
+    write_all(&mut writer, &["a"]);
+    assert_eq!(*output.0.borrow(), vec![write(b"a"), write(b"\n"), Call::Flush]);
+
+    let output = RecordingWriter::default();
+    let mut writer = LineBufferedOutputWriter::new(output.clone()).with_terminator(b'\0');
+    write_all(&mut writer, &["a", "b"]);
+    assert_eq!(
+        *output.0.borrow(),
+        vec![
+            write(b"a"),
+            write(b"\0"),
+            Call::Flush,
+            write(b"b"),
+            write(b"\0"),
+            Call::Flush
+        ]
+    );
+}
+
+#[test]
+fn test_open_output_file() {
+    let path = std::env::temp_dir().join(format!("grits-output-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    for (append, expected) in [(false, "a\n"), (true, "a\nb\n"), (false, "c\n")] {
+        let txt = &expected[expected.len() - 2..expected.len() - 1];
+        let file = open_output_file(path, append).unwrap().unwrap();
+        let mut writer = BlockBufferedOutputWriter::new(file);
+        write_all(&mut writer, &[txt]);
+        drop(writer);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), expected);
+    }
+    std::fs::remove_file(path).unwrap();
+
+    assert!(open_output_file("-", false).unwrap().is_none());
+}
+
+/// A pipe whose reading end was closed after `capacity` bytes were read.
+struct ClosedPipe {
+    capacity: usize,
+}
+
+impl Write for ClosedPipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.capacity == 0 {
+            return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+        }
+        let n = buf.len().min(self.capacity);
+        self.capacity -= n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_broken_pipe() {
+    let mut writer = BlockBufferedOutputWriter::new(ClosedPipe { capacity: 4 }).with_buffer_size(4);
+
+    writer.writeln("abc").unwrap();
+    let err = writer.writeln("def").unwrap_err();
+    assert!(is_broken_pipe(&err));
+
+    let err = anyhow::Error::from(io::Error::from(io::ErrorKind::PermissionDenied)).context("failed to write");
+    assert!(!is_broken_pipe(&err));
+}
+
+#[test]
+fn test_block_buffered_output_writer_no_trailing_terminator() {
+    let mut output = Vec::new();
+    let mut writer = BlockBufferedOutputWriter::new(&mut output)
+        .with_buffer_size(4)
+        .with_trailing_terminator(false);
+
+    write_all(&mut writer, &["abc", "de", "f"]);
+    drop(writer);
+    assert_eq!(output, b"abc\nde\nf");
+
+    let mut output = Vec::new();
+    let mut writer = BlockBufferedOutputWriter::new(&mut output)
+        .with_terminator(b'\0')
+        .with_trailing_terminator(false);
+
+    write_all(&mut writer, &["abc"]);
+    drop(writer);
+    assert_eq!(output, b"abc");
+}
+
+#[test]
+fn test_color_enabled_for() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, val)| val.to_string())
+        }
+    };
+
+    assert!(color_enabled_for(ColorChoice::Auto, true, env(&[])));
+    assert!(!color_enabled_for(ColorChoice::Auto, false, env(&[])));
+    assert!(color_enabled_for(ColorChoice::Always, false, env(&[("NO_COLOR", "1")])));
+    assert!(!color_enabled_for(
+        ColorChoice::Never,
+        true,
+        env(&[("CLICOLOR_FORCE", "1")])
+    ));
+
+    // NO_COLOR takes precedence over CLICOLOR_FORCE which takes precedence over CLICOLOR.
+    assert!(!color_enabled_for(
+        ColorChoice::Auto,
+        true,
+        env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])
+    ));
+    assert!(color_enabled_for(
+        ColorChoice::Auto,
+        false,
+        env(&[("NO_COLOR", ""), ("CLICOLOR_FORCE", "1")])
+    ));
+    assert!(color_enabled_for(
+        ColorChoice::Auto,
+        false,
+        env(&[("CLICOLOR_FORCE", "1"), ("CLICOLOR", "0")])
+    ));
+    assert!(!color_enabled_for(
+        ColorChoice::Auto,
+        false,
+        env(&[("CLICOLOR_FORCE", "0")])
+    ));
+    assert!(!color_enabled_for(ColorChoice::Auto, true, env(&[("CLICOLOR", "0")])));
+    assert!(color_enabled_for(ColorChoice::Auto, true, env(&[("CLICOLOR", "1")])));
+    assert!(!color_enabled_for(ColorChoice::Auto, false, env(&[("CLICOLOR", "1")])));
+}
@@ -0,0 +1,131 @@
+use grits::{RequireMode, Transformer};
+
+#[test]
+fn test_transform_line() {
+    let transformer = Transformer::new(&[r"level=(?<lvl>\w+)", r"ip=(?<ip>[^ ]+)"], &["{lvl}: {ip[*]}"]).unwrap();
+
+    let out = transformer.transform_line("level=info ip=127.0.0.1 ip=10.0.0.1");
+    assert_eq!(out.as_deref(), Some("info: 127.0.0.1,10.0.0.1"));
+
+    let out = transformer.transform_line("level=info");
+    assert_eq!(out.as_deref(), Some("info: "));
+}
+
+#[test]
+fn test_transform_line_multiple_templates() {
+    let transformer = Transformer::new(&[r"level=(?<lvl>\w+)", r"msg=(?<msg>\w+)"], &["[{lvl}]", "{msg}"])
+        .unwrap()
+        .with_separator(" ");
+
+    let out = transformer.transform_line("level=warn msg=disk");
+    assert_eq!(out.as_deref(), Some("[warn] disk"));
+}
+
+#[test]
+fn test_transform_line_dotted_capture_names() {
+    let transformer = Transformer::new(&[r"code (?<http.status>\d+)"], &["status={http.status}"]).unwrap();
+    assert_eq!(transformer.transform_line("code 200").as_deref(), Some("status=200"));
+
+    let transformer = Transformer::new(&[r"code (?<http_status>\d+)"], &["status={http.status}"]).unwrap();
+    assert_eq!(transformer.transform_line("code 200").as_deref(), Some("status=200"));
+}
+
+#[test]
+fn test_transform_line_no_output() {
+    let transformer = Transformer::new(&[r"level=(?<lvl>\w+)"], &["{!lvl}"]).unwrap();
+    assert!(transformer.transform_line("msg=foo").is_none());
+}
+
+#[test]
+fn test_transform_line_require() {
+    let transformer = Transformer::new(&[r"level=(?<lvl>\w+)", r"msg=(?<msg>\w+)"], &["{lvl} {msg}"])
+        .unwrap()
+        .with_require(&["lvl", "msg"], RequireMode::Any);
+
+    assert!(transformer.transform_line("msg=foo").is_some());
+    assert!(transformer.transform_line("foo").is_none());
+
+    let transformer = transformer.with_require(&["lvl", "msg"], RequireMode::All);
+    assert!(transformer.transform_line("msg=foo").is_none());
+    assert!(transformer.transform_line("level=info msg=foo").is_some());
+}
+
+#[test]
+fn test_transformer_errors() {
+    assert!(Transformer::new(&[r"level=(\w+)"], &["{lvl}"]).is_err());
+    assert!(Transformer::new(&[r"level=(?<lvl>\w+"], &["{lvl}"]).is_err());
+    assert!(Transformer::new(&[r"level=(?<lvl>\w+)"], &["{lvl[x]}"]).is_err());
+}
+
+#[test]
+fn test_capture_line() {
+    let transformer = Transformer::new(&[r"level=(?<lvl>\w+)", r"ip=(?<ip>[^ ]+)"], &["{lvl}"]).unwrap();
+
+    let captures = {
+        let line = String::from("ip=127.0.0.1 ip=10.0.0.1");
+        transformer.capture_line(&line)
+    };
+    assert_eq!(captures.len(), 2);
+    assert_eq!(captures["ip"], vec!["127.0.0.1", "10.0.0.1"]);
+    assert!(captures["lvl"].is_empty());
+}
+
+#[test]
+fn test_prefilter() {
+    let patterns = [
+        r"level=(?<lvl>\w+)",
+        r"ip=(?<ip>[^ ]+)",
+        r"level=(?<lvl>\w+) ip=(?<ip>[^ ]+)",
+    ];
+    let templates = ["{lvl[*]}|{ip[*]}"];
+
+    let with_prefilter = Transformer::new(&patterns, &templates).unwrap();
+    let without_prefilter = Transformer::new(&patterns, &templates).unwrap().with_prefilter(false);
+
+    for line in ["level=info ip=127.0.0.1", "ip=10.0.0.1", "level=warn", "foo"] {
+        assert_eq!(
+            with_prefilter.transform_line(line),
+            without_prefilter.transform_line(line)
+        );
+    }
+    assert_eq!(
+        with_prefilter.transform_line("level=info ip=127.0.0.1"),
+        Some(String::from("info,info|127.0.0.1,127.0.0.1"))
+    );
+}
+
+#[test]
+fn test_transform_lines() {
+    let transformer = Transformer::new(
+        &[r"level=(?<lvl>\w+)", r"ip=(?<ip>[^ ]+)"],
+        &["{lvl || \"NONE\"} {ip[*]}"],
+    )
+    .unwrap()
+    .with_require(&["ip"], RequireMode::All);
+
+    let lines = [
+        "level=info ip=127.0.0.1 ip=10.0.0.1 ip=192.168.0.1",
+        "ip=10.0.0.1",
+        "level=warn",
+        "level=error ip=127.0.0.1",
+    ];
+    let expected = lines.iter().map(|l| transformer.transform_line(l)).collect::<Vec<_>>();
+
+    assert_eq!(transformer.transform_lines(lines).collect::<Vec<_>>(), expected);
+    assert_eq!(
+        expected,
+        vec![
+            Some(String::from("info 127.0.0.1,10.0.0.1,192.168.0.1")),
+            Some(String::from("NONE 10.0.0.1")),
+            None,
+            Some(String::from("error 127.0.0.1")),
+        ]
+    );
+}
+
+#[test]
+fn test_positional_groups() {
+    let transformer = Transformer::new(&[r"(\w+)=(\d+)"], &["{$1[*]} {$2[-1]}"]).unwrap();
+    assert_eq!(transformer.transform_line("a=1 b=2"), Some(String::from("a,b 2")));
+    assert_eq!(transformer.transform_line("a b"), Some(String::from(" ")));
+}